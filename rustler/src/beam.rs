@@ -0,0 +1,558 @@
+//! Running a NIF library in a real BEAM from `cargo test`, behind the `beam` feature.
+//!
+//! Behaviour that depends on the VM itself, such as garbage collection of resources or messages
+//! sent to other processes, can not be tested with `rustler::testing::with_env`. A [`Beam`]
+//! builds the NIF crate as a `cdylib`, starts an `erl` node, loads the library into its module and
+//! evaluates Erlang expressions in it:
+//!
+//! ```ignore
+//! use rustler::beam::Builder;
+//! use rustler::types::value::ToValue;
+//!
+//! #[test]
+//! #[ignore = "needs erl, run with `cargo test -- --ignored`"]
+//! fn adds_in_the_beam() {
+//!     let mut beam = Builder::new(env!("CARGO_MANIFEST_DIR"), &math::RUSTLER_NIF_MODULE)
+//!         .start()
+//!         .unwrap();
+//!
+//!     assert_eq!(beam.call("add", &[1.to_value(), 2.to_value()]).unwrap(), 3.to_value());
+//!     assert_eq!(beam.eval("'Elixir.Math':add(1, 2) * 2").unwrap(), 6.to_value());
+//! }
+//! ```
+//!
+//! Unlike the `testing` feature, the `beam` feature does not replace the NIF API, so both the test
+//! and the library can enable it. The library is built with `cargo build --lib` in a separate
+//! target directory, since the one of `cargo test` is locked while tests run. `cargo build` leaves
+//! out dev-dependencies, so with the version 2 feature resolver the `testing` feature of rustler is
+//! not enabled for the library; otherwise the library fails to build. Each node has a module with a stub for each NIF, generated
+//! from the `RUSTLER_NIF_MODULE` of the crate, so Elixir modules can be tested without Elixir.
+//!
+//! Expressions are evaluated one after the other in the same process, so messages sent to
+//! `self()` by a NIF can be received by the next expression. Results are returned as
+//! [`Value`]s. `erl` has to be in the `PATH`, so these tests are best marked `#[ignore]` and run
+//! with `cargo test -- --ignored` where Erlang is installed.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::etf;
+use crate::stubs::{self, NifModule};
+use crate::types::value::ToValue;
+use crate::Value;
+
+/// The Erlang module running in the node, which evaluates the requests sent over its socket.
+const DRIVER: &str = r#"-module(rustler_beam).
+-export([start/1]).
+
+start([_Dir, Port]) ->
+    Options = [binary, {packet, 4}, {active, false}, {nodelay, true}],
+    {ok, Socket} = gen_tcp:connect({127, 0, 0, 1}, list_to_integer(Port), Options),
+    loop(Socket).
+
+loop(Socket) ->
+    case gen_tcp:recv(Socket, 0) of
+        {ok, Request} ->
+            Reply = handle(binary_to_term(Request)),
+            ok = gen_tcp:send(Socket, term_to_binary(Reply)),
+            loop(Socket);
+        {error, _} ->
+            halt()
+    end.
+
+handle({load, File, Library, LoadInfo}) ->
+    case compile:file(binary_to_list(File), [binary, return_errors]) of
+        {ok, Module, Binary} ->
+            {module, Module} = code:load_binary(Module, binary_to_list(File), Binary),
+            case Module:'$rustler_load'(binary_to_list(Library), LoadInfo) of
+                ok ->
+                    put(module, Module),
+                    {ok, Module};
+                {error, {_, Text}} ->
+                    {error, unicode:characters_to_binary(Text)}
+            end;
+        {error, Errors, _} ->
+            {error, format("~p", [Errors])}
+    end;
+handle({eval, Source}) ->
+    run(fun() ->
+        {value, Value, _} = erl_eval:exprs(parse(binary_to_list(Source)), erl_eval:new_bindings()),
+        Value
+    end);
+handle({call, Function, Args}) ->
+    run(fun() -> apply(get(module), binary_to_atom(Function, utf8), Args) end).
+
+run(Fun) ->
+    try
+        {ok, Fun()}
+    catch
+        Class:Reason ->
+            {exception, Class, Reason, format("~p: ~p", [Class, Reason])}
+    end.
+
+parse(Source) ->
+    case erl_scan:string(Source) of
+        {ok, Tokens, _} ->
+            case erl_parse:parse_exprs(Tokens) of
+                {ok, Exprs} -> Exprs;
+                {error, {_, Module, Description}} -> syntax_error(Module, Description)
+            end;
+        {error, {_, Module, Description}, _} ->
+            syntax_error(Module, Description)
+    end.
+
+syntax_error(Module, Description) ->
+    error({syntax_error, unicode:characters_to_binary(Module:format_error(Description))}).
+
+format(Format, Args) ->
+    unicode:characters_to_binary(io_lib:format(Format, Args)).
+"#;
+
+/// Compiles and starts the driver, with the work directory and the port to connect to as plain
+/// arguments.
+const BOOT: &str = "[Dir | _] = Args = init:get_plain_arguments(), \
+    File = filename:join(Dir, \"rustler_beam.erl\"), \
+    {ok, Module, Binary} = compile:file(File, [binary, report]), \
+    {module, Module} = code:load_binary(Module, File, Binary), \
+    Module:start(Args).";
+
+/// An error of a [`Beam`].
+#[derive(Debug)]
+pub enum Error {
+    /// Running `cargo` or `erl`, or talking to the node, failed.
+    Io(io::Error),
+    /// The crate could not be built as a `cdylib`. Contains the output of `cargo`.
+    Build(String),
+    /// The node did not start or the library could not be loaded.
+    Load(String),
+    /// Evaluating an expression raised an exception.
+    Exception {
+        /// `error`, `exit` or `throw`.
+        class: String,
+        reason: Value,
+        /// The exception formatted by the node.
+        message: String,
+    },
+    /// The node sent something that is not a valid reply.
+    Protocol(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Build(output) => write!(f, "failed to build the NIF library:\n{}", output),
+            Error::Load(reason) => write!(f, "failed to load the NIF library: {}", reason),
+            Error::Exception { message, .. } => write!(f, "exception {}", message),
+            Error::Protocol(reason) => write!(f, "invalid reply from the node: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Configures how the NIF crate is built and loaded into a node.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    crate_dir: PathBuf,
+    module: NifModule,
+    release: bool,
+    features: Vec<String>,
+    load_info: Value,
+    erl: PathBuf,
+    timeout: Duration,
+}
+
+impl Builder {
+    /// Creates a builder for the NIF crate in `crate_dir`, usually `env!("CARGO_MANIFEST_DIR")`,
+    /// whose `rustler::init!` generated `module`.
+    pub fn new<P: AsRef<Path>>(crate_dir: P, module: &NifModule) -> Self {
+        Builder {
+            crate_dir: crate_dir.as_ref().to_path_buf(),
+            module: *module,
+            release: false,
+            features: Vec::new(),
+            load_info: Value::Integer(0),
+            erl: PathBuf::from("erl"),
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets whether the library is built in release mode. Defaults to `false`.
+    pub fn release(mut self, release: bool) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// Enables a feature of the NIF crate when building the library.
+    pub fn feature(mut self, feature: &str) -> Self {
+        self.features.push(feature.to_string());
+        self
+    }
+
+    /// Sets the term passed to the `load` function of the library. Defaults to `0`, like
+    /// `use Rustler`.
+    pub fn load_info<T: ToValue + ?Sized>(mut self, load_info: &T) -> Self {
+        self.load_info = load_info.to_value();
+        self
+    }
+
+    /// Sets the path of `erl`. Defaults to the one in the `PATH`.
+    pub fn erl<P: AsRef<Path>>(mut self, erl: P) -> Self {
+        self.erl = erl.as_ref().to_path_buf();
+        self
+    }
+
+    /// Sets how long to wait for the node to start and for each reply. Defaults to 60 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Builds the library, starts a node and loads the library into it.
+    pub fn start(&self) -> Result<Beam, Error> {
+        let library = self.build()?;
+
+        static NODES: AtomicUsize = AtomicUsize::new(0);
+        let dir = target_dir().join("rustler_beam").join(format!(
+            "{}-{}",
+            std::process::id(),
+            NODES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+
+        fs::copy(&library, dir.join("nif.so"))?;
+        fs::write(dir.join("rustler_beam.erl"), DRIVER)?;
+        let stub = dir.join(format!("{}.erl", self.module.name));
+        fs::write(&stub, self.stub())?;
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let mut child = Command::new(&self.erl)
+            .args(["-noshell", "-noinput", "-eval", BOOT, "-extra"])
+            .arg(&dir)
+            .arg(listener.local_addr()?.port().to_string())
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stderr = Arc::new(Mutex::new(String::new()));
+        if let Some(mut pipe) = child.stderr.take() {
+            let stderr = Arc::clone(&stderr);
+            thread::spawn(move || {
+                let mut buffer = [0; 1024];
+                loop {
+                    match pipe.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            let output = String::from_utf8_lossy(&buffer[..n]);
+                            eprint!("{}", output);
+                            stderr.lock().unwrap().push_str(&output);
+                        }
+                    }
+                }
+            });
+        }
+
+        let mut beam = Beam {
+            child,
+            stream: None,
+            dir,
+            stderr,
+            timeout: self.timeout,
+        };
+        beam.stream = Some(beam.accept(&listener)?);
+
+        let request = Value::Tuple(vec![
+            Value::Atom("load".to_string()),
+            Value::Binary(path_bytes(&stub)),
+            Value::Binary(path_bytes(&beam.dir.join("nif"))),
+            self.load_info.clone(),
+        ]);
+        match beam.request(&request)? {
+            Reply::Ok(_) => Ok(beam),
+            Reply::Error(reason) => Err(Error::Load(reason)),
+            Reply::Exception { message, .. } => Err(Error::Load(message)),
+        }
+    }
+
+    /// Runs `cargo build` and returns the path of the `cdylib`.
+    fn build(&self) -> Result<PathBuf, Error> {
+        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let mut command = Command::new(cargo);
+        command
+            .args(["build", "--lib", "--message-format=json-render-diagnostics"])
+            .arg("--manifest-path")
+            .arg(self.crate_dir.join("Cargo.toml"))
+            .arg("--target-dir")
+            .arg(target_dir().join("rustler_beam").join("target"));
+        if self.release {
+            command.arg("--release");
+        }
+        if !self.features.is_empty() {
+            command.arg("--features").arg(self.features.join(","));
+        }
+
+        let output = command.stdin(Stdio::null()).output()?;
+        if !output.status.success() {
+            return Err(Error::Build(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+
+        // The crate itself is built last, after any dependency that is a `cdylib` too.
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .rev()
+            .filter(|line| line.contains("\"reason\":\"compiler-artifact\""))
+            .find_map(cdylib_artifact)
+            .ok_or_else(|| {
+                Error::Build(format!(
+                    "`crate-type` of {} does not include \"cdylib\"",
+                    self.crate_dir.display()
+                ))
+            })
+    }
+
+    /// The source of the module the library is loaded into.
+    fn stub(&self) -> String {
+        stubs::Erlang::new()
+            .specs(false)
+            .preamble("-export(['$rustler_load'/2]).")
+            .preamble("")
+            .preamble("'$rustler_load'(Path, LoadInfo) ->\n    erlang:load_nif(Path, LoadInfo).")
+            .generate(&self.module)
+    }
+}
+
+/// An `erl` node with a NIF library loaded, which is stopped when dropped.
+#[derive(Debug)]
+pub struct Beam {
+    child: Child,
+    stream: Option<TcpStream>,
+    dir: PathBuf,
+    stderr: Arc<Mutex<String>>,
+    timeout: Duration,
+}
+
+enum Reply {
+    Ok(Value),
+    Error(String),
+    Exception {
+        class: String,
+        reason: Value,
+        message: String,
+    },
+}
+
+impl Beam {
+    /// Evaluates a sequence of Erlang expressions and returns the value of the last one. The
+    /// final `.` can be left out.
+    pub fn eval(&mut self, expr: &str) -> Result<Value, Error> {
+        let expr = expr.trim_end();
+        let source = if expr.ends_with('.') {
+            expr.to_string()
+        } else {
+            format!("{}.", expr)
+        };
+
+        let request = Value::Tuple(vec![
+            Value::Atom("eval".to_string()),
+            Value::Binary(source.into_bytes()),
+        ]);
+        self.reply(&request)
+    }
+
+    /// Calls `function` of the module the library is loaded into with `args`.
+    pub fn call(&mut self, function: &str, args: &[Value]) -> Result<Value, Error> {
+        let request = Value::Tuple(vec![
+            Value::Atom("call".to_string()),
+            Value::Binary(function.as_bytes().to_vec()),
+            Value::List(args.to_vec()),
+        ]);
+        self.reply(&request)
+    }
+
+    fn reply(&mut self, request: &Value) -> Result<Value, Error> {
+        match self.request(request)? {
+            Reply::Ok(value) => Ok(value),
+            Reply::Error(reason) => Err(Error::Protocol(reason)),
+            Reply::Exception {
+                class,
+                reason,
+                message,
+            } => Err(Error::Exception {
+                class,
+                reason,
+                message,
+            }),
+        }
+    }
+
+    /// Waits for the driver to connect, failing early if `erl` exits.
+    fn accept(&mut self, listener: &TcpListener) -> Result<TcpStream, Error> {
+        listener.set_nonblocking(true)?;
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(self.timeout))?;
+                    return Ok(stream);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            if let Some(status) = self.child.try_wait()? {
+                // Give the stderr thread a moment to collect the last output.
+                thread::sleep(Duration::from_millis(100));
+                return Err(Error::Load(format!(
+                    "erl exited with {}\n{}",
+                    status,
+                    self.stderr.lock().unwrap()
+                )));
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Load(
+                    "timed out waiting for erl to start".to_string(),
+                ));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn request(&mut self, request: &Value) -> Result<Reply, Error> {
+        let stream = self.stream.as_mut().expect("the node is connected");
+
+        let payload = etf::encode(request);
+        stream.write_all(&(payload.len() as u32).to_be_bytes())?;
+        stream.write_all(&payload)?;
+
+        let mut length = [0; 4];
+        stream.read_exact(&mut length)?;
+        let mut payload = vec![0; u32::from_be_bytes(length) as usize];
+        stream.read_exact(&mut payload)?;
+
+        let reply = etf::decode(&payload).map_err(|err| Error::Protocol(err.to_string()))?;
+        parse_reply(reply)
+    }
+}
+
+impl Drop for Beam {
+    fn drop(&mut self) {
+        self.stream = None;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn parse_reply(reply: Value) -> Result<Reply, Error> {
+    let mut items = match reply {
+        Value::Tuple(items) => items.into_iter(),
+        other => return Err(Error::Protocol(format!("{:?}", other))),
+    };
+
+    match (items.next(), items.next(), items.next(), items.next()) {
+        (Some(Value::Atom(tag)), Some(value), None, None) if tag == "ok" => Ok(Reply::Ok(value)),
+        (Some(Value::Atom(tag)), Some(Value::Binary(reason)), None, None) if tag == "error" => {
+            Ok(Reply::Error(String::from_utf8_lossy(&reason).into_owned()))
+        }
+        (
+            Some(Value::Atom(tag)),
+            Some(Value::Atom(class)),
+            Some(reason),
+            Some(Value::Binary(message)),
+        ) if tag == "exception" => Ok(Reply::Exception {
+            class,
+            reason,
+            message: String::from_utf8_lossy(&message).into_owned(),
+        }),
+        other => Err(Error::Protocol(format!("{:?}", other))),
+    }
+}
+
+/// Returns the path of the `cdylib` in a `compiler-artifact` message of cargo, if any.
+fn cdylib_artifact(message: &str) -> Option<PathBuf> {
+    let crate_types = json_strings(message, "\"crate_types\":[")?;
+    if !crate_types.iter().any(|crate_type| crate_type == "cdylib") {
+        return None;
+    }
+
+    json_strings(message, "\"filenames\":[")?
+        .into_iter()
+        .find(|file| {
+            [".so", ".dylib", ".dll"]
+                .iter()
+                .any(|ext| file.ends_with(ext))
+        })
+        .map(PathBuf::from)
+}
+
+/// Reads the array of strings following `key` in a JSON object.
+fn json_strings(json: &str, key: &str) -> Option<Vec<String>> {
+    let mut chars = json[json.find(key)? + key.len()..].chars();
+    let mut strings = Vec::new();
+
+    loop {
+        match chars.next()? {
+            ']' => return Some(strings),
+            ',' => {}
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            'n' => string.push('\n'),
+                            't' => string.push('\t'),
+                            'u' => {
+                                let code: String = chars.by_ref().take(4).collect();
+                                string.push(std::char::from_u32(
+                                    u32::from_str_radix(&code, 16).ok()?,
+                                )?);
+                            }
+                            c => string.push(c),
+                        },
+                        c => string.push(c),
+                    }
+                }
+                strings.push(string);
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// The target directory of the test binary. The test executable is in `target/<profile>/deps`.
+fn target_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("CARGO_TARGET_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.parent()?.parent()?.to_path_buf()))
+        .unwrap_or_else(env::temp_dir)
+}
+
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
@@ -0,0 +1,280 @@
+//! Typed channels streaming messages from Rust threads to an Erlang process.
+//!
+//! A [`Sender`] encodes each message in a single `OwnedEnv` that is reused for every send, and
+//! reports when the receiving process has exited. Messages can be tagged with a [`Reference`], so
+//! that the process can tell several streams apart:
+//!
+//! ```ignore
+//! use rustler::channel::Sender;
+//! use rustler::{Env, LocalPid, Reference};
+//!
+//! #[rustler::nif]
+//! fn stream_rows<'a>(env: Env<'a>, pid: LocalPid, query: String) -> Reference<'a> {
+//!     let reference = Reference::new(env);
+//!     let mut sender = Sender::new(pid).tagged(reference);
+//!
+//!     std::thread::spawn(move || {
+//!         for row in run_query(&query) {
+//!             // Each row arrives as `{reference, row}`. Stop when the process is gone.
+//!             if sender.send(row).is_err() {
+//!                 break;
+//!             }
+//!         }
+//!     });
+//!
+//!     reference
+//! }
+//! ```
+//!
+//! A channel created with [`bounded`] has at most `capacity` messages in flight. Once that many
+//! messages have been sent, [`Sender::send`] blocks until the process acknowledges one of them by
+//! passing the [`Acknowledger`] to a NIF calling [`Acknowledger::ack`]. The channel is closed when
+//! the `Acknowledger` is dropped by both Rust and Erlang, so a sender blocked on a process that
+//! exited is woken up.
+//!
+//! Like `OwnedEnv::send_and_clear`, sending panics on a thread managed by the Erlang VM.
+
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+
+use crate::env::{OwnedEnv, SavedTerm};
+use crate::resource::{self, ResourceType, ResourceTypeProvider};
+use crate::{Decoder, Encoder, Env, LocalPid, NifResult, Reference, ResourceArc, Term};
+
+/// Sends messages of type `T` to a process.
+pub struct Sender<T> {
+    pid: LocalPid,
+    env: OwnedEnv,
+    tag: Option<(OwnedEnv, SavedTerm)>,
+    window: Option<Arc<Window>>,
+    closed: bool,
+    marker: PhantomData<fn(T)>,
+}
+
+impl<T: Encoder> Sender<T> {
+    /// Creates an unbounded channel to `pid`.
+    pub fn new(pid: LocalPid) -> Self {
+        Sender {
+            pid,
+            env: OwnedEnv::new(),
+            tag: None,
+            window: None,
+            closed: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// Sends each message as a `{reference, message}` tuple.
+    pub fn tagged(mut self, reference: Reference) -> Self {
+        let tag_env = OwnedEnv::new();
+        let tag = tag_env.save(*reference);
+        self.tag = Some((tag_env, tag));
+        self
+    }
+
+    /// Sends `message`, waiting for an acknowledgement first if the channel is bounded and full.
+    ///
+    /// Returns the message back if the process is not alive or the channel is closed.
+    pub fn send(&mut self, message: T) -> Result<(), SendError<T>> {
+        if self.reserve() && self.deliver(|env| message.encode(env)) {
+            Ok(())
+        } else {
+            Err(SendError(message))
+        }
+    }
+
+    /// Sends `messages` as a single message holding a list, which is cheaper than sending them
+    /// one by one. A batch takes up a single place in a bounded channel.
+    pub fn send_batch(&mut self, messages: Vec<T>) -> Result<(), SendError<Vec<T>>> {
+        if self.reserve() && self.deliver(|env| messages.encode(env)) {
+            Ok(())
+        } else {
+            Err(SendError(messages))
+        }
+    }
+
+    /// Whether a send failed because the process exited, or the channel was closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed || matches!(self.window, Some(ref window) if window.is_closed())
+    }
+
+    /// Takes a place in the window of a bounded channel, blocking while it is full.
+    fn reserve(&mut self) -> bool {
+        if self.closed {
+            return false;
+        }
+
+        match self.window {
+            Some(ref window) => window.reserve(),
+            None => true,
+        }
+    }
+
+    fn deliver<F>(&mut self, encode: F) -> bool
+    where
+        F: for<'a> FnOnce(Env<'a>) -> Term<'a>,
+    {
+        let tag = &self.tag;
+        let sent = self.env.send_and_clear(&self.pid, |env| {
+            let message = encode(env);
+            match tag {
+                Some((tag_env, tag)) => {
+                    let tag = tag_env.run(|tag_env| tag.load(tag_env).in_env(env));
+                    (tag, message).encode(env)
+                }
+                None => message,
+            }
+        });
+
+        if !sent {
+            self.closed = true;
+            if let Some(ref window) = self.window {
+                window.close();
+            }
+        }
+        sent
+    }
+}
+
+/// Creates a channel to `pid` with at most `capacity` unacknowledged messages.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn bounded<T: Encoder>(pid: LocalPid, capacity: usize) -> (Sender<T>, Acknowledger) {
+    assert!(capacity > 0, "the capacity of a channel must not be zero");
+
+    let window = Arc::new(Window {
+        capacity,
+        state: Mutex::new(WindowState {
+            in_flight: 0,
+            closed: false,
+        }),
+        changed: Condvar::new(),
+    });
+    let acknowledger = Acknowledger(ResourceArc::new(AckHandle(Arc::clone(&window))));
+
+    let mut sender = Sender::new(pid);
+    sender.window = Some(window);
+    (sender, acknowledger)
+}
+
+/// Acknowledges messages of a bounded channel. It is encoded as a resource, to be passed to the
+/// receiving process and back to a NIF.
+#[derive(Clone)]
+pub struct Acknowledger(ResourceArc<AckHandle>);
+
+impl Acknowledger {
+    /// Acknowledges the oldest unacknowledged message, letting a blocked sender continue.
+    pub fn ack(&self) {
+        (self.0).0.ack();
+    }
+}
+
+impl Encoder for Acknowledger {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        self.0.encode(env)
+    }
+}
+
+impl<'a> Decoder<'a> for Acknowledger {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        Ok(Acknowledger(term.decode()?))
+    }
+}
+
+/// The error returned when sending on a closed channel, holding the message that was not sent.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SendError { .. }")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("sending on a closed channel")
+    }
+}
+
+impl<T> error::Error for SendError<T> {}
+
+/// The messages in flight of a bounded channel.
+struct Window {
+    capacity: usize,
+    state: Mutex<WindowState>,
+    changed: Condvar,
+}
+
+struct WindowState {
+    in_flight: usize,
+    closed: bool,
+}
+
+impl Window {
+    fn reserve(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        while state.in_flight >= self.capacity && !state.closed {
+            state = self.changed.wait(state).unwrap();
+        }
+
+        if state.closed {
+            return false;
+        }
+        state.in_flight += 1;
+        true
+    }
+
+    fn ack(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight = state.in_flight.saturating_sub(1);
+        self.changed.notify_all();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+/// The resource behind an `Acknowledger`, closing the channel when it is destroyed.
+struct AckHandle(Arc<Window>);
+
+impl Drop for AckHandle {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+static ACK_HANDLE_TYPE: OnceLock<ResourceType<AckHandle>> = OnceLock::new();
+
+impl ResourceTypeProvider for AckHandle {
+    fn get_type() -> &'static ResourceType<Self> {
+        ACK_HANDLE_TYPE
+            .get()
+            .expect("The resource type hasn't been initialized. Was the library loaded through `rustler::init!`?")
+    }
+}
+
+/// Opens the resource type of `Acknowledger`. Called when the library is loaded.
+pub(crate) fn open_resource_type(env: Env) -> bool {
+    match resource::open_struct_resource_type::<AckHandle>(
+        env,
+        "rustler_channel_acknowledger\x00",
+        resource::NIF_RESOURCE_FLAGS::ERL_NIF_RT_CREATE,
+    ) {
+        Some(resource_type) => {
+            let _ = ACK_HANDLE_TYPE.set(resource_type);
+            true
+        }
+        None => false,
+    }
+}
@@ -2,8 +2,12 @@
 
 use std::ffi::CString;
 use std::fmt;
+use std::iter::FromIterator;
+use std::sync::OnceLock;
 
-use crate::{Encoder, Env, OwnedBinary, Term};
+use crate::stubs::NifMetadata;
+use crate::types::map::MapIterator;
+use crate::{Binary, Decoder, Encoder, Env, Error, NifResult, OwnedBinary, Term};
 
 // Names used by the `rustler::init!` macro or other generated code.
 pub use crate::wrapper::exception::raise_exception;
@@ -15,6 +19,369 @@ pub use crate::wrapper::{
 #[cfg(windows)]
 pub use rustler_sys::{TWinDynNifCallbacks, WIN_DYN_NIF_CALLBACKS};
 
+pub use inventory;
+
+/// A NIF registered by `#[rustler::nif]`. `rustler::init!` exports all registered NIFs of its
+/// crate when it is not given an explicit list of functions.
+///
+/// `funcs` is empty when the NIF crate is compiled for its own unit tests. Registered entries are
+/// never discarded by the linker, so referencing the NIF functions there would make the test
+/// binary depend on the `enif_*` symbols that only the BEAM provides.
+pub struct NifRegistration {
+    /// The `module_path!()` of the NIF.
+    pub module_path: &'static str,
+    pub funcs: &'static [DEF_NIF_FUNC],
+    pub metadata: NifMetadata,
+}
+
+// `DEF_NIF_FUNC` holds raw pointers to static data and functions only.
+unsafe impl Sync for NifRegistration {}
+
+inventory::collect!(NifRegistration);
+
+/// Returns the NIFs registered by `#[rustler::nif]` in the crate of `module_path`. The NIFs of
+/// other crates linked into the library, such as dependencies that use rustler themselves, are
+/// left out.
+pub fn registered_nifs(module_path: &str) -> impl Iterator<Item = &'static NifRegistration> + '_ {
+    inventory::iter::<NifRegistration>
+        .into_iter()
+        .filter(move |nif| crate_of(nif.module_path) == crate_of(module_path))
+}
+
+fn crate_of(module_path: &str) -> &str {
+    module_path.split("::").next().unwrap_or(module_path)
+}
+
+/// Describes the first two NIFs registered by `#[rustler::nif]` in the crate of `module_path`
+/// that are exported with the same name and an overlapping arity. The `load` function generated
+/// by `rustler::init!` fails in that case.
+pub fn registered_nif_clash(module_path: &str) -> Option<String> {
+    let nifs: Vec<&NifMetadata> = registered_nifs(module_path)
+        .map(|nif| &nif.metadata)
+        .collect();
+
+    for (i, b) in nifs.iter().enumerate() {
+        if let Some(a) = nifs[..i].iter().find(|a| clashes(a, b)) {
+            let arity = (a.arity - a.optional).max(b.arity - b.optional);
+            return Some(format!(
+                "two functions annotated with `#[rustler::nif]` are exported as {}/{}",
+                b.name, arity
+            ));
+        }
+    }
+
+    None
+}
+
+/// Returns the functions of the NIFs registered in the crate of `module_path`, collected on the
+/// first call. A function clashing with one before it is left out, so that the BEAM accepts the
+/// entry and the library fails to load in `load` instead.
+pub fn registered_nif_funcs(module_path: &str) -> &'static [DEF_NIF_FUNC] {
+    static FUNCS: OnceLock<RegisteredFuncs> = OnceLock::new();
+
+    let funcs = FUNCS.get_or_init(|| {
+        let mut funcs: Vec<DEF_NIF_FUNC> = Vec::new();
+        let mut exported: Vec<(&str, u32)> = Vec::new();
+
+        for nif in registered_nifs(module_path) {
+            for func in nif.funcs {
+                if exported.contains(&(nif.metadata.name, func.arity)) {
+                    continue;
+                }
+                exported.push((nif.metadata.name, func.arity));
+                funcs.push(DEF_NIF_FUNC {
+                    name: func.name,
+                    arity: func.arity,
+                    function: func.function,
+                    flags: func.flags,
+                });
+            }
+        }
+
+        RegisteredFuncs(funcs)
+    });
+
+    &funcs.0
+}
+
+struct RegisteredFuncs(Vec<DEF_NIF_FUNC>);
+
+// `DEF_NIF_FUNC` holds raw pointers to static data and functions only.
+unsafe impl Send for RegisteredFuncs {}
+unsafe impl Sync for RegisteredFuncs {}
+
+/// The functions exported by `rustler::init!` when it is given an explicit list of NIFs, built
+/// at compile time into a static that the entry returned by `nif_init` points into.
+pub struct NifFuncs<const N: usize>([DEF_NIF_FUNC; N]);
+
+// `DEF_NIF_FUNC` holds raw pointers to static data and functions only.
+unsafe impl<const N: usize> Sync for NifFuncs<N> {}
+
+impl<const N: usize> NifFuncs<N> {
+    /// Concatenates the `Nif::FUNCS` of the listed NIFs, whose total length must be `N`.
+    pub const fn concat(lists: &[&[DEF_NIF_FUNC]]) -> Self {
+        const UNSET: DEF_NIF_FUNC = DEF_NIF_FUNC {
+            name: b"\0".as_ptr(),
+            arity: 0,
+            function: unset_nif,
+            flags: 0,
+        };
+
+        let mut funcs = [UNSET; N];
+        let mut len = 0;
+        let mut i = 0;
+        while i < lists.len() {
+            let mut j = 0;
+            while j < lists[i].len() {
+                let func = &lists[i][j];
+                funcs[len] = DEF_NIF_FUNC {
+                    name: func.name,
+                    arity: func.arity,
+                    function: func.function,
+                    flags: func.flags,
+                };
+                len += 1;
+                j += 1;
+            }
+            i += 1;
+        }
+
+        assert!(
+            len == N,
+            "the NIF functions do not add up to the length of the array"
+        );
+        NifFuncs(funcs)
+    }
+
+    pub fn as_slice(&self) -> &[DEF_NIF_FUNC] {
+        &self.0
+    }
+}
+
+unsafe extern "C" fn unset_nif(_env: NIF_ENV, _argc: c_int, _argv: *const NIF_TERM) -> NIF_TERM {
+    unreachable!()
+}
+
+/// The entries of a map that did not match any field, collected for a `#[rustler(extra)]` field.
+pub type MapEntries<'a> = Vec<(Term<'a>, Term<'a>)>;
+
+/// Looks up the values of `keys` in a map, as done by the derived decoders of `NifMap` and
+/// `NifStruct`. Missing keys are `None`.
+pub fn map_values<'a, const N: usize>(
+    map: Term<'a>,
+    keys: &[Term<'a>; N],
+) -> NifResult<[Option<Term<'a>>; N]> {
+    // Looking up each key is cheaper than walking a map with more entries than there are keys.
+    if map.map_size()? > N {
+        let mut values = [None; N];
+        for (value, key) in values.iter_mut().zip(keys.iter()) {
+            *value = map.map_get(*key).ok();
+        }
+        return Ok(values);
+    }
+
+    map_values_and_rest(map, keys, false).map(|(values, _)| values)
+}
+
+/// Like `map_values`, with a single pass over the map that also returns the entries whose key is
+/// not one of `keys`, for a `#[rustler(extra)]` field.
+pub fn map_values_and_rest<'a, const N: usize>(
+    map: Term<'a>,
+    keys: &[Term<'a>; N],
+    collect_rest: bool,
+) -> NifResult<([Option<Term<'a>>; N], MapEntries<'a>)> {
+    let mut values = [None; N];
+    let mut rest = Vec::new();
+    let keys = keys.map(MapKey::new);
+
+    for (key, value) in MapIterator::new(map).ok_or(Error::BadArg)? {
+        match MapKey::position(&keys, key) {
+            Some(index) => values[index] = Some(value),
+            None if collect_rest => rest.push((key, value)),
+            None => (),
+        }
+    }
+
+    Ok((values, rest))
+}
+
+/// A key looked up by `map_values_and_rest`, compared with the keys of the map without calling
+/// into the VM where possible.
+enum MapKey<'a> {
+    /// Atoms are immediates, so two atoms are equal if their terms are.
+    Atom(NIF_TERM),
+    Binary(Binary<'a>),
+    Other(Term<'a>),
+}
+
+impl<'a> MapKey<'a> {
+    fn new(term: Term<'a>) -> Self {
+        if term.is_atom() {
+            MapKey::Atom(term.as_c_arg())
+        } else if let Ok(binary) = Binary::from_term(term) {
+            MapKey::Binary(binary)
+        } else {
+            MapKey::Other(term)
+        }
+    }
+
+    fn position(keys: &[MapKey<'a>], term: Term<'a>) -> Option<usize> {
+        let raw = term.as_c_arg();
+        if let Some(index) = keys
+            .iter()
+            .position(|key| matches!(key, MapKey::Atom(atom) if *atom == raw))
+        {
+            return Some(index);
+        }
+
+        // The bytes of `term` are only inspected once, and only if some key is a binary.
+        let mut bytes = None;
+        keys.iter().position(|key| match key {
+            MapKey::Atom(_) => false,
+            MapKey::Binary(binary) => matches!(
+                bytes.get_or_insert_with(|| Binary::from_term(term).ok()),
+                Some(bytes) if bytes.as_slice() == binary.as_slice()
+            ),
+            MapKey::Other(other) => *other == term,
+        })
+    }
+}
+
+/// Decodes the entries collected for a `#[rustler(extra)]` field into any collection of pairs,
+/// such as `HashMap<Atom, Term>` or `Vec<(Term, Term)>`.
+pub fn decode_extra<'a, T, K, V>(entries: MapEntries<'a>) -> NifResult<T>
+where
+    T: FromIterator<(K, V)>,
+    K: Decoder<'a>,
+    V: Decoder<'a>,
+{
+    entries
+        .into_iter()
+        .map(|(key, value)| Ok((key.decode()?, value.decode()?)))
+        .collect()
+}
+
+/// Builds the map encoded by `NifMap` and `NifStruct` when the struct has a `#[rustler(extra)]`
+/// field. `extra` is the encoded field, either a map or a list of `{key, value}` tuples. Its
+/// entries are added to the map unless they conflict with one of `keys`, and the last one wins
+/// when a key is repeated.
+pub fn map_with_extra<'a>(
+    env: Env<'a>,
+    keys: &[Term<'a>],
+    values: &[Term<'a>],
+    extra: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let entries: MapEntries<'a> = match MapIterator::new(extra) {
+        Some(iter) => iter.collect(),
+        None => extra.decode()?,
+    };
+
+    let mut all_keys = keys.to_vec();
+    let mut all_values = values.to_vec();
+    for (key, value) in entries {
+        if !keys.contains(&key) {
+            all_keys.push(key);
+            all_values.push(value);
+        }
+    }
+
+    // `map_from_arrays` fails on repeated keys, which a list of pairs may contain.
+    Term::map_from_arrays(env, &all_keys, &all_values).or_else(|_| {
+        all_keys
+            .iter()
+            .zip(all_values.iter())
+            .try_fold(Term::map_new(env), |map, (key, value)| {
+                map.map_put(*key, *value)
+            })
+    })
+}
+
+/// Builds a list from its elements, for `rustler::term!`.
+pub fn make_list<'a>(env: Env<'a>, items: &[Term<'a>]) -> Term<'a> {
+    let items: Vec<NIF_TERM> = items.iter().map(|item| item.as_c_arg()).collect();
+    unsafe { Term::new(env, crate::wrapper::list::make_list(env.as_c_arg(), &items)) }
+}
+
+/// Builds a map from its keys and values, for `rustler::term!`. As in Elixir, the last value of a
+/// repeated key wins.
+pub fn make_map<'a>(env: Env<'a>, keys: &[Term<'a>], values: &[Term<'a>]) -> Term<'a> {
+    Term::map_from_arrays(env, keys, values).unwrap_or_else(|_| {
+        keys.iter()
+            .zip(values.iter())
+            .fold(Term::map_new(env), |map, (key, value)| {
+                map.map_put(*key, *value).unwrap()
+            })
+    })
+}
+
+/// Returns the elements of `term` if it is a tuple of `N` elements, for `rustler::match_term!`.
+pub fn match_tuple<'a, const N: usize>(term: Term<'a>) -> Option<[Term<'a>; N]> {
+    let env = term.get_env();
+    let elements =
+        unsafe { crate::wrapper::tuple::get_tuple(env.as_c_arg(), term.as_c_arg()) }.ok()?;
+    if elements.len() != N {
+        return None;
+    }
+
+    let mut result = [term; N];
+    for (slot, element) in result.iter_mut().zip(elements) {
+        *slot = unsafe { Term::new(env, *element) };
+    }
+    Some(result)
+}
+
+/// Whether rustler was built with the `testing` feature, whose NIF API would replace the one of
+/// the BEAM. `rustler::init!` fails to compile in that case, except in unit tests.
+pub const TESTING: bool = cfg!(feature = "testing");
+
+/// Whether `nif` is exported with the same name and an overlapping arity as one of the NIFs
+/// listed before it in `rustler::init!`, which would make the BEAM refuse to load the library.
+pub const fn clashes_with_any(previous: &[NifMetadata], nif: &NifMetadata) -> bool {
+    let mut i = 0;
+    while i < previous.len() {
+        if clashes(&previous[i], nif) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Whether `a` and `b` are exported with the same name and an overlapping arity.
+const fn clashes(a: &NifMetadata, b: &NifMetadata) -> bool {
+    let overlaps = a.arity - a.optional <= b.arity && b.arity - b.optional <= a.arity;
+    overlaps && str_eq(a.name, b.name)
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// A resource type registered by `#[rustler::resource_impl]`. All registered resource types are
+/// opened when the library is loaded, before the `load` function passed to `rustler::init!` runs.
+pub struct ResourceRegistration {
+    pub open: for<'a> fn(Env<'a>) -> bool,
+}
+
+inventory::collect!(ResourceRegistration);
+
+fn open_registered_resources(env: Env) -> bool {
+    inventory::iter::<ResourceRegistration>
+        .into_iter()
+        .all(|resource| (resource.open)(env))
+}
+
 pub unsafe trait NifReturnable {
     unsafe fn into_returned(self, env: Env) -> NifReturned;
 }
@@ -105,6 +472,13 @@ pub unsafe fn handle_nif_init_call(
     let env = Env::new(&(), r_env);
     let term = Term::new(env, load_info);
 
+    if !open_registered_resources(env)
+        || !crate::channel::open_resource_type(env)
+        || !crate::thread::open_resource_type(env)
+    {
+        return 1;
+    }
+
     if let Some(inner) = function {
         if inner(env, term) {
             0
@@ -116,6 +490,11 @@ pub unsafe fn handle_nif_init_call(
     }
 }
 
+/// Called when the library is unloaded.
+pub fn handle_nif_unload_call() {
+    crate::thread::shutdown_pool();
+}
+
 pub fn handle_nif_result<T>(
     result: std::thread::Result<Result<T, crate::error::Error>>,
     env: Env,
@@ -1,6 +1,6 @@
 use crate::types::LocalPid;
-use crate::wrapper::{NIF_ENV, NIF_TERM};
-use crate::{Encoder, Term};
+use crate::wrapper::{pid, NIF_ENV, NIF_TERM};
+use crate::{Atom, Encoder, Term};
 use std::marker::PhantomData;
 use std::ptr;
 use std::sync::{Arc, Weak};
@@ -60,7 +60,8 @@ impl<'a> Env<'a> {
         (error, reason).encode(self)
     }
 
-    /// Send a message to a process.
+    /// Send a message to a process. Returns `false` if the process is not alive, in which case
+    /// the message was dropped.
     ///
     /// The Erlang VM imposes some odd restrictions on sending messages.
     /// You can send messages in either of these situations:
@@ -76,9 +77,52 @@ impl<'a> Env<'a> {
     /// Panics if the above rules are broken (by trying to send a message from
     /// an `OwnedEnv` on a thread that's managed by the Erlang VM).
     ///
-    pub fn send(self, pid: &LocalPid, message: Term<'a>) {
+    pub fn send(self, pid: &LocalPid, message: Term<'a>) -> bool {
+        let env = self.calling_process_env();
+
+        // Send the message.
+        unsafe {
+            rustler_sys::enif_send(env, pid.as_c_arg(), ptr::null_mut(), message.as_c_arg()) != 0
+        }
+    }
+
+    /// Looks up the local process registered as `name`. Returns `None` if no process is
+    /// registered under `name`.
+    ///
+    /// Like `send`, this can be called with the environment of the calling process on a thread
+    /// managed by the Erlang VM, or with any environment on other threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called with an `OwnedEnv` on a thread that's managed by the Erlang VM.
+    pub fn whereis(self, name: Atom) -> Option<LocalPid> {
+        let env = self.calling_process_env();
+        unsafe { pid::whereis_pid(env, name.as_c_arg()) }.map(LocalPid::from_c_arg)
+    }
+
+    /// Sends a message to the local process registered as `name`, following the same rules as
+    /// `send`. Returns `false` if no process is registered under `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called with an `OwnedEnv` on a thread that's managed by the Erlang VM.
+    pub fn send_to_name(self, name: Atom, message: Term<'a>) -> bool {
+        match self.whereis(name) {
+            Some(pid) => self.send(&pid, message),
+            None => false,
+        }
+    }
+
+    /// The environment to pass to NIF functions taking the environment of the calling process,
+    /// such as `enif_send`: `self` on a thread managed by the Erlang VM, and null on other
+    /// threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics on a managed thread if `self` is not the environment of the calling process.
+    fn calling_process_env(self) -> NIF_ENV {
         let thread_type = unsafe { rustler_sys::enif_thread_type() };
-        let env = if thread_type == rustler_sys::ERL_NIF_THR_UNDEFINED {
+        if thread_type == rustler_sys::ERL_NIF_THR_UNDEFINED {
             ptr::null_mut()
         } else if thread_type == rustler_sys::ERL_NIF_THR_NORMAL_SCHEDULER
             || thread_type == rustler_sys::ERL_NIF_THR_DIRTY_CPU_SCHEDULER
@@ -89,12 +133,7 @@ impl<'a> Env<'a> {
 
             self.as_c_arg()
         } else {
-            panic!("Env::send(): unrecognized calling thread type");
-        };
-
-        // Send the message.
-        unsafe {
-            rustler_sys::enif_send(env, pid.as_c_arg(), ptr::null_mut(), message.as_c_arg());
+            panic!("unrecognized calling thread type");
         }
     }
 
@@ -157,7 +196,8 @@ impl OwnedEnv {
         closure(env)
     }
 
-    /// Send a message from a Rust thread to an Erlang process.
+    /// Send a message from a Rust thread to an Erlang process. Returns `false` if the process is
+    /// not alive, in which case the message was dropped.
     ///
     /// The environment is cleared as though by calling the `.clear()` method.
     /// To avoid that, use `env.send(pid, term)` instead.
@@ -166,9 +206,10 @@ impl OwnedEnv {
     ///
     /// Panics if called from a thread that is managed by the Erlang VM. You
     /// can only use this method on a thread that was created by other
-    /// means. (This curious restriction is imposed by the Erlang VM.)
+    /// means. (This curious restriction is imposed by the Erlang VM.) Within a NIF call, use
+    /// `send_from` instead.
     ///
-    pub fn send_and_clear<F>(&mut self, recipient: &LocalPid, closure: F)
+    pub fn send_and_clear<F>(&mut self, recipient: &LocalPid, closure: F) -> bool
     where
         F: for<'a> FnOnce(Env<'a>) -> Term<'a>,
     {
@@ -178,11 +219,71 @@ impl OwnedEnv {
 
         let message = self.run(|env| closure(env).as_c_arg());
 
-        unsafe {
-            rustler_sys::enif_send(ptr::null_mut(), recipient.as_c_arg(), *self.env, message);
-        }
+        let sent = unsafe {
+            rustler_sys::enif_send(ptr::null_mut(), recipient.as_c_arg(), *self.env, message)
+        };
 
         self.clear();
+        sent != 0
+    }
+
+    /// Like `send_and_clear`, sending the message to the local process registered as `name`.
+    /// Returns `false` if no process is registered under `name`, in which case `closure` is not
+    /// called but the environment is still cleared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a thread that is managed by the Erlang VM.
+    pub fn send_to_name_and_clear<F>(&mut self, name: Atom, closure: F) -> bool
+    where
+        F: for<'a> FnOnce(Env<'a>) -> Term<'a>,
+    {
+        if unsafe { rustler_sys::enif_thread_type() } != rustler_sys::ERL_NIF_THR_UNDEFINED {
+            panic!("send_to_name_and_clear: current thread is managed");
+        }
+
+        match self.run(|env| env.whereis(name)) {
+            Some(pid) => self.send_and_clear(&pid, closure),
+            None => {
+                self.clear();
+                false
+            }
+        }
+    }
+
+    /// Send a message built in this environment from within a NIF call, where `caller` is the
+    /// environment of the calling process.
+    ///
+    /// Unlike `send_and_clear`, the message is copied and the environment is left untouched, so
+    /// saved terms stay valid and a prebuilt message can be sent to several processes. Returns
+    /// `false` if the process is not alive.
+    ///
+    /// On a thread that is not managed by the Erlang VM, there is no calling process and `caller`
+    /// is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics on a thread managed by the Erlang VM if `caller` is not the environment of the
+    /// calling process.
+    ///
+    ///     use rustler::env::{OwnedEnv, SavedTerm};
+    ///     use rustler::{Env, LocalPid};
+    ///
+    ///     fn broadcast(caller: Env, payload_env: &OwnedEnv, payload: &SavedTerm, pids: &[LocalPid]) {
+    ///         for pid in pids {
+    ///             payload_env.send_from(caller, pid, |env| payload.load(env));
+    ///         }
+    ///     }
+    pub fn send_from<F>(&self, caller: Env, recipient: &LocalPid, closure: F) -> bool
+    where
+        F: for<'a> FnOnce(Env<'a>) -> Term<'a>,
+    {
+        let caller = caller.calling_process_env();
+        let message = self.run(|env| closure(env).as_c_arg());
+
+        unsafe {
+            rustler_sys::enif_send(caller, recipient.as_c_arg(), ptr::null_mut(), message) != 0
+        }
     }
 
     /// Free all terms in this environment and clear it for reuse.
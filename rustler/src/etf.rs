@@ -0,0 +1,591 @@
+//! Reading and writing the Erlang external term format without an `Env`.
+//!
+//! This is the format of `:erlang.term_to_binary/1` and `:erlang.binary_to_term/1`. Unlike
+//! `Term::to_binary` and `Env::binary_to_term`, the functions in this module work on [`Value`]s
+//! and do not need a running BEAM, so they can be used on any thread, for example to exchange
+//! terms with Elixir over a socket.
+//!
+//! ```
+//! use rustler::{etf, Value};
+//!
+//! let bytes = etf::to_vec(&(1, "two", vec![3.0]));
+//! let (one, two, three): (i64, String, Vec<f64>) = etf::from_slice(&bytes).unwrap();
+//! assert_eq!((one, two.as_str(), three), (1, "two", vec![3.0]));
+//!
+//! let value = etf::decode(&[131, 100, 0, 2, 111, 107]).unwrap();
+//! assert_eq!(value, Value::Atom("ok".to_string()));
+//! ```
+//!
+//! Decoding accepts every form `binary_to_term/1` accepts outside of the distribution protocol,
+//! including compressed terms, old atom, float, pid, port and reference encodings, and bignums.
+//! Bitstrings that are not binaries and old-style funs can't be represented as a [`Value`] and
+//! are rejected. Compressed terms may only decompress to [`DEFAULT_MAX_DECOMPRESSED_LEN`] bytes,
+//! unless decoded with [`DecodeOptions`].
+//!
+//! Encoding produces terms `binary_to_term/1` decodes to the same value, although not always the
+//! exact bytes `term_to_binary/1` would produce: for example, lists of small integers are not
+//! encoded as `STRING_EXT`.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Read, Write};
+
+use flate2::bufread::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::types::value::{BigInt, FromValue, Opaque, ToValue, Value};
+
+const VERSION: u8 = 131;
+
+const NEW_FLOAT_EXT: u8 = 70;
+const COMPRESSED: u8 = 80;
+const NEW_PID_EXT: u8 = 88;
+const NEW_PORT_EXT: u8 = 89;
+const NEWER_REFERENCE_EXT: u8 = 90;
+const SMALL_INTEGER_EXT: u8 = 97;
+const INTEGER_EXT: u8 = 98;
+const FLOAT_EXT: u8 = 99;
+const ATOM_EXT: u8 = 100;
+const REFERENCE_EXT: u8 = 101;
+const PORT_EXT: u8 = 102;
+const PID_EXT: u8 = 103;
+const SMALL_TUPLE_EXT: u8 = 104;
+const LARGE_TUPLE_EXT: u8 = 105;
+const NIL_EXT: u8 = 106;
+const STRING_EXT: u8 = 107;
+const LIST_EXT: u8 = 108;
+const BINARY_EXT: u8 = 109;
+const SMALL_BIG_EXT: u8 = 110;
+const LARGE_BIG_EXT: u8 = 111;
+const NEW_FUN_EXT: u8 = 112;
+const EXPORT_EXT: u8 = 113;
+const NEW_REFERENCE_EXT: u8 = 114;
+const SMALL_ATOM_EXT: u8 = 115;
+const MAP_EXT: u8 = 116;
+const ATOM_UTF8_EXT: u8 = 118;
+const SMALL_ATOM_UTF8_EXT: u8 = 119;
+const V4_PORT_EXT: u8 = 120;
+
+/// How deeply tuples, lists and maps may be nested in a decoded term, so that malicious input
+/// can't overflow the stack.
+const MAX_DEPTH: usize = 1024;
+
+/// The most a zlib stream can expand its input, so that longer decompressed lengths can be
+/// rejected before inflating anything.
+const MAX_ZLIB_RATIO: usize = 1032;
+
+/// The default limit on the size of a compressed term once decompressed, 64 MiB.
+pub const DEFAULT_MAX_DECOMPRESSED_LEN: usize = 64 * 1024 * 1024;
+
+/// An error decoding a term from the external term format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a term.
+    UnexpectedEnd,
+    /// The input does not start with the version byte 131.
+    InvalidVersion(u8),
+    /// The input contains a tag that is unknown, only valid in the distribution protocol, or
+    /// can't be represented as a `Value`.
+    UnsupportedTag(u8),
+    /// An atom, float or compressed term is malformed.
+    Invalid(&'static str),
+    /// Terms are nested more deeply than supported.
+    TooDeep,
+    /// A compressed term is larger than allowed once decompressed.
+    TooLarge,
+    /// There are bytes left after the term.
+    TrailingBytes,
+    /// The term was decoded, but could not be converted to the requested type.
+    Conversion,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::InvalidVersion(version) => {
+                write!(f, "invalid external term format version {}", version)
+            }
+            DecodeError::UnsupportedTag(tag) => write!(f, "unsupported term tag {}", tag),
+            DecodeError::Invalid(what) => write!(f, "invalid {}", what),
+            DecodeError::TooDeep => write!(f, "term nested too deeply"),
+            DecodeError::TooLarge => write!(f, "compressed term too large"),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after term"),
+            DecodeError::Conversion => write!(f, "term has the wrong type"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Encodes a value in the external term format.
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut bytes = vec![VERSION];
+    write_value(&mut bytes, value);
+    bytes
+}
+
+/// Encodes a value in the external term format, compressed with the given zlib level from 0 to
+/// 9, like `:erlang.term_to_binary(term, compressed: level)`.
+///
+/// As in Erlang, the term is left uncompressed if compressing does not make it smaller.
+pub fn encode_compressed(value: &Value, level: u32) -> Vec<u8> {
+    let mut raw = Vec::new();
+    write_value(&mut raw, value);
+
+    let mut bytes = vec![VERSION, COMPRESSED];
+    bytes.extend_from_slice(&(raw.len() as u32).to_be_bytes());
+    let mut encoder = ZlibEncoder::new(bytes, Compression::new(level.min(9)));
+    let compressed = encoder
+        .write_all(&raw)
+        .and_then(|()| encoder.finish())
+        .expect("writing to a Vec can't fail");
+
+    if compressed.len() < raw.len() + 1 {
+        compressed
+    } else {
+        raw.insert(0, VERSION);
+        raw
+    }
+}
+
+/// Encodes anything that converts to a [`Value`] in the external term format.
+pub fn to_vec<T: ToValue + ?Sized>(value: &T) -> Vec<u8> {
+    encode(&value.to_value())
+}
+
+/// Decodes a term in the external term format, which must make up all of `bytes`.
+pub fn decode(bytes: &[u8]) -> Result<Value, DecodeError> {
+    DecodeOptions::new().decode(bytes)
+}
+
+/// Decodes a term in the external term format at the start of `bytes`, returning it with the
+/// number of bytes it took up.
+pub fn decode_prefix(bytes: &[u8]) -> Result<(Value, usize), DecodeError> {
+    DecodeOptions::new().decode_prefix(bytes)
+}
+
+/// Decodes a term in the external term format and converts it with [`FromValue`].
+pub fn from_slice<T: FromValue>(bytes: &[u8]) -> Result<T, DecodeError> {
+    DecodeOptions::new().from_slice(bytes)
+}
+
+/// Options for decoding terms from untrusted input. The functions of this module use the
+/// defaults.
+///
+/// ```
+/// use rustler::etf::{self, DecodeError, DecodeOptions};
+/// use rustler::Value;
+///
+/// let bytes = etf::encode_compressed(&Value::Binary(vec![0; 4096]), 9);
+/// let options = DecodeOptions::new().max_decompressed_len(1024);
+/// assert_eq!(options.decode(&bytes), Err(DecodeError::TooLarge));
+///
+/// let options = DecodeOptions::new().max_decompressed_len(8192);
+/// assert_eq!(options.decode(&bytes), Ok(Value::Binary(vec![0; 4096])));
+///
+/// // A few bytes claiming to decompress to 4 GiB are rejected before inflating anything.
+/// let bomb = [131, 80, 255, 255, 255, 255, 120, 156];
+/// assert_eq!(etf::decode(&bomb), Err(DecodeError::TooLarge));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct DecodeOptions {
+    max_decompressed_len: usize,
+}
+
+impl DecodeOptions {
+    pub fn new() -> Self {
+        DecodeOptions {
+            max_decompressed_len: DEFAULT_MAX_DECOMPRESSED_LEN,
+        }
+    }
+
+    /// Limits the size a compressed term may have once decompressed, so that a small input
+    /// can't make the decoder allocate and inflate gigabytes. Defaults to
+    /// [`DEFAULT_MAX_DECOMPRESSED_LEN`].
+    pub fn max_decompressed_len(mut self, len: usize) -> Self {
+        self.max_decompressed_len = len;
+        self
+    }
+
+    /// Like [`decode`], with these options.
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value, DecodeError> {
+        let (value, len) = self.decode_prefix(bytes)?;
+        if len == bytes.len() {
+            Ok(value)
+        } else {
+            Err(DecodeError::TrailingBytes)
+        }
+    }
+
+    /// Like [`decode_prefix`], with these options.
+    pub fn decode_prefix(&self, bytes: &[u8]) -> Result<(Value, usize), DecodeError> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(DecodeError::InvalidVersion(version));
+        }
+
+        if reader.peek()? != COMPRESSED {
+            let value = reader.value(0)?;
+            return Ok((value, reader.pos));
+        }
+
+        reader.pos += 1;
+        let len = reader.u32()? as usize;
+        let compressed = &bytes[reader.pos..];
+        if len > self.max_decompressed_len {
+            return Err(DecodeError::TooLarge);
+        }
+        if len > compressed.len().saturating_mul(MAX_ZLIB_RATIO) {
+            return Err(DecodeError::Invalid("compressed term"));
+        }
+
+        let mut decoder = ZlibDecoder::new(compressed);
+        let mut raw = Vec::with_capacity(len.min(compressed.len().saturating_mul(4)));
+        let invalid = |_| DecodeError::Invalid("compressed term");
+        decoder
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut raw)
+            .map_err(invalid)?;
+        // Reading past the end makes the decoder check the zlib trailer.
+        if raw.len() != len || decoder.read(&mut [0]).map_err(invalid)? != 0 {
+            return Err(DecodeError::Invalid("compressed term"));
+        }
+
+        let mut inner = Reader {
+            bytes: &raw,
+            pos: 0,
+        };
+        let value = inner.value(0)?;
+        if inner.pos != raw.len() {
+            return Err(DecodeError::Invalid("compressed term"));
+        }
+        Ok((value, reader.pos + decoder.total_in() as usize))
+    }
+
+    /// Like [`from_slice`], with these options.
+    pub fn from_slice<T: FromValue>(&self, bytes: &[u8]) -> Result<T, DecodeError> {
+        T::from_value(self.decode(bytes)?).map_err(|_| DecodeError::Conversion)
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions::new()
+    }
+}
+
+fn write_value(bytes: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Atom(name) => write_atom(bytes, name),
+        Value::Integer(integer) => write_integer(bytes, *integer),
+        Value::BigInt(int) => match int.to_i64() {
+            Some(integer) => write_integer(bytes, integer),
+            None => write_big(bytes, int),
+        },
+        Value::Float(float) => {
+            bytes.push(NEW_FLOAT_EXT);
+            bytes.extend_from_slice(&float.to_bits().to_be_bytes());
+        }
+        Value::Binary(binary) => {
+            bytes.push(BINARY_EXT);
+            bytes.extend_from_slice(&(binary.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(binary);
+        }
+        Value::List(items) if items.is_empty() => bytes.push(NIL_EXT),
+        Value::List(items) => {
+            write_list(bytes, items);
+            bytes.push(NIL_EXT);
+        }
+        Value::ImproperList(items, tail) if items.is_empty() => write_value(bytes, tail),
+        Value::ImproperList(items, tail) => {
+            write_list(bytes, items);
+            write_value(bytes, tail);
+        }
+        Value::Tuple(items) => {
+            match u8::try_from(items.len()) {
+                Ok(len) => bytes.extend_from_slice(&[SMALL_TUPLE_EXT, len]),
+                Err(_) => {
+                    bytes.push(LARGE_TUPLE_EXT);
+                    bytes.extend_from_slice(&(items.len() as u32).to_be_bytes());
+                }
+            }
+            for item in items {
+                write_value(bytes, item);
+            }
+        }
+        Value::Map(map) => {
+            bytes.push(MAP_EXT);
+            bytes.extend_from_slice(&(map.len() as u32).to_be_bytes());
+            for (key, value) in map {
+                write_value(bytes, key);
+                write_value(bytes, value);
+            }
+        }
+        Value::Pid(opaque)
+        | Value::Reference(opaque)
+        | Value::Port(opaque)
+        | Value::Fun(opaque) => bytes.extend_from_slice(&opaque.as_bytes()[1..]),
+    }
+}
+
+fn write_atom(bytes: &mut Vec<u8>, name: &str) {
+    match u8::try_from(name.len()) {
+        Ok(len) => bytes.extend_from_slice(&[SMALL_ATOM_UTF8_EXT, len]),
+        Err(_) => {
+            bytes.push(ATOM_UTF8_EXT);
+            bytes.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        }
+    }
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+fn write_integer(bytes: &mut Vec<u8>, integer: i64) {
+    if let Ok(small) = u8::try_from(integer) {
+        bytes.extend_from_slice(&[SMALL_INTEGER_EXT, small]);
+    } else if let Ok(integer) = i32::try_from(integer) {
+        bytes.push(INTEGER_EXT);
+        bytes.extend_from_slice(&integer.to_be_bytes());
+    } else {
+        write_big(bytes, &BigInt::from(integer));
+    }
+}
+
+fn write_big(bytes: &mut Vec<u8>, int: &BigInt) {
+    let magnitude = int.magnitude();
+    match u8::try_from(magnitude.len()) {
+        Ok(len) => bytes.extend_from_slice(&[SMALL_BIG_EXT, len]),
+        Err(_) => {
+            bytes.push(LARGE_BIG_EXT);
+            bytes.extend_from_slice(&(magnitude.len() as u32).to_be_bytes());
+        }
+    }
+    bytes.push(int.is_negative() as u8);
+    bytes.extend_from_slice(magnitude);
+}
+
+/// Writes the head of a non-empty list and its items, without the tail.
+fn write_list(bytes: &mut Vec<u8>, items: &[Value]) {
+    bytes.push(LIST_EXT);
+    bytes.extend_from_slice(&(items.len() as u32).to_be_bytes());
+    for item in items {
+        write_value(bytes, item);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn peek(&self) -> Result<u8, DecodeError> {
+        self.bytes
+            .get(self.pos)
+            .copied()
+            .ok_or(DecodeError::UnexpectedEnd)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A vector for `len` items read from the input, each taking at least one byte, without
+    /// trusting `len` for the allocation.
+    fn vec_for<T>(&self, len: usize) -> Vec<T> {
+        Vec::with_capacity(len.min(self.bytes.len() - self.pos))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, DecodeError> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::TooDeep);
+        }
+
+        let start = self.pos;
+        let tag = self.u8()?;
+        match tag {
+            SMALL_INTEGER_EXT => Ok(Value::Integer(self.u8()?.into())),
+            INTEGER_EXT => Ok(Value::Integer((self.u32()? as i32).into())),
+            SMALL_BIG_EXT | LARGE_BIG_EXT => {
+                let len = if tag == SMALL_BIG_EXT {
+                    self.u8()? as usize
+                } else {
+                    self.u32()? as usize
+                };
+                let negative = self.u8()? != 0;
+                let int = BigInt::new(negative, self.take(len)?.to_vec());
+                Ok(int.to_i64().map_or(Value::BigInt(int), Value::Integer))
+            }
+            NEW_FLOAT_EXT => {
+                let bytes = self.take(8)?;
+                let mut bits = [0; 8];
+                bits.copy_from_slice(bytes);
+                Ok(Value::Float(f64::from_bits(u64::from_be_bytes(bits))))
+            }
+            FLOAT_EXT => {
+                let text = std::str::from_utf8(self.take(31)?)
+                    .map_err(|_| DecodeError::Invalid("float"))?;
+                text.trim_end_matches('\0')
+                    .trim()
+                    .parse()
+                    .map(Value::Float)
+                    .map_err(|_| DecodeError::Invalid("float"))
+            }
+            ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT => {
+                self.pos = start;
+                self.atom().map(Value::Atom)
+            }
+            SMALL_TUPLE_EXT | LARGE_TUPLE_EXT => {
+                let len = if tag == SMALL_TUPLE_EXT {
+                    self.u8()? as usize
+                } else {
+                    self.u32()? as usize
+                };
+                let mut items = self.vec_for(len);
+                for _ in 0..len {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(Value::Tuple(items))
+            }
+            MAP_EXT => {
+                let len = self.u32()?;
+                let mut map = std::collections::BTreeMap::new();
+                for _ in 0..len {
+                    let key = self.value(depth + 1)?;
+                    let value = self.value(depth + 1)?;
+                    map.insert(key, value);
+                }
+                Ok(Value::Map(map))
+            }
+            NIL_EXT | STRING_EXT | LIST_EXT => {
+                self.pos = start;
+                self.list(depth)
+            }
+            BINARY_EXT => {
+                let len = self.u32()? as usize;
+                Ok(Value::Binary(self.take(len)?.to_vec()))
+            }
+            PID_EXT | NEW_PID_EXT => {
+                self.atom()?;
+                self.take(if tag == PID_EXT { 9 } else { 12 })?;
+                Ok(Value::Pid(self.opaque(start)))
+            }
+            PORT_EXT | NEW_PORT_EXT | V4_PORT_EXT => {
+                self.atom()?;
+                self.take(match tag {
+                    PORT_EXT => 5,
+                    NEW_PORT_EXT => 8,
+                    _ => 12,
+                })?;
+                Ok(Value::Port(self.opaque(start)))
+            }
+            REFERENCE_EXT => {
+                self.atom()?;
+                self.take(5)?;
+                Ok(Value::Reference(self.opaque(start)))
+            }
+            NEW_REFERENCE_EXT | NEWER_REFERENCE_EXT => {
+                let len = self.u16()? as usize;
+                self.atom()?;
+                self.take(if tag == NEW_REFERENCE_EXT { 1 } else { 4 })?;
+                self.take(len * 4)?;
+                Ok(Value::Reference(self.opaque(start)))
+            }
+            NEW_FUN_EXT => {
+                // The size includes the size field itself.
+                let size = self.u32()? as usize;
+                self.take(size.checked_sub(4).ok_or(DecodeError::Invalid("fun"))?)?;
+                Ok(Value::Fun(self.opaque(start)))
+            }
+            EXPORT_EXT => {
+                self.atom()?;
+                self.atom()?;
+                if self.u8()? != SMALL_INTEGER_EXT {
+                    return Err(DecodeError::Invalid("fun"));
+                }
+                self.u8()?;
+                Ok(Value::Fun(self.opaque(start)))
+            }
+            _ => Err(DecodeError::UnsupportedTag(tag)),
+        }
+    }
+
+    fn atom(&mut self) -> Result<String, DecodeError> {
+        let tag = self.u8()?;
+        let len = match tag {
+            SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT => self.u8()? as usize,
+            ATOM_EXT | ATOM_UTF8_EXT => self.u16()? as usize,
+            _ => return Err(DecodeError::UnsupportedTag(tag)),
+        };
+        let bytes = self.take(len)?;
+
+        match tag {
+            // Latin-1 characters are the first 256 Unicode code points.
+            ATOM_EXT | SMALL_ATOM_EXT => Ok(bytes.iter().map(|&byte| char::from(byte)).collect()),
+            _ => String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Invalid("atom")),
+        }
+    }
+
+    /// Reads a list, flattening list tails into the list as `binary_to_term/1` does.
+    fn list(&mut self, depth: usize) -> Result<Value, DecodeError> {
+        let mut items = Vec::new();
+        loop {
+            match self.u8()? {
+                NIL_EXT => return Ok(Value::List(items)),
+                STRING_EXT => {
+                    let len = self.u16()? as usize;
+                    items.extend(self.take(len)?.iter().map(|&c| Value::Integer(c.into())));
+                    return Ok(Value::List(items));
+                }
+                LIST_EXT => {
+                    let len = self.u32()? as usize;
+                    items.reserve(len.min(self.bytes.len() - self.pos));
+                    for _ in 0..len {
+                        items.push(self.value(depth + 1)?);
+                    }
+                    if !matches!(self.peek()?, NIL_EXT | STRING_EXT | LIST_EXT) {
+                        let tail = self.value(depth + 1)?;
+                        if items.is_empty() {
+                            return Ok(tail);
+                        }
+                        return Ok(Value::ImproperList(items, Box::new(tail)));
+                    }
+                }
+                tag => return Err(DecodeError::UnsupportedTag(tag)),
+            }
+        }
+    }
+
+    /// The term read since `start`, as returned by `term_to_binary/1`.
+    fn opaque(&self, start: usize) -> Opaque {
+        let mut bytes = Vec::with_capacity(self.pos - start + 1);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.bytes[start..self.pos]);
+        Opaque(bytes)
+    }
+}
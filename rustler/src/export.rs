@@ -37,6 +37,12 @@ macro_rules! rustler_export_nifs {
                 }
             }
 
+            extern "C" fn nif_unload(
+                _env: $crate::codegen_runtime::NIF_ENV,
+                _priv_data: *mut $crate::codegen_runtime::c_void) {
+                $crate::codegen_runtime::handle_nif_unload_call()
+            }
+
             const FUN_ENTRIES: &'static [$crate::codegen_runtime::DEF_NIF_FUNC] = &[
                 $($crate::rustler_export_nifs!(internal_item_init, $exported_nif)),*
             ];
@@ -50,7 +56,7 @@ macro_rules! rustler_export_nifs {
                 load: Some(nif_load),
                 reload: None,
                 upgrade: None,
-                unload: None,
+                unload: Some(nif_unload),
                 vm_variant: b"beam.vanilla\x00".as_ptr(),
                 options: 0,
                 sizeof_ErlNifResourceTypeInit: $crate::codegen_runtime::get_nif_resource_type_init_size(),
@@ -23,7 +23,7 @@
 //! For more information about this, see [the documentation for
 //! rustler_mix](https://hexdocs.pm/rustler/basics.html).
 
-#[macro_use(enif_snprintf)]
+#[cfg_attr(not(feature = "testing"), macro_use(enif_snprintf))]
 extern crate rustler_sys;
 
 #[doc(hidden)]
@@ -41,10 +41,11 @@ mod term;
 
 pub use crate::term::Term;
 pub use crate::types::{
-    Atom, Binary, Decoder, Encoder, ListIterator, LocalPid, MapIterator, OwnedBinary,
+    Atom, Binary, Decoder, Encoder, Keyword, ListIterator, LocalPid, MapIterator, OwnedBinary,
+    Reference, Value,
 };
 pub mod resource;
-pub use crate::resource::ResourceArc;
+pub use crate::resource::{ResourceArc, ResourceLock};
 
 #[doc(hidden)]
 pub mod dynamic;
@@ -57,10 +58,20 @@ pub use crate::env::{Env, OwnedEnv};
 pub mod thread;
 pub use crate::thread::{spawn, JobSpawner, ThreadSpawner};
 
+pub mod channel;
 pub mod error;
+pub mod etf;
 pub mod export;
 pub use crate::error::Error;
 
+pub mod stubs;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "beam")]
+pub mod beam;
+
 pub mod r#return;
 pub use crate::r#return::Return;
 
@@ -72,5 +83,6 @@ pub type NifResult<T> = Result<T, Error>;
 
 #[cfg(feature = "derive")]
 pub use rustler_codegen::{
-    init, nif, NifMap, NifRecord, NifStruct, NifTuple, NifUnitEnum, NifUntaggedEnum,
+    init, match_term, nif, resource_impl, term, NifKeywordList, NifMap, NifRecord, NifStruct,
+    NifTuple, NifUnitEnum, NifUntaggedEnum,
 };
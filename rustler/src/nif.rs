@@ -1,10 +1,14 @@
 use crate::codegen_runtime::{c_int, DEF_NIF_FUNC, NIF_ENV, NIF_TERM};
+use crate::stubs::NifMetadata;
 
 pub trait Nif {
     const NAME: *const u8;
     const ARITY: u32;
     const FLAGS: u32;
     const FUNC: DEF_NIF_FUNC;
+    /// `FUNC`, followed by one entry for each arity with trailing optional arguments left out.
+    const FUNCS: &'static [DEF_NIF_FUNC];
+    const METADATA: NifMetadata;
     const RAW_FUNC: unsafe extern "C" fn(
         nif_env: NIF_ENV,
         argc: c_int,
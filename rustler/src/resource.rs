@@ -8,6 +8,7 @@ use std::marker::PhantomData;
 use std::mem;
 use std::ops::Deref;
 use std::ptr;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use super::{Decoder, Encoder, Env, Error, NifResult, Term};
 use crate::wrapper::{
@@ -29,6 +30,11 @@ pub struct ResourceType<T> {
     pub struct_type: PhantomData<T>,
 }
 
+// The resource type handle is only written while the library is loaded and never changes
+// afterwards.
+unsafe impl<T> Send for ResourceType<T> {}
+unsafe impl<T> Sync for ResourceType<T> {}
+
 /// This trait gets implemented for the type we want to put into a resource when
 /// resource! is called on it. It provides the ResourceType.
 ///
@@ -38,6 +44,62 @@ pub trait ResourceTypeProvider: Sized + Send + Sync + 'static {
     fn get_type() -> &'static ResourceType<Self>;
 }
 
+/// This trait gets implemented by `#[rustler::resource_impl]` for the type of the annotated impl
+/// block. The type is stored in the resource as a `ResourceLock<T>`.
+///
+/// In most cases the user should not have to worry about this.
+#[doc(hidden)]
+pub trait ResourceImpl: Sized + Send + Sync + 'static {
+    fn get_type() -> &'static ResourceType<ResourceLock<Self>>;
+}
+
+impl<T> ResourceTypeProvider for ResourceLock<T>
+where
+    T: ResourceImpl,
+{
+    fn get_type() -> &'static ResourceType<Self> {
+        T::get_type()
+    }
+}
+
+/// The value of a resource created by `#[rustler::resource_impl]`.
+///
+/// Methods taking `&self` are called with a read lock held, methods taking `&mut self` with a
+/// write lock. Other NIFs can accept the resource as a `ResourceArc<ResourceLock<T>>`.
+pub struct ResourceLock<T> {
+    inner: RwLock<T>,
+}
+
+impl<T> ResourceLock<T> {
+    pub fn new(data: T) -> Self {
+        ResourceLock {
+            inner: RwLock::new(data),
+        }
+    }
+
+    /// Locks the value for reading.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a NIF panicked while holding the write lock.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.inner
+            .read()
+            .expect("a NIF panicked while holding the resource lock")
+    }
+
+    /// Locks the value for writing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a NIF panicked while holding the write lock.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.inner
+            .write()
+            .expect("a NIF panicked while holding the resource lock")
+    }
+}
+
 impl<T> Encoder for ResourceArc<T>
 where
     T: ResourceTypeProvider,
@@ -0,0 +1,444 @@
+//! Generation of Elixir and Erlang stub modules from NIF metadata.
+//!
+//! Every function annotated with `#[rustler::nif]` carries a description of its signature in
+//! `Nif::METADATA`, and `rustler::init!` collects the metadata of all exported functions into a
+//! `RUSTLER_NIF_MODULE` constant at the root of the NIF crate. The generators in this module turn
+//! that description into the source of an Elixir or Erlang module that contains a stub for each
+//! NIF, along with a typespec derived from the Rust signature.
+//!
+//! Keeping the generated module in sync with the Rust code avoids arity mismatches that would
+//! otherwise only be detected when the library is loaded.
+//!
+//! ```ignore
+//! #[rustler::nif]
+//! fn add(a: i64, b: i64) -> i64 {
+//!     a + b
+//! }
+//!
+//! rustler::init!("Elixir.Math", [add]);
+//!
+//! #[test]
+//! fn generate_stubs() {
+//!     rustler::stubs::Elixir::new()
+//!         .preamble("use Rustler, otp_app: :math, crate: :math")
+//!         .write(&RUSTLER_NIF_MODULE, "../../lib/math.ex")
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! The generated Elixir module looks like this:
+//!
+//! ```elixir
+//! defmodule Math do
+//!   use Rustler, otp_app: :math, crate: :math
+//!
+//!   @spec add(a :: integer(), b :: integer()) :: integer()
+//!   def add(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+//! end
+//! ```
+//!
+//! Types are mapped from their Rust spelling. Types that Rustler does not know about, like
+//! structs using one of the derive macros, are specified as `term()`.
+
+use std::fmt::{self, Write};
+use std::io;
+use std::path::Path;
+
+/// A language-independent description of an Erlang type, as used in typespecs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeSpec {
+    /// Any term, `term()`.
+    Any,
+    /// `integer()`
+    Integer,
+    /// `non_neg_integer()`
+    NonNegInteger,
+    /// `float()`
+    Float,
+    /// `boolean()`
+    Boolean,
+    /// `atom()`
+    Atom,
+    /// A binary holding arbitrary bytes, `binary()`.
+    Binary,
+    /// A binary holding UTF-8, `String.t()` in Elixir and `binary()` in Erlang.
+    String,
+    /// `pid()`
+    Pid,
+    /// `reference()`, which is also how resources are represented.
+    Reference,
+    /// A proper list with elements of the given type.
+    List(&'static TypeSpec),
+    /// A tuple with elements of the given types.
+    Tuple(&'static [TypeSpec]),
+    /// A map with keys and values of the given types.
+    Map(&'static TypeSpec, &'static TypeSpec),
+    /// The given type or `nil`.
+    Option(&'static TypeSpec),
+    /// `{:ok, T} | {:error, E}`
+    Result(&'static TypeSpec, &'static TypeSpec),
+}
+
+/// A single argument of a NIF.
+#[derive(Debug, Clone, Copy)]
+pub struct NifArgument {
+    /// The name of the argument in the Rust function, without leading underscores. Arguments
+    /// without a name, like `_` or patterns, are named after their position, e.g. `arg0`.
+    pub name: &'static str,
+    /// The Rust type of the argument, as written in the function signature.
+    pub rust_type: &'static str,
+    /// The type of the argument.
+    pub spec: TypeSpec,
+}
+
+/// The description of a NIF, generated by `#[rustler::nif]`.
+#[derive(Debug, Clone, Copy)]
+pub struct NifMetadata {
+    /// The name of the function as seen from Erlang.
+    pub name: &'static str,
+    /// The number of arguments, not counting `Env`.
+    pub arity: u32,
+    /// The arguments, not including `Env`.
+    pub args: &'static [NifArgument],
+    /// The type of the returned term.
+    pub returns: TypeSpec,
+    /// The scheduler the NIF runs on: `"Normal"`, `"DirtyCpu"` or `"DirtyIo"`.
+    pub schedule: &'static str,
+    /// The number of trailing `Option` arguments that can be left out. The NIF is exported once
+    /// for each arity from `arity - optional` to `arity`.
+    pub optional: u32,
+}
+
+impl NifMetadata {
+    /// Returns the metadata of each exported arity, from the shortest to the full one.
+    fn arities(&self) -> impl Iterator<Item = NifMetadata> + '_ {
+        (0..=self.optional).rev().map(move |omitted| NifMetadata {
+            arity: self.arity - omitted,
+            args: &self.args[..self.args.len() - omitted as usize],
+            optional: 0,
+            ..*self
+        })
+    }
+}
+
+/// The description of a NIF library, generated by `rustler::init!` as `RUSTLER_NIF_MODULE`.
+#[derive(Debug, Clone, Copy)]
+pub struct NifModule {
+    /// The name of the module the NIFs are loaded into, e.g. `"Elixir.Math"`.
+    pub name: &'static str,
+    /// The NIFs listed in `rustler::init!`, or `None` if all functions annotated with
+    /// `#[rustler::nif]` in the crate are exported.
+    pub nifs: Option<&'static [NifMetadata]>,
+    /// The `module_path!()` of the `rustler::init!` call, which tells the crate whose NIFs are
+    /// exported when `nifs` is `None`.
+    pub module_path: &'static str,
+}
+
+impl NifModule {
+    /// The exported NIFs, with one entry per arity. Automatically registered NIFs are sorted by
+    /// name and arity.
+    pub fn nifs(&self) -> Vec<NifMetadata> {
+        let mut nifs: Vec<NifMetadata> = match self.nifs {
+            Some(nifs) => nifs.to_vec(),
+            None => crate::codegen_runtime::registered_nifs(self.module_path)
+                .map(|nif| nif.metadata)
+                .collect(),
+        };
+
+        if self.nifs.is_none() {
+            nifs.sort_by_key(|nif| (nif.name, nif.arity));
+        }
+
+        nifs.iter().flat_map(NifMetadata::arities).collect()
+    }
+
+    /// The module name as written in Elixir code, e.g. `Math` for `"Elixir.Math"`.
+    ///
+    /// Erlang module names are returned as atoms, e.g. `:math`.
+    pub fn elixir_name(&self) -> String {
+        match self.name.strip_prefix("Elixir.") {
+            Some(name) => name.to_string(),
+            None => format!(":{}", self.name),
+        }
+    }
+}
+
+/// Generates an Elixir module with a stub for each NIF.
+#[derive(Debug, Clone)]
+pub struct Elixir {
+    preamble: Vec<String>,
+    specs: bool,
+}
+
+impl Elixir {
+    /// Creates a generator emitting typespecs and no preamble.
+    pub fn new() -> Self {
+        Elixir {
+            preamble: Vec::new(),
+            specs: true,
+        }
+    }
+
+    /// Adds a line at the top of the module body, for example `use Rustler, ...`.
+    pub fn preamble(mut self, line: &str) -> Self {
+        self.preamble.push(line.to_string());
+        self
+    }
+
+    /// Sets whether `@spec` attributes are emitted. Defaults to `true`.
+    pub fn specs(mut self, specs: bool) -> Self {
+        self.specs = specs;
+        self
+    }
+
+    /// Returns the source of the Elixir module.
+    pub fn generate(&self, module: &NifModule) -> String {
+        let mut out = String::new();
+        self.write_module(&mut out, module)
+            .expect("writing to a String does not fail");
+        out
+    }
+
+    /// Writes the Elixir module to `path`. The file is left untouched if it is up to date.
+    pub fn write<P: AsRef<Path>>(&self, module: &NifModule, path: P) -> io::Result<()> {
+        write_if_changed(path.as_ref(), &self.generate(module))
+    }
+
+    fn write_module(&self, out: &mut String, module: &NifModule) -> fmt::Result {
+        let nifs = module.nifs();
+        writeln!(out, "defmodule {} do", module.elixir_name())?;
+        for line in &self.preamble {
+            writeln!(out, "  {}", line)?;
+        }
+
+        for nif in &nifs {
+            writeln!(out)?;
+
+            if self.specs {
+                let args: Vec<String> = nif
+                    .args
+                    .iter()
+                    .map(|arg| format!("{} :: {}", arg.name, elixir_type(&arg.spec)))
+                    .collect();
+                writeln!(
+                    out,
+                    "  @spec {}({}) :: {}",
+                    nif.name,
+                    args.join(", "),
+                    elixir_type(&nif.returns)
+                )?;
+            }
+
+            let args: Vec<String> = nif
+                .args
+                .iter()
+                .map(|arg| format!("_{}", arg.name))
+                .collect();
+            writeln!(
+                out,
+                "  def {}({}), do: :erlang.nif_error(:nif_not_loaded)",
+                nif.name,
+                args.join(", ")
+            )?;
+        }
+
+        writeln!(out, "end")
+    }
+}
+
+impl Default for Elixir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates an Erlang module with a stub for each NIF.
+#[derive(Debug, Clone)]
+pub struct Erlang {
+    preamble: Vec<String>,
+    specs: bool,
+}
+
+impl Erlang {
+    /// Creates a generator emitting typespecs and no preamble.
+    pub fn new() -> Self {
+        Erlang {
+            preamble: Vec::new(),
+            specs: true,
+        }
+    }
+
+    /// Adds a line after the `-export` attribute, for example `-on_load(init/0).`.
+    pub fn preamble(mut self, line: &str) -> Self {
+        self.preamble.push(line.to_string());
+        self
+    }
+
+    /// Sets whether `-spec` attributes are emitted. Defaults to `true`.
+    pub fn specs(mut self, specs: bool) -> Self {
+        self.specs = specs;
+        self
+    }
+
+    /// Returns the source of the Erlang module.
+    pub fn generate(&self, module: &NifModule) -> String {
+        let mut out = String::new();
+        self.write_module(&mut out, module)
+            .expect("writing to a String does not fail");
+        out
+    }
+
+    /// Writes the Erlang module to `path`. The file is left untouched if it is up to date.
+    pub fn write<P: AsRef<Path>>(&self, module: &NifModule, path: P) -> io::Result<()> {
+        write_if_changed(path.as_ref(), &self.generate(module))
+    }
+
+    fn write_module(&self, out: &mut String, module: &NifModule) -> fmt::Result {
+        writeln!(out, "-module({}).", erlang_atom(module.name))?;
+
+        let nifs = module.nifs();
+        let exports: Vec<String> = nifs
+            .iter()
+            .map(|nif| format!("{}/{}", erlang_atom(nif.name), nif.arity))
+            .collect();
+        writeln!(out, "-export([{}]).", exports.join(", "))?;
+
+        for line in &self.preamble {
+            writeln!(out, "{}", line)?;
+        }
+
+        for nif in &nifs {
+            let name = erlang_atom(nif.name);
+            writeln!(out)?;
+
+            if self.specs {
+                let args: Vec<String> = nif
+                    .args
+                    .iter()
+                    .map(|arg| {
+                        format!(
+                            "{} :: {}",
+                            erlang_variable(arg.name),
+                            erlang_type(&arg.spec)
+                        )
+                    })
+                    .collect();
+                writeln!(
+                    out,
+                    "-spec {}({}) -> {}.",
+                    name,
+                    args.join(", "),
+                    erlang_type(&nif.returns)
+                )?;
+            }
+
+            let args: Vec<String> = nif
+                .args
+                .iter()
+                .map(|arg| format!("_{}", erlang_variable(arg.name)))
+                .collect();
+            writeln!(
+                out,
+                "{}({}) ->\n    erlang:nif_error(nif_not_loaded).",
+                name,
+                args.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Erlang {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn write_if_changed(path: &Path, contents: &str) -> io::Result<()> {
+    match std::fs::read_to_string(path) {
+        Ok(ref existing) if existing == contents => Ok(()),
+        _ => std::fs::write(path, contents),
+    }
+}
+
+fn elixir_type(spec: &TypeSpec) -> String {
+    match *spec {
+        TypeSpec::Any => "term()".to_string(),
+        TypeSpec::Integer => "integer()".to_string(),
+        TypeSpec::NonNegInteger => "non_neg_integer()".to_string(),
+        TypeSpec::Float => "float()".to_string(),
+        TypeSpec::Boolean => "boolean()".to_string(),
+        TypeSpec::Atom => "atom()".to_string(),
+        TypeSpec::Binary => "binary()".to_string(),
+        TypeSpec::String => "String.t()".to_string(),
+        TypeSpec::Pid => "pid()".to_string(),
+        TypeSpec::Reference => "reference()".to_string(),
+        TypeSpec::List(inner) => format!("[{}]", elixir_type(inner)),
+        TypeSpec::Tuple(elements) => {
+            let elements: Vec<String> = elements.iter().map(elixir_type).collect();
+            format!("{{{}}}", elements.join(", "))
+        }
+        TypeSpec::Map(key, value) => {
+            format!(
+                "%{{optional({}) => {}}}",
+                elixir_type(key),
+                elixir_type(value)
+            )
+        }
+        TypeSpec::Option(inner) => format!("{} | nil", elixir_type(inner)),
+        TypeSpec::Result(ok, error) => format!(
+            "{{:ok, {}}} | {{:error, {}}}",
+            elixir_type(ok),
+            elixir_type(error)
+        ),
+    }
+}
+
+fn erlang_type(spec: &TypeSpec) -> String {
+    match *spec {
+        TypeSpec::String => "binary()".to_string(),
+        TypeSpec::List(inner) => format!("[{}]", erlang_type(inner)),
+        TypeSpec::Tuple(elements) => {
+            let elements: Vec<String> = elements.iter().map(erlang_type).collect();
+            format!("{{{}}}", elements.join(", "))
+        }
+        TypeSpec::Map(key, value) => {
+            format!("#{{{} => {}}}", erlang_type(key), erlang_type(value))
+        }
+        TypeSpec::Option(inner) => format!("{} | nil", erlang_type(inner)),
+        TypeSpec::Result(ok, error) => format!(
+            "{{ok, {}}} | {{error, {}}}",
+            erlang_type(ok),
+            erlang_type(error)
+        ),
+        ref other => elixir_type(other),
+    }
+}
+
+/// Quotes an atom if it is not a valid unquoted Erlang atom.
+fn erlang_atom(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = matches!(chars.next(), Some(c) if c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@');
+
+    if plain {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+}
+
+/// Turns a Rust argument name into an Erlang variable name, e.g. `max_len` into `MaxLen`.
+fn erlang_variable(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,398 @@
+//! The terms of the test environment. Each term is a pointer to an immutable `Node` that is never
+//! freed, so terms stay valid in every environment until the test process exits.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use crate::types::value::{BigInt, Opaque, Value};
+use crate::wrapper::NIF_TERM;
+
+pub(super) enum Node {
+    Atom(String),
+    Integer(i64),
+    BigInt(BigInt),
+    Float(f64),
+    Binary(Vec<u8>),
+    Nil,
+    Cons(NIF_TERM, NIF_TERM),
+    Tuple(Vec<NIF_TERM>),
+    /// The entries of a map, sorted by key.
+    Map(Vec<(NIF_TERM, NIF_TERM)>),
+    Pid(Opaque),
+    Port(Opaque),
+    Reference(Opaque),
+    Fun(Opaque),
+    /// A resource object, as returned by `enif_alloc_resource`.
+    Resource(*const c_void),
+    /// The value returned by a NIF raising an exception.
+    Exception,
+}
+
+lazy_static::lazy_static! {
+    static ref ATOMS: Mutex<HashMap<String, NIF_TERM>> = Mutex::new(HashMap::new());
+    static ref NIL: NIF_TERM = alloc(Node::Nil);
+    static ref EXCEPTION: NIF_TERM = alloc(Node::Exception);
+}
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+const NODE_NAME: &[u8] = b"nonode@nohost";
+/// Marks the references standing for resources when converting terms to values.
+const RESOURCE_MARKER: u32 = 0x5253_5243;
+
+const NEW_PID_EXT: u8 = 88;
+const NEWER_REFERENCE_EXT: u8 = 90;
+const SMALL_ATOM_UTF8_EXT: u8 = 119;
+
+pub(super) fn alloc(node: Node) -> NIF_TERM {
+    Box::into_raw(Box::new(node)) as NIF_TERM
+}
+
+/// The node of a term.
+///
+/// # Safety
+///
+/// `term` must have been returned by one of the functions of this module.
+pub(super) unsafe fn node<'a>(term: NIF_TERM) -> &'a Node {
+    &*(term as *const Node)
+}
+
+/// The atom named `name`. Atoms are interned, so that equal atoms are the same term.
+pub(super) fn atom(name: &str) -> NIF_TERM {
+    *ATOMS
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_insert_with(|| alloc(Node::Atom(name.to_string())))
+}
+
+pub(super) fn existing_atom(name: &str) -> Option<NIF_TERM> {
+    ATOMS.lock().unwrap().get(name).copied()
+}
+
+pub(super) fn nil() -> NIF_TERM {
+    *NIL
+}
+
+pub(super) fn exception() -> NIF_TERM {
+    *EXCEPTION
+}
+
+pub(super) fn integer(integer: i64) -> NIF_TERM {
+    alloc(Node::Integer(integer))
+}
+
+pub(super) fn big_integer(int: BigInt) -> NIF_TERM {
+    match int.to_i64() {
+        Some(integer) => self::integer(integer),
+        None => alloc(Node::BigInt(int)),
+    }
+}
+
+pub(super) fn list(items: &[NIF_TERM], tail: NIF_TERM) -> NIF_TERM {
+    items
+        .iter()
+        .rev()
+        .fold(tail, |list, &item| alloc(Node::Cons(item, list)))
+}
+
+/// A map with the given entries, or `None` if a key appears twice.
+pub(super) fn map(mut entries: Vec<(NIF_TERM, NIF_TERM)>) -> Option<NIF_TERM> {
+    let mut keyed: Vec<_> = entries
+        .drain(..)
+        .map(|(key, value)| (to_value(key), key, value))
+        .collect();
+    keyed.sort_by(|a, b| a.0.cmp(&b.0));
+    if keyed.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return None;
+    }
+    Some(alloc(Node::Map(
+        keyed
+            .into_iter()
+            .map(|(_, key, value)| (key, value))
+            .collect(),
+    )))
+}
+
+/// The items and tail of a list, or `None` if `term` is not a list.
+pub(super) fn list_items(term: NIF_TERM) -> Option<(Vec<NIF_TERM>, NIF_TERM)> {
+    let mut items = Vec::new();
+    let mut rest = term;
+    loop {
+        match unsafe { node(rest) } {
+            Node::Cons(head, tail) => {
+                items.push(*head);
+                rest = *tail;
+            }
+            Node::Nil => return Some((items, rest)),
+            _ if items.is_empty() => return None,
+            _ => return Some((items, rest)),
+        }
+    }
+}
+
+/// A new pid, as in `term_to_binary/1`.
+pub(super) fn new_pid() -> NIF_TERM {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut bytes = vec![131, NEW_PID_EXT, SMALL_ATOM_UTF8_EXT, NODE_NAME.len() as u8];
+    bytes.extend_from_slice(NODE_NAME);
+    bytes.extend_from_slice(&id.to_be_bytes());
+    bytes.extend_from_slice(&[0; 8]);
+    alloc(Node::Pid(Opaque(bytes)))
+}
+
+pub(super) fn new_reference() -> NIF_TERM {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut bytes = vec![131, NEWER_REFERENCE_EXT, 0, 3, SMALL_ATOM_UTF8_EXT];
+    bytes.push(NODE_NAME.len() as u8);
+    bytes.extend_from_slice(NODE_NAME);
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(&id.to_be_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    alloc(Node::Reference(Opaque(bytes)))
+}
+
+/// A reference standing for a resource object when a term is converted to a `Value`.
+fn resource_reference(resource: *const c_void) -> Opaque {
+    let address = resource as u64;
+    let mut bytes = vec![131, NEWER_REFERENCE_EXT, 0, 3, SMALL_ATOM_UTF8_EXT];
+    bytes.push(NODE_NAME.len() as u8);
+    bytes.extend_from_slice(NODE_NAME);
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&RESOURCE_MARKER.to_be_bytes());
+    bytes.extend_from_slice(&(address as u32).to_be_bytes());
+    bytes.extend_from_slice(&((address >> 32) as u32).to_be_bytes());
+    Opaque(bytes)
+}
+
+/// The resource object a reference made by `resource_reference` stands for.
+fn reference_resource(reference: &Opaque) -> Option<*const c_void> {
+    let bytes = reference.as_bytes();
+    let ids = bytes.get(bytes.len().checked_sub(12)?..)?;
+    let word = |index: usize| {
+        u32::from_be_bytes([
+            ids[index * 4],
+            ids[index * 4 + 1],
+            ids[index * 4 + 2],
+            ids[index * 4 + 3],
+        ])
+    };
+
+    if bytes.len() != 4 + NODE_NAME.len() + 2 + 4 + 12 || word(0) != RESOURCE_MARKER {
+        return None;
+    }
+    let address = u64::from(word(1)) | (u64::from(word(2)) << 32);
+    Some(address as usize as *const c_void)
+}
+
+pub(super) fn to_value(term: NIF_TERM) -> Value {
+    match unsafe { node(term) } {
+        Node::Atom(name) => Value::Atom(name.clone()),
+        Node::Integer(integer) => Value::Integer(*integer),
+        Node::BigInt(int) => Value::BigInt(int.clone()),
+        Node::Float(float) => Value::Float(*float),
+        Node::Binary(bytes) => Value::Binary(bytes.clone()),
+        Node::Nil | Node::Cons(_, _) => {
+            let (items, tail) = list_items(term).unwrap();
+            let items = items.into_iter().map(to_value).collect();
+            match unsafe { node(tail) } {
+                Node::Nil => Value::List(items),
+                _ => Value::ImproperList(items, Box::new(to_value(tail))),
+            }
+        }
+        Node::Tuple(items) => Value::Tuple(items.iter().map(|&item| to_value(item)).collect()),
+        Node::Map(entries) => Value::Map(
+            entries
+                .iter()
+                .map(|&(key, value)| (to_value(key), to_value(value)))
+                .collect(),
+        ),
+        Node::Pid(opaque) => Value::Pid(opaque.clone()),
+        Node::Port(opaque) => Value::Port(opaque.clone()),
+        Node::Reference(opaque) => Value::Reference(opaque.clone()),
+        Node::Fun(opaque) => Value::Fun(opaque.clone()),
+        Node::Resource(resource) => Value::Reference(resource_reference(*resource)),
+        Node::Exception => panic!("the exception marker is not a term"),
+    }
+}
+
+pub(super) fn from_value(value: &Value) -> NIF_TERM {
+    match value {
+        Value::Atom(name) => atom(name),
+        Value::Integer(integer) => self::integer(*integer),
+        Value::BigInt(int) => big_integer(int.clone()),
+        Value::Float(float) => alloc(Node::Float(*float)),
+        Value::Binary(bytes) => alloc(Node::Binary(bytes.clone())),
+        Value::List(items) => {
+            let items: Vec<_> = items.iter().map(from_value).collect();
+            list(&items, nil())
+        }
+        Value::ImproperList(items, tail) => {
+            let items: Vec<_> = items.iter().map(from_value).collect();
+            list(&items, from_value(tail))
+        }
+        Value::Tuple(items) => alloc(Node::Tuple(items.iter().map(from_value).collect())),
+        Value::Map(map) => alloc(Node::Map(
+            map.iter()
+                .map(|(key, value)| (from_value(key), from_value(value)))
+                .collect(),
+        )),
+        Value::Pid(opaque) => alloc(Node::Pid(opaque.clone())),
+        Value::Port(opaque) => alloc(Node::Port(opaque.clone())),
+        Value::Reference(opaque) => match reference_resource(opaque) {
+            Some(resource) => {
+                unsafe { super::nif_api::enif_keep_resource(resource) };
+                alloc(Node::Resource(resource))
+            }
+            None => alloc(Node::Reference(opaque.clone())),
+        },
+        Value::Fun(opaque) => alloc(Node::Fun(opaque.clone())),
+    }
+}
+
+/// Formats a term in Erlang syntax, like `enif_snprintf` with `%T`.
+pub(super) fn format(term: NIF_TERM, f: &mut fmt::Formatter) -> fmt::Result {
+    match unsafe { node(term) } {
+        Node::Atom(name) => format_atom(name, f),
+        Node::Integer(integer) => write!(f, "{}", integer),
+        Node::BigInt(int) => write!(f, "{}", big_to_string(int)),
+        Node::Float(float) => {
+            let text = format!("{:?}", float);
+            match text.find('e') {
+                Some(index) if !text[..index].contains('.') => {
+                    write!(f, "{}.0{}", &text[..index], &text[index..])
+                }
+                _ => f.write_str(&text),
+            }
+        }
+        Node::Binary(bytes) if !bytes.is_empty() && bytes.iter().all(is_printable) => {
+            write!(f, "<<\"{}\">>", escape(bytes, '"'))
+        }
+        Node::Binary(bytes) => {
+            f.write_str("<<")?;
+            format_separated(bytes, f, |byte, f| write!(f, "{}", byte))?;
+            f.write_str(">>")
+        }
+        Node::Nil | Node::Cons(_, _) => {
+            let (items, tail) = list_items(term).unwrap();
+            if let Some(string) = printable_string(&items, tail) {
+                return write!(f, "\"{}\"", escape(&string, '"'));
+            }
+            f.write_str("[")?;
+            format_separated(&items, f, |&item, f| format(item, f))?;
+            if !matches!(unsafe { node(tail) }, Node::Nil) {
+                f.write_str("|")?;
+                format(tail, f)?;
+            }
+            f.write_str("]")
+        }
+        Node::Tuple(items) => {
+            f.write_str("{")?;
+            format_separated(items, f, |&item, f| format(item, f))?;
+            f.write_str("}")
+        }
+        Node::Map(entries) => {
+            f.write_str("#{")?;
+            format_separated(entries, f, |&(key, value), f| {
+                format(key, f)?;
+                f.write_str(" => ")?;
+                format(value, f)
+            })?;
+            f.write_str("}")
+        }
+        Node::Pid(opaque) => write!(f, "<0.{}.0>", id(opaque)),
+        Node::Port(opaque) => write!(f, "#Port<0.{}>", id(opaque)),
+        Node::Reference(opaque) => write!(f, "#Ref<0.{}>", id(opaque)),
+        Node::Resource(resource) => write!(f, "#Ref<0.{:?}>", resource),
+        Node::Fun(opaque) => write!(f, "#Fun<{}>", id(opaque)),
+        Node::Exception => f.write_str("THE_NON_VALUE"),
+    }
+}
+
+fn format_separated<T>(
+    items: &[T],
+    f: &mut fmt::Formatter,
+    mut format_item: impl FnMut(&T, &mut fmt::Formatter) -> fmt::Result,
+) -> fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            f.write_str(",")?;
+        }
+        format_item(item, f)?;
+    }
+    Ok(())
+}
+
+fn format_atom(name: &str, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut chars = name.chars();
+    let bare = matches!(chars.next(), Some(c) if c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@');
+    if bare {
+        f.write_str(name)
+    } else {
+        write!(f, "'{}'", escape(name.as_bytes(), '\''))
+    }
+}
+
+fn is_printable(byte: &u8) -> bool {
+    (0x20..0x7f).contains(byte)
+}
+
+fn escape(bytes: &[u8], quote: char) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        let c = char::from(byte);
+        if c == quote || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The characters of a proper list of printable characters, which Erlang prints as a string.
+fn printable_string(items: &[NIF_TERM], tail: NIF_TERM) -> Option<Vec<u8>> {
+    if items.is_empty() || !matches!(unsafe { node(tail) }, Node::Nil) {
+        return None;
+    }
+    items
+        .iter()
+        .map(|&item| match unsafe { node(item) } {
+            Node::Integer(c) => u8::try_from(*c).ok().filter(is_printable),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A number identifying a pid, port, reference or fun when formatting it.
+fn id(opaque: &Opaque) -> u32 {
+    opaque.as_bytes().iter().fold(0u32, |hash, &byte| {
+        hash.wrapping_mul(31).wrapping_add(byte.into())
+    })
+}
+
+fn big_to_string(int: &BigInt) -> String {
+    let mut magnitude = int.magnitude().to_vec();
+    let mut digits = Vec::new();
+    while !magnitude.is_empty() {
+        let mut remainder = 0u32;
+        for byte in magnitude.iter_mut().rev() {
+            let current = (remainder << 8) | u32::from(*byte);
+            *byte = (current / 10) as u8;
+            remainder = current % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+    }
+    if int.is_negative() {
+        digits.push(b'-');
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
@@ -0,0 +1,309 @@
+//! Calling NIFs from `cargo test`, without a BEAM.
+//!
+//! With the `testing` feature, rustler provides its own implementation of the NIF API, with terms
+//! kept in Rust memory. [`with_env`] runs a closure with an `Env` standing in for the environment
+//! of a process calling a NIF, and [`call`] calls a `#[rustler::nif]` function with terms built in
+//! that environment, returning the result or the exception it raised:
+//!
+//! ```
+//! # use rustler::{Encoder, Error, NifResult};
+//! use rustler::testing::{self, Exception};
+//!
+//! #[rustler::nif]
+//! fn add(a: i64, b: i64) -> NifResult<i64> {
+//!     a.checked_add(b).ok_or(Error::RaiseAtom("overflow"))
+//! }
+//!
+//! testing::with_env(|env| {
+//!     let result = testing::call(env, add, &[1.encode(env), 2.encode(env)]).unwrap();
+//!     assert_eq!(result.decode::<i64>().unwrap(), 3);
+//!
+//!     let result = testing::call(env, add, &[1.encode(env), "two".encode(env)]);
+//!     assert!(matches!(result, Err(Exception::BadArg)));
+//!
+//!     match testing::call(env, add, &[i64::MAX.encode(env), 1.encode(env)]) {
+//!         Err(Exception::Raise(reason)) => assert_eq!(reason.atom_to_string().unwrap(), "overflow"),
+//!         other => panic!("unexpected {:?}", other),
+//!     }
+//! });
+//! ```
+//!
+//! Terms built with `Encoder`, [`Value`](crate::Value) or [`term!`](crate::term) and decoded with
+//! `Decoder` behave as in the BEAM. Messages sent to the process of the environment, from the NIF
+//! or from threads with `OwnedEnv`, can be read with [`receive`]. Resource types have to be
+//! registered first, by calling the `load` function of the library with the test environment.
+//!
+//! Terms are never garbage collected, so resources that were turned into terms are never
+//! destroyed, and the test environment is slower than the BEAM. It is only meant for tests.
+//! Behaviour that depends on the VM can be tested with `rustler::beam`, behind the `beam`
+//! feature, which loads the library into a real node instead.
+//!
+//! The feature must never be enabled when building the library loaded by the BEAM, since its NIF
+//! API would replace the one of the BEAM; `rustler::init!` fails to compile with it outside of
+//! `#[cfg(test)]` builds. Enable it in `[dev-dependencies]`, with the version 2 feature resolver
+//! (the default since edition 2021) so that the feature does not leak into regular builds. The
+//! test environment is not available on Windows, where NIFs call the BEAM through a table of
+//! function pointers.
+
+#[cfg(windows)]
+compile_error!("The `testing` feature of rustler is not supported on Windows");
+
+mod heap;
+mod nif_api;
+
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+use std::sync::{Condvar, Mutex, Once};
+use std::time::{Duration, Instant};
+
+use crate::wrapper::{NIF_ENV, NIF_TERM};
+use crate::{Atom, Env, Nif, Term};
+
+/// An exception raised by a NIF.
+#[derive(Debug)]
+pub enum Exception<'a> {
+    /// The NIF returned `Error::BadArg`, for example because an argument could not be decoded.
+    BadArg,
+    /// The NIF raised `reason`. Panics are raised as `nif_panicked`.
+    Raise(Term<'a>),
+}
+
+/// An exception raised by `enif_make_badarg` or `enif_raise_exception` and not returned yet.
+#[derive(Clone, Copy)]
+enum Pending {
+    BadArg,
+    Raise(NIF_TERM),
+}
+
+/// The `ErlNifEnv` of the test environment.
+struct TestEnv {
+    /// The process of the environment, or `None` for a process independent environment.
+    pid: Option<NIF_TERM>,
+    exception: Cell<Option<Pending>>,
+}
+
+impl TestEnv {
+    fn new(pid: Option<NIF_TERM>) -> Self {
+        TestEnv {
+            pid,
+            exception: Cell::new(None),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    /// The mailboxes of the processes that are alive, by pid.
+    static ref MAILBOXES: Mutex<HashMap<Vec<u8>, VecDeque<NIF_TERM>>> = Mutex::new(HashMap::new());
+    static ref DELIVERED: Condvar = Condvar::new();
+    /// The registered names of processes, as atoms, and their pids.
+    static ref REGISTERED: Mutex<HashMap<NIF_TERM, NIF_TERM>> = Mutex::new(HashMap::new());
+}
+
+thread_local! {
+    static THREAD_TYPE: Cell<c_int> = const { Cell::new(rustler_sys::ERL_NIF_THR_UNDEFINED) };
+}
+
+/// Makes the current thread look like a scheduler thread to `enif_thread_type` until dropped.
+struct ThreadType(c_int);
+
+impl ThreadType {
+    fn set(thread_type: c_int) -> Self {
+        ThreadType(THREAD_TYPE.with(|current| current.replace(thread_type)))
+    }
+
+    /// The scheduler running a NIF with the given flags.
+    fn for_flags(flags: u32) -> Self {
+        ThreadType::set(match flags {
+            rustler_sys::ERL_NIF_DIRTY_JOB_CPU_BOUND => {
+                rustler_sys::ERL_NIF_THR_DIRTY_CPU_SCHEDULER
+            }
+            rustler_sys::ERL_NIF_DIRTY_JOB_IO_BOUND => rustler_sys::ERL_NIF_THR_DIRTY_IO_SCHEDULER,
+            _ => rustler_sys::ERL_NIF_THR_NORMAL_SCHEDULER,
+        })
+    }
+}
+
+impl Drop for ThreadType {
+    fn drop(&mut self) {
+        THREAD_TYPE.with(|current| current.set(self.0));
+    }
+}
+
+fn thread_type() -> c_int {
+    THREAD_TYPE.with(Cell::get)
+}
+
+fn pid_key(pid: NIF_TERM) -> Vec<u8> {
+    match unsafe { heap::node(pid) } {
+        heap::Node::Pid(opaque) => opaque.as_bytes().to_vec(),
+        _ => panic!("not a pid"),
+    }
+}
+
+fn is_alive(pid: NIF_TERM) -> bool {
+    MAILBOXES.lock().unwrap().contains_key(&pid_key(pid))
+}
+
+/// The process registered as `name`, if it is alive.
+fn whereis(name: NIF_TERM) -> Option<NIF_TERM> {
+    let pid = *REGISTERED.lock().unwrap().get(&name)?;
+    if is_alive(pid) {
+        Some(pid)
+    } else {
+        None
+    }
+}
+
+/// Puts a message in the mailbox of `pid`, returning `false` if the process is not alive.
+fn deliver(pid: NIF_TERM, message: NIF_TERM) -> bool {
+    match MAILBOXES.lock().unwrap().get_mut(&pid_key(pid)) {
+        Some(mailbox) => {
+            mailbox.push_back(message);
+            DELIVERED.notify_all();
+            true
+        }
+        None => false,
+    }
+}
+
+/// A process of the test environment, which exits when dropped.
+struct Process(NIF_TERM);
+
+impl Process {
+    fn spawn() -> Self {
+        let pid = heap::new_pid();
+        MAILBOXES
+            .lock()
+            .unwrap()
+            .insert(pid_key(pid), VecDeque::new());
+        Process(pid)
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        MAILBOXES.lock().unwrap().remove(&pid_key(self.0));
+
+        let key = pid_key(self.0);
+        REGISTERED
+            .lock()
+            .unwrap()
+            .retain(|_, pid| pid_key(*pid) != key);
+    }
+}
+
+/// Runs `f` with the environment of a new process, as if it was called from a NIF on a normal
+/// scheduler thread. The process exits when `f` returns.
+pub fn with_env<F, R>(f: F) -> R
+where
+    F: for<'a> FnOnce(Env<'a>) -> R,
+{
+    let process = Process::spawn();
+    let mut test_env = TestEnv::new(Some(process.0));
+    let _thread_type = ThreadType::set(rustler_sys::ERL_NIF_THR_NORMAL_SCHEDULER);
+
+    let lifetime = ();
+    let env = unsafe { Env::new(&lifetime, &mut test_env as *mut TestEnv as NIF_ENV) };
+
+    // Resource types of rustler itself, opened by `rustler::init!` in the BEAM.
+    static OPEN_RESOURCE_TYPES: Once = Once::new();
+    OPEN_RESOURCE_TYPES.call_once(|| {
+        assert!(crate::channel::open_resource_type(env));
+        assert!(crate::thread::open_resource_type(env));
+    });
+
+    f(env)
+}
+
+/// Calls the NIF `nif` with `args` in `env`, which must come from [`with_env`].
+///
+/// # Panics
+///
+/// Panics if the NIF is not exported with `args.len()` arguments.
+pub fn call<'a, N: Nif>(
+    env: Env<'a>,
+    _nif: N,
+    args: &[Term<'a>],
+) -> Result<Term<'a>, Exception<'a>> {
+    if !N::FUNCS
+        .iter()
+        .any(|func| func.arity as usize == args.len())
+    {
+        let name = unsafe { CStr::from_ptr(N::NAME as *const c_char) };
+        panic!(
+            "NIF {}/{} is not exported",
+            name.to_string_lossy(),
+            args.len()
+        );
+    }
+
+    let test_env = unsafe { &*(env.as_c_arg() as *const TestEnv) };
+    test_env.exception.set(None);
+
+    let argv: Vec<NIF_TERM> = args.iter().map(|arg| arg.as_c_arg()).collect();
+    let result = {
+        let _thread_type = ThreadType::for_flags(N::FLAGS);
+        unsafe { N::RAW_FUNC(env.as_c_arg(), argv.len() as c_int, argv.as_ptr()) }
+    };
+
+    match test_env.exception.take() {
+        None => Ok(unsafe { Term::new(env, result) }),
+        Some(Pending::BadArg) => Err(Exception::BadArg),
+        Some(Pending::Raise(reason)) => Err(Exception::Raise(unsafe { Term::new(env, reason) })),
+    }
+}
+
+/// Takes the oldest message from the mailbox of the process of `env`, waiting up to `timeout`
+/// for one to arrive.
+///
+/// # Panics
+///
+/// Panics if `env` is not the environment of a process from [`with_env`].
+pub fn receive(env: Env, timeout: Duration) -> Option<Term> {
+    let test_env = unsafe { &*(env.as_c_arg() as *const TestEnv) };
+    let key = pid_key(test_env.pid.expect("not the environment of a process"));
+    let deadline = Instant::now() + timeout;
+
+    let mut mailboxes = MAILBOXES.lock().unwrap();
+    loop {
+        if let Some(message) = mailboxes.get_mut(&key).and_then(VecDeque::pop_front) {
+            return Some(unsafe { Term::new(env, message) });
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        mailboxes = DELIVERED.wait_timeout(mailboxes, deadline - now).unwrap().0;
+    }
+}
+
+/// Registers the process of `env` as `name`, like `register/2`, so that NIFs can find it with
+/// `Env::whereis`. The name is unregistered when the process exits.
+///
+/// # Panics
+///
+/// Panics if `env` is not the environment of a process from [`with_env`], or if another process
+/// is registered as `name`.
+pub fn register(env: Env, name: Atom) {
+    let test_env = unsafe { &*(env.as_c_arg() as *const TestEnv) };
+    let pid = test_env.pid.expect("not the environment of a process");
+
+    let mut registered = REGISTERED.lock().unwrap();
+    if let Some(&other) = registered.get(&name.as_c_arg()) {
+        assert!(
+            !is_alive(other),
+            "another process is registered as {:?}",
+            name
+        );
+    }
+    registered.insert(name.as_c_arg(), pid);
+}
+
+/// Formats a term of the test environment, in place of `enif_snprintf`.
+pub(crate) fn fmt(term: NIF_TERM, f: &mut fmt::Formatter) -> fmt::Result {
+    heap::format(term, f)
+}
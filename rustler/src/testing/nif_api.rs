@@ -0,0 +1,1054 @@
+//! The functions of the NIF API used by rustler, implemented on top of the terms of `heap`.
+//!
+//! They are exported under the same names as the functions of the BEAM, so that they take their
+//! place when linking a test binary.
+
+use std::alloc::{self, Layout};
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_double, c_int, c_long, c_uchar, c_uint, c_ulong, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use rustler_sys::{
+    ErlNifBinary, ErlNifBinaryToTerm, ErlNifCharEncoding, ErlNifEnv, ErlNifMapIterator,
+    ErlNifMapIteratorEntry, ErlNifPid, ErlNifResourceFlags, ErlNifResourceType, ErlNifThreadOpts,
+    ErlNifTid, ERL_NIF_TERM,
+};
+
+use super::heap::{self, Node};
+use super::{Pending, TestEnv};
+use crate::etf;
+use crate::types::value::BigInt;
+
+type NifFunction = unsafe extern "C" fn(*mut ErlNifEnv, c_int, *const ERL_NIF_TERM) -> ERL_NIF_TERM;
+type ResourceDtor = unsafe extern "C" fn(*mut ErlNifEnv, *mut c_void);
+
+/// The layout of `ErlNifBinary`, whose private fields can't be set directly.
+#[repr(C)]
+struct RawBinary {
+    size: usize,
+    data: *mut u8,
+    ref_bin: *mut c_void,
+    spare: [*mut c_void; 2],
+}
+
+/// The layout of `ErlNifMapIterator`, whose fields are private.
+#[repr(C)]
+struct RawMapIterator {
+    map: ERL_NIF_TERM,
+    size: usize,
+    index: usize,
+    keys: *mut ERL_NIF_TERM,
+    values: *mut ERL_NIF_TERM,
+    spare: [*mut c_void; 2],
+}
+
+struct ResourceType {
+    dtor: Option<ResourceDtor>,
+}
+
+/// Stored right before the object returned by `enif_alloc_resource`.
+#[repr(C)]
+struct ResourceHeader {
+    resource_type: *const ResourceType,
+    refs: AtomicUsize,
+    size: usize,
+}
+
+const RESOURCE_ALIGN: usize = 16;
+const RESOURCE_HEADER_SIZE: usize = 32;
+
+unsafe fn write<T>(ptr: *mut T, value: T) -> c_int {
+    ptr.write(value);
+    1
+}
+
+unsafe fn test_env<'a>(env: *mut ErlNifEnv) -> Option<&'a TestEnv> {
+    (env as *const TestEnv).as_ref()
+}
+
+fn is(condition: bool) -> c_int {
+    condition as c_int
+}
+
+// Environments
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_alloc_env() -> *mut ErlNifEnv {
+    Box::into_raw(Box::new(TestEnv::new(None))) as *mut ErlNifEnv
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_free_env(env: *mut ErlNifEnv) {
+    drop(Box::from_raw(env as *mut TestEnv));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_clear_env(_env: *mut ErlNifEnv) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_copy(
+    _dst_env: *mut ErlNifEnv,
+    src_term: ERL_NIF_TERM,
+) -> ERL_NIF_TERM {
+    src_term
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_thread_type() -> c_int {
+    super::thread_type()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_thread_create(
+    name: *mut c_uchar,
+    tid: *mut ErlNifTid,
+    func: Option<unsafe extern "C" fn(*mut c_void) -> *mut c_void>,
+    args: *mut c_void,
+    _opts: *mut ErlNifThreadOpts,
+) -> c_int {
+    let func = match func {
+        Some(func) => func,
+        None => return 1,
+    };
+    let name = CStr::from_ptr(name as *const c_char)
+        .to_string_lossy()
+        .into_owned();
+    let args = args as usize;
+
+    match thread::Builder::new()
+        .name(name)
+        .spawn(move || unsafe { func(args as *mut c_void) as usize })
+    {
+        Ok(handle) => {
+            *tid = Box::into_raw(Box::new(handle)) as ErlNifTid;
+            0
+        }
+        Err(_) => 1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_thread_join(tid: ErlNifTid, respp: *mut *mut c_void) -> c_int {
+    let handle = Box::from_raw(tid as *mut thread::JoinHandle<usize>);
+    match handle.join() {
+        Ok(resp) => {
+            if !respp.is_null() {
+                *respp = resp as *mut c_void;
+            }
+            0
+        }
+        Err(_) => 1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_consume_timeslice(_env: *mut ErlNifEnv, _percent: c_int) -> c_int {
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_schedule_nif(
+    env: *mut ErlNifEnv,
+    _fun_name: *const c_uchar,
+    flags: c_int,
+    fp: NifFunction,
+    argc: c_int,
+    argv: *const ERL_NIF_TERM,
+) -> ERL_NIF_TERM {
+    let _thread_type = super::ThreadType::for_flags(flags as u32);
+    fp(env, argc, argv)
+}
+
+// Exceptions
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_badarg(env: *mut ErlNifEnv) -> ERL_NIF_TERM {
+    if let Some(env) = test_env(env) {
+        env.exception.set(Some(Pending::BadArg));
+    }
+    heap::exception()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_raise_exception(
+    env: *mut ErlNifEnv,
+    reason: ERL_NIF_TERM,
+) -> ERL_NIF_TERM {
+    if let Some(env) = test_env(env) {
+        env.exception.set(Some(Pending::Raise(reason)));
+    }
+    heap::exception()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_exception(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(term == heap::exception())
+}
+
+// Type checks and comparisons
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_atom(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Atom(_)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_binary(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Binary(_)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_empty_list(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Nil))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_fun(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Fun(_)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_list(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Nil | Node::Cons(_, _)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_map(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Map(_)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_number(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(
+        heap::node(term),
+        Node::Integer(_) | Node::BigInt(_) | Node::Float(_)
+    ))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_pid(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Pid(_)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_port(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Port(_)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_ref(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(
+        heap::node(term),
+        Node::Reference(_) | Node::Resource(_)
+    ))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_tuple(_env: *mut ErlNifEnv, term: ERL_NIF_TERM) -> c_int {
+    is(matches!(heap::node(term), Node::Tuple(_)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_identical(lhs: ERL_NIF_TERM, rhs: ERL_NIF_TERM) -> c_int {
+    is(lhs == rhs || heap::to_value(lhs) == heap::to_value(rhs))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_compare(lhs: ERL_NIF_TERM, rhs: ERL_NIF_TERM) -> c_int {
+    heap::to_value(lhs).cmp(&heap::to_value(rhs)) as c_int
+}
+
+// Numbers
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_int(_env: *mut ErlNifEnv, i: c_int) -> ERL_NIF_TERM {
+    heap::integer(i.into())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_uint(_env: *mut ErlNifEnv, i: c_uint) -> ERL_NIF_TERM {
+    heap::integer(i.into())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_long(_env: *mut ErlNifEnv, i: c_long) -> ERL_NIF_TERM {
+    heap::big_integer(BigInt::from(i))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_ulong(_env: *mut ErlNifEnv, i: c_ulong) -> ERL_NIF_TERM {
+    heap::big_integer(BigInt::from(i))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_double(env: *mut ErlNifEnv, d: c_double) -> ERL_NIF_TERM {
+    if d.is_finite() {
+        heap::alloc(Node::Float(d))
+    } else {
+        enif_make_badarg(env)
+    }
+}
+
+fn integer_of(term: ERL_NIF_TERM) -> Option<i128> {
+    match unsafe { heap::node(term) } {
+        Node::Integer(integer) => Some((*integer).into()),
+        Node::BigInt(int) => int.to_u64().map(i128::from),
+        _ => None,
+    }
+}
+
+unsafe fn get_integer<T: TryFrom<i128>>(term: ERL_NIF_TERM, ip: *mut T) -> c_int {
+    match integer_of(term).and_then(|integer| T::try_from(integer).ok()) {
+        Some(integer) => write(ip, integer),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_int(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    ip: *mut c_int,
+) -> c_int {
+    get_integer(term, ip)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_uint(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    ip: *mut c_uint,
+) -> c_int {
+    get_integer(term, ip)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_long(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    ip: *mut c_long,
+) -> c_int {
+    get_integer(term, ip)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_ulong(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    ip: *mut c_ulong,
+) -> c_int {
+    get_integer(term, ip)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_double(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    dp: *mut c_double,
+) -> c_int {
+    match heap::node(term) {
+        Node::Float(float) => write(dp, *float),
+        _ => 0,
+    }
+}
+
+// Atoms
+
+unsafe fn latin1(name: *const c_uchar, len: usize) -> String {
+    std::slice::from_raw_parts(name, len)
+        .iter()
+        .map(|&byte| char::from(byte))
+        .collect()
+}
+
+fn latin1_bytes(term: ERL_NIF_TERM) -> Option<Vec<u8>> {
+    match unsafe { heap::node(term) } {
+        Node::Atom(name) => name
+            .chars()
+            .map(|c| u8::try_from(u32::from(c)).ok())
+            .collect(),
+        _ => None,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_atom_len(
+    _env: *mut ErlNifEnv,
+    name: *const c_uchar,
+    len: usize,
+) -> ERL_NIF_TERM {
+    heap::atom(&latin1(name, len))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_existing_atom_len(
+    _env: *mut ErlNifEnv,
+    name: *const c_uchar,
+    len: usize,
+    atom: *mut ERL_NIF_TERM,
+    _encoding: ErlNifCharEncoding,
+) -> c_int {
+    match heap::existing_atom(&latin1(name, len)) {
+        Some(term) => write(atom, term),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_atom_length(
+    _env: *mut ErlNifEnv,
+    atom: ERL_NIF_TERM,
+    len: *mut c_uint,
+    _encoding: ErlNifCharEncoding,
+) -> c_int {
+    match latin1_bytes(atom) {
+        Some(bytes) => write(len, bytes.len() as c_uint),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_atom(
+    _env: *mut ErlNifEnv,
+    atom: ERL_NIF_TERM,
+    buf: *mut c_uchar,
+    len: c_uint,
+    _encoding: ErlNifCharEncoding,
+) -> c_int {
+    match latin1_bytes(atom) {
+        Some(bytes) if bytes.len() < len as usize => {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+            *buf.add(bytes.len()) = 0;
+            bytes.len() as c_int + 1
+        }
+        _ => 0,
+    }
+}
+
+// Binaries
+
+unsafe fn raw_binary<'a>(bin: *mut ErlNifBinary) -> &'a mut RawBinary {
+    &mut *(bin as *mut RawBinary)
+}
+
+unsafe fn binary_data<'a>(bin: &RawBinary) -> &'a mut [u8] {
+    std::slice::from_raw_parts_mut(bin.data, bin.size)
+}
+
+/// Sets `bin` to a binary with the given contents, owned by the caller.
+unsafe fn set_binary(bin: *mut ErlNifBinary, data: Box<[u8]>) {
+    let size = data.len();
+    let data = Box::into_raw(data) as *mut u8;
+    ptr::write(
+        bin as *mut RawBinary,
+        RawBinary {
+            size,
+            data,
+            ref_bin: ptr::null_mut(),
+            spare: [ptr::null_mut(); 2],
+        },
+    );
+}
+
+/// Sets `bin` to the contents of a binary term, which stay valid as long as the term.
+unsafe fn set_borrowed_binary(bin: *mut ErlNifBinary, data: &[u8]) {
+    ptr::write(
+        bin as *mut RawBinary,
+        RawBinary {
+            size: data.len(),
+            data: data.as_ptr() as *mut u8,
+            ref_bin: ptr::null_mut(),
+            spare: [ptr::null_mut(); 2],
+        },
+    );
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_alloc_binary(size: usize, bin: *mut ErlNifBinary) -> c_int {
+    set_binary(bin, vec![0; size].into_boxed_slice());
+    1
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_realloc_binary(bin: *mut ErlNifBinary, size: usize) -> c_int {
+    let mut data = vec![0; size];
+    {
+        let old = binary_data(raw_binary(bin));
+        let len = old.len().min(size);
+        data[..len].copy_from_slice(&old[..len]);
+    }
+    enif_release_binary(bin);
+    set_binary(bin, data.into_boxed_slice());
+    1
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_release_binary(bin: *mut ErlNifBinary) {
+    drop(Box::from_raw(binary_data(raw_binary(bin)) as *mut [u8]));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_binary(
+    _env: *mut ErlNifEnv,
+    bin: *mut ErlNifBinary,
+) -> ERL_NIF_TERM {
+    let term = heap::alloc(Node::Binary(binary_data(raw_binary(bin)).to_vec()));
+    enif_release_binary(bin);
+    term
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_inspect_binary(
+    _env: *mut ErlNifEnv,
+    bin_term: ERL_NIF_TERM,
+    bin: *mut ErlNifBinary,
+) -> c_int {
+    match heap::node(bin_term) {
+        Node::Binary(data) => {
+            set_borrowed_binary(bin, data);
+            1
+        }
+        _ => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_sub_binary(
+    env: *mut ErlNifEnv,
+    bin_term: ERL_NIF_TERM,
+    pos: usize,
+    size: usize,
+) -> ERL_NIF_TERM {
+    match heap::node(bin_term) {
+        Node::Binary(data) if matches!(pos.checked_add(size), Some(end) if end <= data.len()) => {
+            heap::alloc(Node::Binary(data[pos..pos + size].to_vec()))
+        }
+        _ => enif_make_badarg(env),
+    }
+}
+
+/// Appends the bytes of an iolist to `bytes`, returning `false` if `term` is not an iolist.
+fn flatten_iolist(term: ERL_NIF_TERM, bytes: &mut Vec<u8>) -> bool {
+    if let Node::Binary(data) = unsafe { heap::node(term) } {
+        bytes.extend_from_slice(data);
+        return true;
+    }
+
+    let (items, tail) = match heap::list_items(term) {
+        Some(list) => list,
+        None => return false,
+    };
+    let items_ok = items
+        .into_iter()
+        .all(|item| match unsafe { heap::node(item) } {
+            Node::Integer(byte @ 0..=255) => {
+                bytes.push(*byte as u8);
+                true
+            }
+            Node::Integer(_) => false,
+            _ => flatten_iolist(item, bytes),
+        });
+    items_ok
+        && match unsafe { heap::node(tail) } {
+            Node::Nil => true,
+            Node::Binary(data) => {
+                bytes.extend_from_slice(data);
+                true
+            }
+            _ => false,
+        }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_inspect_iolist_as_binary(
+    env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    bin: *mut ErlNifBinary,
+) -> c_int {
+    let mut bytes = Vec::new();
+    if !flatten_iolist(term, &mut bytes) {
+        return 0;
+    }
+    enif_inspect_binary(env, heap::alloc(Node::Binary(bytes)), bin)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_term_to_binary(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    bin: *mut ErlNifBinary,
+) -> c_int {
+    set_binary(bin, etf::encode(&heap::to_value(term)).into_boxed_slice());
+    1
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_binary_to_term(
+    _env: *mut ErlNifEnv,
+    data: *const c_uchar,
+    sz: usize,
+    term: *mut ERL_NIF_TERM,
+    _opts: ErlNifBinaryToTerm,
+) -> usize {
+    match etf::decode_prefix(std::slice::from_raw_parts(data, sz)) {
+        Ok((value, len)) => {
+            term.write(heap::from_value(&value));
+            len
+        }
+        Err(_) => 0,
+    }
+}
+
+// Lists
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_list_cell(
+    _env: *mut ErlNifEnv,
+    car: ERL_NIF_TERM,
+    cdr: ERL_NIF_TERM,
+) -> ERL_NIF_TERM {
+    heap::alloc(Node::Cons(car, cdr))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_list_from_array(
+    _env: *mut ErlNifEnv,
+    arr: *const ERL_NIF_TERM,
+    cnt: c_uint,
+) -> ERL_NIF_TERM {
+    heap::list(std::slice::from_raw_parts(arr, cnt as usize), heap::nil())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_list_cell(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    head: *mut ERL_NIF_TERM,
+    tail: *mut ERL_NIF_TERM,
+) -> c_int {
+    match heap::node(term) {
+        Node::Cons(car, cdr) => {
+            head.write(*car);
+            write(tail, *cdr)
+        }
+        _ => 0,
+    }
+}
+
+/// The items of a proper list.
+fn proper_list(term: ERL_NIF_TERM) -> Option<Vec<ERL_NIF_TERM>> {
+    match heap::list_items(term)? {
+        (items, tail) if matches!(unsafe { heap::node(tail) }, Node::Nil) => Some(items),
+        _ => None,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_list_length(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    len: *mut c_uint,
+) -> c_int {
+    match proper_list(term) {
+        Some(items) => write(len, items.len() as c_uint),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_reverse_list(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    list: *mut ERL_NIF_TERM,
+) -> c_int {
+    match proper_list(term) {
+        Some(mut items) => {
+            items.reverse();
+            write(list, heap::list(&items, heap::nil()))
+        }
+        None => 0,
+    }
+}
+
+// Tuples
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_tuple_from_array(
+    _env: *mut ErlNifEnv,
+    arr: *const ERL_NIF_TERM,
+    cnt: c_uint,
+) -> ERL_NIF_TERM {
+    let items = if cnt == 0 {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(arr, cnt as usize).to_vec()
+    };
+    heap::alloc(Node::Tuple(items))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_tuple(
+    _env: *mut ErlNifEnv,
+    tpl: ERL_NIF_TERM,
+    arity: *mut c_int,
+    array: *mut *const ERL_NIF_TERM,
+) -> c_int {
+    match heap::node(tpl) {
+        Node::Tuple(items) => {
+            arity.write(items.len() as c_int);
+            write(array, items.as_ptr())
+        }
+        _ => 0,
+    }
+}
+
+// Maps
+
+fn map_entries<'a>(term: ERL_NIF_TERM) -> Option<&'a Vec<(ERL_NIF_TERM, ERL_NIF_TERM)>> {
+    match unsafe { heap::node(term) } {
+        Node::Map(entries) => Some(entries),
+        _ => None,
+    }
+}
+
+/// The position of `key` in the entries of a map.
+fn find_key(entries: &[(ERL_NIF_TERM, ERL_NIF_TERM)], key: ERL_NIF_TERM) -> Option<usize> {
+    let key = heap::to_value(key);
+    entries
+        .iter()
+        .position(|&(entry_key, _)| heap::to_value(entry_key) == key)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_new_map(_env: *mut ErlNifEnv) -> ERL_NIF_TERM {
+    heap::alloc(Node::Map(Vec::new()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_map_size(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    size: *mut usize,
+) -> c_int {
+    match map_entries(term) {
+        Some(entries) => write(size, entries.len()),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_map_value(
+    _env: *mut ErlNifEnv,
+    map: ERL_NIF_TERM,
+    key: ERL_NIF_TERM,
+    value: *mut ERL_NIF_TERM,
+) -> c_int {
+    match map_entries(map).and_then(|entries| Some(entries[find_key(entries, key)?].1)) {
+        Some(found) => write(value, found),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_map_put(
+    _env: *mut ErlNifEnv,
+    map_in: ERL_NIF_TERM,
+    key: ERL_NIF_TERM,
+    value: ERL_NIF_TERM,
+    map_out: *mut ERL_NIF_TERM,
+) -> c_int {
+    let entries = match map_entries(map_in) {
+        Some(entries) => entries,
+        None => return 0,
+    };
+    let mut entries = entries.clone();
+    match find_key(&entries, key) {
+        Some(index) => entries[index].1 = value,
+        None => entries.push((key, value)),
+    }
+    write(map_out, heap::map(entries).unwrap())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_map_update(
+    env: *mut ErlNifEnv,
+    map_in: ERL_NIF_TERM,
+    key: ERL_NIF_TERM,
+    value: ERL_NIF_TERM,
+    map_out: *mut ERL_NIF_TERM,
+) -> c_int {
+    match map_entries(map_in).and_then(|entries| find_key(entries, key)) {
+        Some(_) => enif_make_map_put(env, map_in, key, value, map_out),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_map_remove(
+    _env: *mut ErlNifEnv,
+    map_in: ERL_NIF_TERM,
+    key: ERL_NIF_TERM,
+    map_out: *mut ERL_NIF_TERM,
+) -> c_int {
+    let entries = match map_entries(map_in) {
+        Some(entries) => entries,
+        None => return 0,
+    };
+    match find_key(entries, key) {
+        Some(index) => {
+            let mut entries = entries.clone();
+            entries.remove(index);
+            write(map_out, heap::alloc(Node::Map(entries)))
+        }
+        None => write(map_out, map_in),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_map_from_arrays(
+    _env: *mut ErlNifEnv,
+    keys: *const ERL_NIF_TERM,
+    values: *const ERL_NIF_TERM,
+    cnt: usize,
+    map_out: *mut ERL_NIF_TERM,
+) -> c_int {
+    let entries = if cnt == 0 {
+        Vec::new()
+    } else {
+        let keys = std::slice::from_raw_parts(keys, cnt);
+        let values = std::slice::from_raw_parts(values, cnt);
+        keys.iter().copied().zip(values.iter().copied()).collect()
+    };
+    match heap::map(entries) {
+        Some(map) => write(map_out, map),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_map_iterator_create(
+    _env: *mut ErlNifEnv,
+    map: ERL_NIF_TERM,
+    iter: *mut ErlNifMapIterator,
+    entry: ErlNifMapIteratorEntry,
+) -> c_int {
+    let size = match map_entries(map) {
+        Some(entries) => entries.len(),
+        None => return 0,
+    };
+    let index = match entry {
+        ErlNifMapIteratorEntry::ERL_NIF_MAP_ITERATOR_HEAD => 0,
+        ErlNifMapIteratorEntry::ERL_NIF_MAP_ITERATOR_TAIL => size.wrapping_sub(1),
+    };
+    write(
+        iter as *mut RawMapIterator,
+        RawMapIterator {
+            map,
+            size,
+            index,
+            keys: ptr::null_mut(),
+            values: ptr::null_mut(),
+            spare: [ptr::null_mut(); 2],
+        },
+    )
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_map_iterator_destroy(
+    _env: *mut ErlNifEnv,
+    _iter: *mut ErlNifMapIterator,
+) {
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_map_iterator_next(
+    _env: *mut ErlNifEnv,
+    iter: *mut ErlNifMapIterator,
+) -> c_int {
+    let iter = &mut *(iter as *mut RawMapIterator);
+    iter.index = iter.index.wrapping_add(1);
+    is(iter.index < iter.size)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_map_iterator_get_pair(
+    _env: *mut ErlNifEnv,
+    iter: *mut ErlNifMapIterator,
+    key: *mut ERL_NIF_TERM,
+    value: *mut ERL_NIF_TERM,
+) -> c_int {
+    let iter = &*(iter as *const RawMapIterator);
+    match map_entries(iter.map).and_then(|entries| entries.get(iter.index)) {
+        Some(&(entry_key, entry_value)) => {
+            key.write(entry_key);
+            write(value, entry_value)
+        }
+        None => 0,
+    }
+}
+
+// References
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_ref(_env: *mut ErlNifEnv) -> ERL_NIF_TERM {
+    heap::new_reference()
+}
+
+// Processes
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_self(
+    caller_env: *mut ErlNifEnv,
+    pid: *mut ErlNifPid,
+) -> *mut ErlNifPid {
+    match test_env(caller_env).and_then(|env| env.pid) {
+        Some(term) => {
+            write(pid as *mut ERL_NIF_TERM, term);
+            pid
+        }
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_local_pid(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    pid: *mut ErlNifPid,
+) -> c_int {
+    match heap::node(term) {
+        Node::Pid(_) => write(pid as *mut ERL_NIF_TERM, term),
+        _ => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_process_alive(
+    _env: *mut ErlNifEnv,
+    pid: *const ErlNifPid,
+) -> c_int {
+    is(super::is_alive(*(pid as *const ERL_NIF_TERM)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_is_current_process_alive(env: *mut ErlNifEnv) -> c_int {
+    match test_env(env).and_then(|env| env.pid) {
+        Some(pid) => is(super::is_alive(pid)),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_whereis_pid(
+    _env: *mut ErlNifEnv,
+    name: ERL_NIF_TERM,
+    pid: *mut ErlNifPid,
+) -> c_int {
+    match super::whereis(name) {
+        Some(term) => write(pid as *mut ERL_NIF_TERM, term),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_send(
+    _env: *mut ErlNifEnv,
+    to_pid: *const ErlNifPid,
+    _msg_env: *mut ErlNifEnv,
+    msg: ERL_NIF_TERM,
+) -> c_int {
+    is(super::deliver(*(to_pid as *const ERL_NIF_TERM), msg))
+}
+
+// Resources
+
+unsafe fn resource_header<'a>(obj: *const c_void) -> &'a ResourceHeader {
+    &*((obj as *const u8).sub(RESOURCE_HEADER_SIZE) as *const ResourceHeader)
+}
+
+fn resource_layout(size: usize) -> Layout {
+    Layout::from_size_align(RESOURCE_HEADER_SIZE + size, RESOURCE_ALIGN).unwrap()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_open_resource_type(
+    _env: *mut ErlNifEnv,
+    _module_str: *const c_uchar,
+    _name_str: *const c_uchar,
+    dtor: Option<ResourceDtor>,
+    flags: ErlNifResourceFlags,
+    tried: *mut ErlNifResourceFlags,
+) -> *const ErlNifResourceType {
+    if !tried.is_null() {
+        tried.write(flags);
+    }
+    Box::into_raw(Box::new(ResourceType { dtor })) as *const ErlNifResourceType
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_alloc_resource(
+    resource_type: *const ErlNifResourceType,
+    size: usize,
+) -> *mut c_void {
+    let memory = alloc::alloc_zeroed(resource_layout(size));
+    if memory.is_null() {
+        alloc::handle_alloc_error(resource_layout(size));
+    }
+    (memory as *mut ResourceHeader).write(ResourceHeader {
+        resource_type: resource_type as *const ResourceType,
+        refs: AtomicUsize::new(1),
+        size,
+    });
+    memory.add(RESOURCE_HEADER_SIZE) as *mut c_void
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_keep_resource(obj: *const c_void) {
+    resource_header(obj).refs.fetch_add(1, Ordering::Relaxed);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_release_resource(obj: *const c_void) {
+    let header = resource_header(obj);
+    if header.refs.fetch_sub(1, Ordering::AcqRel) != 1 {
+        return;
+    }
+
+    if let Some(dtor) = (*header.resource_type).dtor {
+        let env = enif_alloc_env();
+        dtor(env, obj as *mut c_void);
+        enif_free_env(env);
+    }
+    let layout = resource_layout(header.size);
+    alloc::dealloc((obj as *mut u8).sub(RESOURCE_HEADER_SIZE), layout);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_make_resource(
+    _env: *mut ErlNifEnv,
+    obj: *const c_void,
+) -> ERL_NIF_TERM {
+    // Terms are never freed, so the resource is kept for good.
+    enif_keep_resource(obj);
+    heap::alloc(Node::Resource(obj))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enif_get_resource(
+    _env: *mut ErlNifEnv,
+    term: ERL_NIF_TERM,
+    resource_type: *const ErlNifResourceType,
+    objp: *mut *const c_void,
+) -> c_int {
+    match heap::node(term) {
+        Node::Resource(obj)
+            if resource_header(*obj).resource_type == resource_type as *const ResourceType =>
+        {
+            write(objp, *obj)
+        }
+        _ => 0,
+    }
+}
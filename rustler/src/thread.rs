@@ -1,7 +1,16 @@
 use crate::env::OwnedEnv;
-use crate::types::atom::Atom;
-use crate::{Encoder, Env, Term};
+use crate::resource::{self, ResourceType, ResourceTypeProvider};
+use crate::types::atom::{self, Atom};
+use crate::wrapper::pid;
+use crate::{Decoder, Encoder, Env, LocalPid, NifResult, Reference, ResourceArc, Term};
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::num::NonZeroUsize;
+use std::os::raw::{c_uchar, c_void};
 use std::panic;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
 use std::thread;
 
 /// A `JobSpawner` is a value that can run Rust code on non-Erlang system threads.
@@ -13,6 +22,14 @@ use std::thread;
 pub trait JobSpawner {
     /// Run the given closure on another thread.
     fn spawn<F: FnOnce() + Send + panic::UnwindSafe + 'static>(job: F);
+
+    /// Run the given closure on another thread, or give it back if the spawner is overloaded.
+    ///
+    /// The default implementation always accepts the job.
+    fn try_spawn<F: FnOnce() + Send + panic::UnwindSafe + 'static>(job: F) -> Result<(), F> {
+        Self::spawn(job);
+        Ok(())
+    }
 }
 
 /// A `JobSpawner` that uses a separate system thread for each job.
@@ -25,40 +42,532 @@ impl JobSpawner for ThreadSpawner {
     }
 }
 
+/// A `JobSpawner` that runs jobs on a bounded pool of named worker threads, configured with
+/// `configure_pool()`.
+///
+/// When every thread is busy and the queue is full, jobs are rejected. The threads are stopped
+/// when the library is unloaded, after running the jobs that were already queued.
+pub struct PoolSpawner;
+
+impl JobSpawner for PoolSpawner {
+    /// Drops the job if it is rejected.
+    fn spawn<F: FnOnce() + Send + panic::UnwindSafe + 'static>(job: F) {
+        let _ = Self::try_spawn(job);
+    }
+
+    fn try_spawn<F: FnOnce() + Send + panic::UnwindSafe + 'static>(job: F) -> Result<(), F> {
+        let pool = {
+            let mut slot = POOL.lock().unwrap();
+            if let PoolSlot::Unstarted = *slot {
+                if let Some(workers) = Workers::start(PoolConfig::new()) {
+                    *slot = PoolSlot::Running(workers);
+                }
+            }
+            match *slot {
+                PoolSlot::Running(ref workers) => Arc::clone(&workers.pool),
+                _ => return Err(job),
+            }
+        };
+
+        pool.submit(job)
+    }
+}
+
+/// The configuration of the pool of `PoolSpawner`.
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    threads: usize,
+    queue_len: usize,
+    name: String,
+    vm_threads: bool,
+}
+
+impl PoolConfig {
+    /// A pool with a thread per CPU and a queue of 1024 jobs.
+    pub fn new() -> Self {
+        PoolConfig {
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            queue_len: 1024,
+            name: "rustler_pool".to_string(),
+            vm_threads: false,
+        }
+    }
+
+    /// Sets the number of worker threads. At least one thread is started.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets how many jobs can wait for a free thread before new jobs are rejected.
+    pub fn queue_len(mut self, queue_len: usize) -> Self {
+        self.queue_len = queue_len;
+        self
+    }
+
+    /// Sets the prefix of the thread names, which are followed by the index of the thread, as in
+    /// `rustler_pool_0`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Creates the threads with `enif_thread_create`, so that they are listed with the threads of
+    /// the Erlang VM.
+    pub fn vm_threads(mut self, vm_threads: bool) -> Self {
+        self.vm_threads = vm_threads;
+        self
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig::new()
+    }
+}
+
+/// Starts the pool of `PoolSpawner` with the given configuration, typically from the `load`
+/// function of the library. Otherwise, the pool is started with `PoolConfig::new()` when the first
+/// job is spawned.
+///
+/// Returns `false` if the pool is already running, or its threads could not be created.
+pub fn configure_pool(config: PoolConfig) -> bool {
+    let mut slot = POOL.lock().unwrap();
+    if let PoolSlot::Running(_) = *slot {
+        return false;
+    }
+
+    match Workers::start(config) {
+        Some(workers) => {
+            *slot = PoolSlot::Running(workers);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Stops the pool of `PoolSpawner`, waiting for the queued jobs to finish. Called when the library
+/// is unloaded.
+pub(crate) fn shutdown_pool() {
+    let slot = std::mem::replace(&mut *POOL.lock().unwrap(), PoolSlot::Stopped);
+    if let PoolSlot::Running(workers) = slot {
+        workers.stop();
+    }
+}
+
+static POOL: Mutex<PoolSlot> = Mutex::new(PoolSlot::Unstarted);
+
+enum PoolSlot {
+    Unstarted,
+    Running(Workers),
+    Stopped,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Pool {
+    threads: usize,
+    queue_len: usize,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+struct PoolState {
+    jobs: VecDeque<Job>,
+    busy: usize,
+    stopping: bool,
+}
+
+impl Pool {
+    fn submit<F: FnOnce() + Send + 'static>(&self, job: F) -> Result<(), F> {
+        let mut state = self.state.lock().unwrap();
+        let free_threads = self.threads - state.busy;
+        if state.stopping || state.jobs.len() >= free_threads + self.queue_len {
+            return Err(job);
+        }
+
+        state.jobs.push_back(Box::new(job));
+        self.available.notify_one();
+        Ok(())
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                state.busy += 1;
+                drop(state);
+                // Keep the worker alive when a job panics.
+                let _ = panic::catch_unwind(panic::AssertUnwindSafe(job));
+                state = self.state.lock().unwrap();
+                state.busy -= 1;
+            } else if state.stopping {
+                return;
+            } else {
+                state = self.available.wait(state).unwrap();
+            }
+        }
+    }
+}
+
+/// A running pool and its threads.
+struct Workers {
+    pool: Arc<Pool>,
+    threads: Vec<Worker>,
+}
+
+enum Worker {
+    Std(thread::JoinHandle<()>),
+    Vm(VmThread),
+}
+
+struct VmThread(rustler_sys::ErlNifTid);
+
+// A thread id is only used to join the thread.
+unsafe impl Send for VmThread {}
+
+impl Workers {
+    fn start(config: PoolConfig) -> Option<Self> {
+        let mut workers = Workers {
+            pool: Arc::new(Pool {
+                threads: config.threads,
+                queue_len: config.queue_len,
+                state: Mutex::new(PoolState {
+                    jobs: VecDeque::new(),
+                    busy: 0,
+                    stopping: false,
+                }),
+                available: Condvar::new(),
+            }),
+            threads: Vec::with_capacity(config.threads),
+        };
+
+        for index in 0..config.threads {
+            let name = format!("{}_{}", config.name, index);
+            let pool = Arc::clone(&workers.pool);
+            let worker = if config.vm_threads {
+                spawn_vm_thread(&name, pool)
+            } else {
+                thread::Builder::new()
+                    .name(name)
+                    .spawn(move || pool.run())
+                    .ok()
+                    .map(Worker::Std)
+            };
+
+            match worker {
+                Some(worker) => workers.threads.push(worker),
+                None => {
+                    workers.stop();
+                    return None;
+                }
+            }
+        }
+
+        Some(workers)
+    }
+
+    fn stop(self) {
+        self.pool.state.lock().unwrap().stopping = true;
+        self.pool.available.notify_all();
+
+        for worker in self.threads {
+            match worker {
+                Worker::Std(handle) => {
+                    let _ = handle.join();
+                }
+                Worker::Vm(VmThread(tid)) => unsafe {
+                    rustler_sys::enif_thread_join(tid, ptr::null_mut());
+                },
+            }
+        }
+    }
+}
+
+fn spawn_vm_thread(name: &str, pool: Arc<Pool>) -> Option<Worker> {
+    unsafe extern "C" fn run(arg: *mut c_void) -> *mut c_void {
+        let pool = Box::from_raw(arg as *mut Arc<Pool>);
+        pool.run();
+        ptr::null_mut()
+    }
+
+    let name = CString::new(name).ok()?;
+    let arg = Box::into_raw(Box::new(pool));
+    let mut tid = ptr::null_mut();
+    let result = unsafe {
+        rustler_sys::enif_thread_create(
+            name.as_ptr() as *mut c_uchar,
+            &mut tid,
+            Some(run),
+            arg as *mut c_void,
+            ptr::null_mut(),
+        )
+    };
+
+    if result == 0 {
+        Some(Worker::Vm(VmThread(tid)))
+    } else {
+        drop(unsafe { Box::from_raw(arg) });
+        None
+    }
+}
+
 /// Implements threaded NIFs.
 ///
 /// This spawns a thread that calls the given closure `thread_fn`. When the closure returns, the
-/// thread sends its return value back to the calling process.  If the closure panics, an `{error,
-/// Reason}` tuple is sent instead.
+/// thread sends `{Ref, Result}` back to the calling process, where `Ref` is the reference returned
+/// by `spawn()` and `Result` is the return value of the closure. If the closure panics, `Result`
+/// is an `{error, Reason}` tuple instead. If the `JobSpawner` rejects the job, `{Ref, {error,
+/// overloaded}}` is sent right away.
 ///
 /// Note that the thread creates a new `Env` and passes it to the closure, so the closure
 /// runs under a separate environment, not under `env`.
 ///
-pub fn spawn<'a, S, F>(env: Env<'a>, thread_fn: F)
+pub fn spawn<'a, S, F>(env: Env<'a>, thread_fn: F) -> Reference<'a>
 where
     F: for<'b> FnOnce(Env<'b>) -> Term<'b> + Send + panic::UnwindSafe + 'static,
     S: JobSpawner,
+{
+    let token = CancellationToken::new(env);
+    spawn_job::<S, _>(env, atom::nil().encode(env), token, move |env, _, _| {
+        thread_fn(env)
+    })
+}
+
+/// Like `spawn()`, also copying `args` into the environment of the thread and returning a
+/// `JobHandle` to cancel the job.
+///
+/// The closure is given the copy of `args` and a `CancellationToken`, which it can check to stop
+/// early once the job is cancelled or the calling process has exited. A job that is cancelled
+/// before it starts is not run.
+///
+/// When the job is cancelled through its `JobHandle`, `{Ref, {error, cancelled}}` is sent instead
+/// of the result. Nothing is sent when the calling process has exited.
+///
+/// ```ignore
+/// use rustler::thread::{self, JobHandle, ThreadSpawner};
+/// use rustler::types::atom;
+/// use rustler::{Atom, Encoder, Env, Reference, Term};
+///
+/// #[rustler::nif]
+/// fn sum_async<'a>(env: Env<'a>, numbers: Term<'a>) -> (Reference<'a>, JobHandle) {
+///     thread::spawn_with::<ThreadSpawner, _>(env, numbers, |env, numbers, token| {
+///         let mut sum = 0i64;
+///         for number in numbers.decode::<Vec<i64>>().unwrap_or_default() {
+///             if token.is_cancelled() {
+///                 break;
+///             }
+///             sum += number;
+///         }
+///         sum.encode(env)
+///     })
+/// }
+///
+/// #[rustler::nif]
+/// fn cancel(handle: JobHandle) -> Atom {
+///     handle.cancel();
+///     atom::ok()
+/// }
+/// ```
+pub fn spawn_with<'a, S, F>(
+    env: Env<'a>,
+    args: Term<'a>,
+    thread_fn: F,
+) -> (Reference<'a>, JobHandle)
+where
+    F: for<'b> FnOnce(Env<'b>, Term<'b>, &CancellationToken) -> Term<'b>
+        + Send
+        + panic::UnwindSafe
+        + 'static,
+    S: JobSpawner,
+{
+    let token = CancellationToken::new(env);
+    let handle = JobHandle(ResourceArc::new(JobState(Arc::clone(&token.cancelled))));
+    let reference = spawn_job::<S, _>(env, args, token, thread_fn);
+    (reference, handle)
+}
+
+/// Tells a long-running computation that it should stop.
+///
+/// The token of a job spawned by `spawn_with()` is cancelled when `JobHandle::cancel()` is called,
+/// or when the process that spawned the job is no longer alive.
+///
+/// A NIF can also take a `CancellationToken` argument, which is not part of the arity of the NIF
+/// and is cancelled when the calling process exits. This lets dirty NIFs stop early when their
+/// caller was killed, for instance after a timeout. The argument is recognized by its path, so it
+/// has to be written `thread::CancellationToken` or `rustler::thread::CancellationToken`; a bare
+/// `CancellationToken` is a compile error:
+///
+/// ```ignore
+/// use rustler::thread;
+///
+/// #[rustler::nif(schedule = "DirtyCpu")]
+/// fn count_primes(token: thread::CancellationToken, limit: u64) -> Option<usize> {
+///     let mut count = 0;
+///     for n in 2..limit {
+///         if n % 1024 == 0 && token.is_cancelled() {
+///             return None;
+///         }
+///         if is_prime(n) {
+///             count += 1;
+///         }
+///     }
+///     Some(count)
+/// }
+/// ```
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    pid: LocalPid,
+}
+
+impl CancellationToken {
+    /// A token that is cancelled when the calling process of `env` exits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `env` is process-independent.
+    pub fn new(env: Env) -> Self {
+        CancellationToken::for_pid(env.pid())
+    }
+
+    fn for_pid(pid: LocalPid) -> Self {
+        CancellationToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            pid,
+        }
+    }
+
+    /// Whether the job should stop.
+    pub fn is_cancelled(&self) -> bool {
+        // Process liveness can be checked from any thread without an environment.
+        self.is_cancelled_by_handle()
+            || !unsafe { pid::is_process_alive(ptr::null_mut(), self.pid.as_c_arg()) }
+    }
+
+    fn is_cancelled_by_handle(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Cancels a job spawned by `spawn_with()`. It is encoded as a resource, to be passed to the
+/// calling process and back to a NIF calling `cancel()`.
+#[derive(Clone)]
+pub struct JobHandle(ResourceArc<JobState>);
+
+impl JobHandle {
+    /// Cancels the job. Has no effect if the result was already sent.
+    pub fn cancel(&self) {
+        (self.0).0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether `cancel()` was called.
+    pub fn is_cancelled(&self) -> bool {
+        (self.0).0.load(Ordering::SeqCst)
+    }
+}
+
+impl Encoder for JobHandle {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        self.0.encode(env)
+    }
+}
+
+impl<'a> Decoder<'a> for JobHandle {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        Ok(JobHandle(term.decode()?))
+    }
+}
+
+/// The resource behind a `JobHandle`.
+struct JobState(Arc<AtomicBool>);
+
+static JOB_STATE_TYPE: OnceLock<ResourceType<JobState>> = OnceLock::new();
+
+impl ResourceTypeProvider for JobState {
+    fn get_type() -> &'static ResourceType<Self> {
+        JOB_STATE_TYPE
+            .get()
+            .expect("The resource type hasn't been initialized. Was the library loaded through `rustler::init!`?")
+    }
+}
+
+/// Opens the resource type of `JobHandle`. Called when the library is loaded.
+pub(crate) fn open_resource_type(env: Env) -> bool {
+    match resource::open_struct_resource_type::<JobState>(
+        env,
+        "rustler_thread_job_handle\x00",
+        resource::NIF_RESOURCE_FLAGS::ERL_NIF_RT_CREATE,
+    ) {
+        Some(resource_type) => {
+            let _ = JOB_STATE_TYPE.set(resource_type);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Spawns a job replying `{Ref, Result}` to the calling process, and returns `Ref`.
+fn spawn_job<'a, S, F>(
+    env: Env<'a>,
+    args: Term<'a>,
+    token: CancellationToken,
+    thread_fn: F,
+) -> Reference<'a>
+where
+    F: for<'b> FnOnce(Env<'b>, Term<'b>, &CancellationToken) -> Term<'b>
+        + Send
+        + panic::UnwindSafe
+        + 'static,
+    S: JobSpawner,
 {
     let pid = env.pid();
-    S::spawn(move || {
-        OwnedEnv::new().send_and_clear(&pid, |env| {
-            match panic::catch_unwind(|| thread_fn(env)) {
-                Ok(term) => term,
-                Err(err) => {
-                    // Try to get an error message from Rust.
-                    let reason = if let Some(string) = err.downcast_ref::<String>() {
-                        string.encode(env)
-                    } else if let Some(&s) = err.downcast_ref::<&'static str>() {
-                        s.encode(env)
-                    } else {
-                        Atom::from_bytes(env, b"nif_panic")
-                            .ok()
-                            .unwrap()
-                            .to_term(env)
-                    };
-                    env.error_tuple(reason)
-                }
+    let reference = Reference::new(env);
+
+    let mut owned_env = OwnedEnv::new();
+    let saved_reference = owned_env.save(*reference);
+    let saved_args = owned_env.save(args);
+
+    let spawned = S::try_spawn(move || {
+        // Don't start a job whose caller is gone.
+        if token.is_cancelled() && !token.is_cancelled_by_handle() {
+            return;
+        }
+
+        owned_env.send_and_clear(&pid, |env| {
+            let reference = saved_reference.load(env);
+            let mut result = None;
+            if !token.is_cancelled_by_handle() {
+                let args = saved_args.load(env);
+                result = Some(match panic::catch_unwind(|| thread_fn(env, args, &token)) {
+                    Ok(term) => term,
+                    Err(err) => {
+                        // Try to get an error message from Rust.
+                        let reason = if let Some(string) = err.downcast_ref::<String>() {
+                            string.encode(env)
+                        } else if let Some(&s) = err.downcast_ref::<&'static str>() {
+                            s.encode(env)
+                        } else {
+                            Atom::from_bytes(env, b"nif_panic")
+                                .ok()
+                                .unwrap()
+                                .to_term(env)
+                        };
+                        env.error_tuple(reason)
+                    }
+                });
             }
+
+            let result = match result {
+                Some(term) if !token.is_cancelled_by_handle() => term,
+                _ => env.error_tuple(atom::cancelled().encode(env)),
+            };
+            (reference, result).encode(env)
         });
     });
+
+    if spawned.is_err() {
+        let reply = (reference, env.error_tuple(atom::overloaded().encode(env)));
+        env.send(&env.pid(), reply.encode(env));
+    }
+    reference
 }
@@ -4,7 +4,7 @@ use crate::{Decoder, Encoder, Env, Error, NifResult, Term};
 
 // Atoms are a special case of a term. They can be stored and used on all envs regardless of where
 // it lives and when it is created.
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Atom {
     term: NIF_TERM,
 }
@@ -305,4 +305,10 @@ atoms! {
 
     /// The `last` atom used by `Elixir.Range`.
     last,
+
+    /// The `overloaded` atom, sent by `rustler::thread::spawn()` when a job is rejected.
+    overloaded,
+
+    /// The `cancelled` atom, sent by `rustler::thread::spawn_with()` when a job is cancelled.
+    cancelled,
 }
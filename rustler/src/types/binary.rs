@@ -349,6 +349,16 @@ impl<'a> Decoder<'a> for Binary<'a> {
         Binary::from_term(term)
     }
 }
+/// Decodes the bytes of a binary without copying them.
+///
+/// Slices are encoded as lists, including `[u8]`, so a `&[u8]` does not round-trip. Return a
+/// `Binary` or an `OwnedBinary` to build a binary instead.
+impl<'a> Decoder<'a> for &'a [u8] {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        Ok(Binary::from_term(term)?.as_slice())
+    }
+}
+
 impl<'a> Encoder for Binary<'a> {
     fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
         self.to_term(env)
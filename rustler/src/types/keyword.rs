@@ -0,0 +1,108 @@
+//! Keyword lists and proplists, the usual way of passing options in Elixir and Erlang.
+//!
+//! A keyword list is a list of `{atom, value}` tuples such as `[timeout: 5000, retries: 3]`.
+//! Erlang proplists may also contain bare atoms, which are read as `{atom, true}`. Structs can be
+//! decoded from and encoded to keyword lists by annotating them with `#[derive(NifKeywordList)]`.
+
+use super::atom::{self, Atom};
+use super::tuple::{get_tuple, make_tuple};
+use crate::{Decoder, Encoder, Env, Error, ListIterator, NifResult, Term};
+
+/// A decoded keyword list or proplist.
+///
+/// Entries are kept in the order of the list. As with `Keyword.get/2`, lookups return the first
+/// entry with the given key.
+///
+/// ```
+/// # use rustler::{NifResult, Term};
+/// # use rustler::types::keyword::Keyword;
+/// mod atoms {
+///     rustler::atoms! { timeout }
+/// }
+///
+/// # fn keyword_example(opts: Term) -> NifResult<u64> {
+/// let opts: Keyword = opts.decode()?;
+/// let timeout: u64 = opts.get_as(atoms::timeout())?.unwrap_or(5000);
+/// # Ok(timeout)
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Keyword<'a> {
+    entries: Vec<(Atom, Term<'a>)>,
+}
+
+impl<'a> Keyword<'a> {
+    /// Returns the value of the first entry with the given key.
+    pub fn get(&self, key: Atom) -> Option<Term<'a>> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| *entry == key)
+            .map(|(_, value)| *value)
+    }
+
+    /// Decodes the value of the first entry with the given key, returning `Ok(None)` if there is
+    /// no such entry.
+    pub fn get_as<T>(&self, key: Atom) -> NifResult<Option<T>>
+    where
+        T: Decoder<'a>,
+    {
+        self.get(key).map(|value| value.decode()).transpose()
+    }
+
+    pub fn contains_key(&self, key: Atom) -> bool {
+        self.entries.iter().any(|(entry, _)| *entry == key)
+    }
+
+    /// Iterates over all entries in list order, including repeated keys.
+    pub fn iter(&self) -> impl Iterator<Item = (Atom, Term<'a>)> + '_ {
+        self.entries.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'a> std::iter::FromIterator<(Atom, Term<'a>)> for Keyword<'a> {
+    fn from_iter<I: IntoIterator<Item = (Atom, Term<'a>)>>(iter: I) -> Self {
+        Keyword {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> Decoder<'a> for Keyword<'a> {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        let env = term.get_env();
+        let iter: ListIterator = term.decode()?;
+
+        let entries = iter
+            .map(|item| {
+                if item.is_atom() {
+                    return Ok((Atom::from_term(item)?, atom::true_().to_term(env)));
+                }
+
+                match get_tuple(item)?[..] {
+                    [key, value] => Ok((Atom::from_term(key)?, value)),
+                    _ => Err(Error::BadArg),
+                }
+            })
+            .collect::<NifResult<_>>()?;
+
+        Ok(Keyword { entries })
+    }
+}
+
+impl<'a> Encoder for Keyword<'a> {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        self.entries
+            .iter()
+            .map(|(key, value)| make_tuple(env, &[key.to_term(env), value.in_env(env)]))
+            .collect::<Vec<Term>>()
+            .encode(env)
+    }
+}
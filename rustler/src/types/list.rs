@@ -2,6 +2,8 @@
 //!
 //! Right now the only supported way to read lists are through the ListIterator.
 
+use std::convert::TryInto;
+
 use crate::wrapper::{list, NIF_TERM};
 use crate::{Decoder, Encoder, Env, Error, NifResult, Term};
 
@@ -113,6 +115,26 @@ where
     }
 }
 
+impl<T, const N: usize> Encoder for [T; N]
+where
+    T: Encoder,
+{
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        self[..].encode(env)
+    }
+}
+
+/// Decodes a list of exactly `N` elements.
+impl<'a, T, const N: usize> Decoder<'a> for [T; N]
+where
+    T: Decoder<'a>,
+{
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        let vec: Vec<T> = term.decode()?;
+        vec.try_into().map_err(|_| Error::BadArg)
+    }
+}
+
 impl<'a, T> Encoder for [T]
 where
     T: Encoder,
@@ -11,6 +11,10 @@ impl LocalPid {
     pub fn as_c_arg(&self) -> &ErlNifPid {
         &self.c
     }
+
+    pub(crate) fn from_c_arg(pid: ErlNifPid) -> Self {
+        LocalPid { c: pid }
+    }
 }
 
 impl<'a> Decoder<'a> for LocalPid {
@@ -44,4 +48,24 @@ impl<'a> Env<'a> {
             c: unsafe { pid.assume_init() },
         }
     }
+
+    /// Whether the calling process is still alive.
+    ///
+    /// A dirty NIF keeps running when its caller is killed, for instance after a timeout. Long
+    /// computations can check this to stop early. See also `thread::CancellationToken`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this environment is process-independent.
+    pub fn is_current_process_alive(self) -> bool {
+        // Panic if `self` is not the environment of a process.
+        self.pid();
+
+        unsafe { pid::is_current_process_alive(self.as_c_arg()) }
+    }
+
+    /// Whether the local process `pid` is alive.
+    pub fn is_process_alive(self, pid: &LocalPid) -> bool {
+        unsafe { pid::is_process_alive(self.as_c_arg(), pid.as_c_arg()) }
+    }
 }
@@ -11,6 +11,9 @@ pub use crate::types::binary::{Binary, OwnedBinary};
 pub mod list;
 pub use crate::types::list::ListIterator;
 
+pub mod keyword;
+pub use self::keyword::Keyword;
+
 #[doc(hidden)]
 pub mod map;
 pub use self::map::MapIterator;
@@ -33,10 +36,16 @@ pub mod pid {
 #[deprecated(since = "0.22.0", note = "Please use LocalPid instead")]
 pub use self::LocalPid as Pid;
 
+pub mod reference;
+pub use self::reference::Reference;
+
 pub mod truthy;
 
 pub mod elixir_struct;
 
+pub mod value;
+pub use self::value::Value;
+
 pub trait Encoder {
     fn encode<'a>(&self, env: Env<'a>) -> Term<'a>;
 }
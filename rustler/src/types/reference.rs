@@ -0,0 +1,44 @@
+use std::ops::Deref;
+
+use crate::{Decoder, Encoder, Env, Error, NifResult, Term};
+
+/// A reference, as returned by `make_ref/0`. References are unique and are commonly used to tag
+/// messages, so that the receiving process can tell which request a message answers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reference<'a>(Term<'a>);
+
+impl<'a> Reference<'a> {
+    /// Creates a new unique reference, like `make_ref/0`.
+    pub fn new(env: Env<'a>) -> Reference<'a> {
+        Reference(unsafe { Term::new(env, rustler_sys::enif_make_ref(env.as_c_arg())) })
+    }
+
+    /// Copies the reference into `env`.
+    pub fn in_env<'b>(&self, env: Env<'b>) -> Reference<'b> {
+        Reference(self.0.in_env(env))
+    }
+}
+
+impl<'a> Deref for Reference<'a> {
+    type Target = Term<'a>;
+
+    fn deref(&self) -> &Term<'a> {
+        &self.0
+    }
+}
+
+impl<'a> Encoder for Reference<'a> {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        self.0.in_env(env)
+    }
+}
+
+impl<'a> Decoder<'a> for Reference<'a> {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        if term.is_ref() {
+            Ok(Reference(term))
+        } else {
+            Err(Error::BadArg)
+        }
+    }
+}
@@ -0,0 +1,757 @@
+//! An owned representation of arbitrary Erlang terms.
+//!
+//! [`Value`] decodes any term into plain Rust data that is `Send` and does not borrow from the
+//! `Env`, so it can be kept around after the NIF returns, moved to other threads, and encoded
+//! again in another environment. Values are ordered like the terms they represent in Erlang.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use std::io::Write;
+
+use super::atom::Atom;
+use super::tuple::{get_tuple, make_tuple};
+use crate::{Binary, Decoder, Encoder, Env, Error, NifResult, OwnedBinary, Term};
+
+/// Tag of `SMALL_BIG_EXT` in the external term format.
+const SMALL_BIG_EXT: u8 = 110;
+/// Tag of `LARGE_BIG_EXT` in the external term format.
+const LARGE_BIG_EXT: u8 = 111;
+/// Version byte starting every term in the external term format.
+const ETF_VERSION: u8 = 131;
+
+/// An owned Erlang term.
+///
+/// ```
+/// # use rustler::{NifResult, Term, Value};
+/// # fn value_example(term: Term) -> NifResult<usize> {
+/// let value: Value = term.decode()?;
+/// let count = match value {
+///     Value::List(ref items) | Value::Tuple(ref items) => items.len(),
+///     Value::Map(ref map) => map.len(),
+///     _ => 1,
+/// };
+/// # Ok(count)
+/// # }
+/// ```
+///
+/// `PartialEq` and `Ord` follow the exact Erlang term order: numbers < atoms < references < funs
+/// < ports < pids < tuples < maps < lists < binaries. Integers and floats are compared by value,
+/// and as for map keys in Erlang, an integer is ordered before a float of the same value, so `1`
+/// and `1.0` are not equal. Pids, ports, references and funs are only ordered consistently among
+/// themselves, not necessarily in the same order as in Erlang.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Atom(String),
+    /// An integer that fits in an `i64`.
+    Integer(i64),
+    /// An integer of any size.
+    BigInt(BigInt),
+    Float(f64),
+    Binary(Vec<u8>),
+    /// A proper list, including the empty list.
+    List(Vec<Value>),
+    /// A list whose tail is not a list, such as `[1 | 2]`.
+    ImproperList(Vec<Value>, Box<Value>),
+    Tuple(Vec<Value>),
+    Map(BTreeMap<Value, Value>),
+    Pid(Opaque),
+    Reference(Opaque),
+    Port(Opaque),
+    /// A fun. It can be encoded again, but not called from Rust.
+    Fun(Opaque),
+}
+
+/// An arbitrarily large integer, as a sign and magnitude.
+#[derive(Clone, Debug)]
+pub struct BigInt {
+    negative: bool,
+    /// The magnitude in little-endian bytes, without trailing zeros.
+    magnitude: Vec<u8>,
+}
+
+impl BigInt {
+    /// Creates an integer from its sign and the little-endian bytes of its magnitude.
+    pub fn new(negative: bool, mut magnitude: Vec<u8>) -> Self {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        let negative = negative && !magnitude.is_empty();
+        BigInt {
+            negative,
+            magnitude,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The little-endian bytes of the absolute value.
+    pub fn magnitude(&self) -> &[u8] {
+        &self.magnitude
+    }
+
+    /// Returns the integer as an `i64`, if it fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        let magnitude = self.to_u64_magnitude()?;
+        if self.negative && magnitude == 1 << 63 {
+            Some(i64::MIN)
+        } else if self.negative {
+            i64::try_from(magnitude).ok().map(|magnitude| -magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    /// Returns the integer as a `u64`, if it fits.
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.negative {
+            return None;
+        }
+        self.to_u64_magnitude()
+    }
+
+    fn to_u64_magnitude(&self) -> Option<u64> {
+        if self.magnitude.len() > 8 {
+            return None;
+        }
+        let mut bytes = [0; 8];
+        bytes[..self.magnitude.len()].copy_from_slice(&self.magnitude);
+        Some(u64::from_le_bytes(bytes))
+    }
+
+    /// The integer part of a finite float.
+    fn from_f64_trunc(float: f64) -> Self {
+        let bits = float.trunc().to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i32 - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+
+        if float.trunc() == 0.0 {
+            return BigInt::new(false, Vec::new());
+        }
+
+        let magnitude = if exponent < 0 {
+            (mantissa >> -exponent).to_le_bytes().to_vec()
+        } else {
+            let mut bytes = vec![0; exponent as usize / 8];
+            bytes.extend_from_slice(&((mantissa as u128) << (exponent % 8)).to_le_bytes());
+            bytes
+        };
+
+        BigInt::new(float < 0.0, magnitude)
+    }
+
+    /// Reads a `SMALL_BIG_EXT` or `LARGE_BIG_EXT` term, without the version byte.
+    fn from_etf(bytes: &[u8]) -> Option<Self> {
+        let (len, rest) = match bytes.split_first()? {
+            (&SMALL_BIG_EXT, rest) => (*rest.first()? as usize, rest.get(1..)?),
+            (&LARGE_BIG_EXT, rest) => {
+                let len = rest.get(..4)?;
+                let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+                (len as usize, &rest[4..])
+            }
+            _ => return None,
+        };
+
+        let (&sign, digits) = rest.split_first()?;
+        Some(BigInt::new(sign != 0, digits.get(..len)?.to_vec()))
+    }
+
+    /// Writes the integer as a `LARGE_BIG_EXT` term, including the version byte.
+    fn to_etf(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.magnitude.len() + 7);
+        bytes.push(ETF_VERSION);
+        bytes.push(LARGE_BIG_EXT);
+        bytes.extend_from_slice(&(self.magnitude.len() as u32).to_be_bytes());
+        bytes.push(self.negative as u8);
+        bytes.extend_from_slice(&self.magnitude);
+        bytes
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        BigInt::new(value < 0, value.unsigned_abs().to_le_bytes().to_vec())
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(value: u64) -> Self {
+        BigInt::new(false, value.to_le_bytes().to_vec())
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(value: i128) -> Self {
+        BigInt::new(value < 0, value.unsigned_abs().to_le_bytes().to_vec())
+    }
+}
+
+impl From<u128> for BigInt {
+    fn from(value: u128) -> Self {
+        BigInt::new(false, value.to_le_bytes().to_vec())
+    }
+}
+
+impl PartialEq for BigInt {
+    fn eq(&self, other: &BigInt) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BigInt {}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        let magnitude = self
+            .magnitude
+            .len()
+            .cmp(&other.magnitude.len())
+            .then_with(|| {
+                self.magnitude
+                    .iter()
+                    .rev()
+                    .cmp(other.magnitude.iter().rev())
+            });
+
+        match (self.negative, other.negative) {
+            (false, false) => magnitude,
+            (true, true) => magnitude.reverse(),
+            (negative, _) => {
+                if negative {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }
+        }
+    }
+}
+
+/// A pid, port, reference or fun, kept in the external term format so that it can outlive the
+/// environment it was decoded in.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Opaque(pub(crate) Vec<u8>);
+
+impl Opaque {
+    /// The term in the external term format, as returned by `:erlang.term_to_binary/1`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Opaque {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Opaque({} bytes)", self.0.len())
+    }
+}
+
+impl Value {
+    /// The position of the type of the value in the Erlang term order.
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::Integer(_) | Value::BigInt(_) | Value::Float(_) => 0,
+            Value::Atom(_) => 1,
+            Value::Reference(_) => 2,
+            Value::Fun(_) => 3,
+            Value::Port(_) => 4,
+            Value::Pid(_) => 5,
+            Value::Tuple(_) => 6,
+            Value::Map(_) => 7,
+            Value::List(items) if items.is_empty() => 8,
+            Value::List(_) | Value::ImproperList(_, _) => 9,
+            Value::Binary(_) => 10,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Integer(a), Value::BigInt(b)) => BigInt::from(*a).cmp(b),
+            (Value::BigInt(a), Value::Integer(b)) => a.cmp(&BigInt::from(*b)),
+            (Value::BigInt(a), Value::BigInt(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Float(b)) => cmp_int_float(&BigInt::from(*a), *b),
+            (Value::BigInt(a), Value::Float(b)) => cmp_int_float(a, *b),
+            (Value::Float(a), Value::Integer(b)) => cmp_int_float(&BigInt::from(*b), *a).reverse(),
+            (Value::Float(a), Value::BigInt(b)) => cmp_int_float(b, *a).reverse(),
+            // Floats in terms are always finite.
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Value::Atom(a), Value::Atom(b)) => a.cmp(b),
+            (Value::Reference(a), Value::Reference(b))
+            | (Value::Fun(a), Value::Fun(b))
+            | (Value::Port(a), Value::Port(b))
+            | (Value::Pid(a), Value::Pid(b)) => a.cmp(b),
+            (Value::Tuple(a), Value::Tuple(b)) => a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+            (Value::Map(a), Value::Map(b)) => a
+                .len()
+                .cmp(&b.len())
+                .then_with(|| a.keys().cmp(b.keys()))
+                .then_with(|| a.values().cmp(b.values())),
+            (Value::List(a), Value::List(b)) => a.cmp(b),
+            (Value::List(a), Value::ImproperList(b, b_tail)) => cmp_lists(a, None, b, Some(b_tail)),
+            (Value::ImproperList(a, a_tail), Value::List(b)) => cmp_lists(a, Some(a_tail), b, None),
+            (Value::ImproperList(a, a_tail), Value::ImproperList(b, b_tail)) => {
+                cmp_lists(a, Some(a_tail), b, Some(b_tail))
+            }
+            (Value::Binary(a), Value::Binary(b)) => a.cmp(b),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+}
+
+/// Compares an integer with a float by value, ordering the integer first if they are equal.
+fn cmp_int_float(int: &BigInt, float: f64) -> Ordering {
+    if float.is_infinite() {
+        return if float > 0.0 {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        };
+    }
+
+    let trunc = float.trunc();
+    int.cmp(&BigInt::from_f64_trunc(float)).then_with(|| {
+        if float < trunc {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        }
+    })
+}
+
+/// Compares two lists cell by cell, where a missing tail is the empty list.
+fn cmp_lists(a: &[Value], a_tail: Option<&Value>, b: &[Value], b_tail: Option<&Value>) -> Ordering {
+    // A non-empty list, standing in for the remaining cells of the longer list.
+    const CONS_RANK: u8 = 9;
+    // The type rank of the empty list.
+    const NIL_RANK: u8 = 8;
+
+    for (x, y) in a.iter().zip(b.iter()) {
+        match x.cmp(y) {
+            Ordering::Equal => (),
+            ordering => return ordering,
+        }
+    }
+
+    let tail_rank = |tail: Option<&Value>| tail.map_or(NIL_RANK, Value::type_rank);
+
+    match a.len().cmp(&b.len()) {
+        Ordering::Less => tail_rank(a_tail).cmp(&CONS_RANK),
+        Ordering::Greater => CONS_RANK.cmp(&tail_rank(b_tail)),
+        Ordering::Equal => match (a_tail, b_tail) {
+            (Some(a_tail), Some(b_tail)) => a_tail.cmp(b_tail),
+            (a_tail, b_tail) => tail_rank(a_tail).cmp(&tail_rank(b_tail)),
+        },
+    }
+}
+
+impl<'a> Decoder<'a> for Value {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        if term.is_atom() {
+            return Ok(Value::Atom(term.atom_to_string()?));
+        }
+
+        if term.is_number() {
+            if let Ok(integer) = term.decode::<i64>() {
+                return Ok(Value::Integer(integer));
+            }
+            if let Ok(float) = term.decode::<f64>() {
+                return Ok(Value::Float(float));
+            }
+            let etf = term.to_binary();
+            return BigInt::from_etf(&etf.as_slice()[1..])
+                .map(Value::BigInt)
+                .ok_or(Error::BadArg);
+        }
+
+        if term.is_binary() {
+            return Ok(Value::Binary(Binary::from_term(term)?.as_slice().to_vec()));
+        }
+
+        if term.is_list() {
+            let mut items = Vec::new();
+            let mut rest = term;
+            while !rest.is_empty_list() {
+                match rest.list_get_cell() {
+                    Ok((head, tail)) => {
+                        items.push(head.decode()?);
+                        rest = tail;
+                    }
+                    Err(_) => return Ok(Value::ImproperList(items, Box::new(rest.decode()?))),
+                }
+            }
+            return Ok(Value::List(items));
+        }
+
+        if term.is_tuple() {
+            let items = get_tuple(term)?;
+            return Ok(Value::Tuple(
+                items
+                    .into_iter()
+                    .map(Term::decode)
+                    .collect::<NifResult<_>>()?,
+            ));
+        }
+
+        if term.is_map() {
+            return Ok(Value::Map(term.decode::<crate::MapIterator>()?.try_fold(
+                BTreeMap::new(),
+                |mut map, (key, value)| {
+                    map.insert(key.decode()?, value.decode()?);
+                    Ok::<_, Error>(map)
+                },
+            )?));
+        }
+
+        let opaque = || Opaque(term.to_binary().as_slice().to_vec());
+        if term.is_pid() {
+            Ok(Value::Pid(opaque()))
+        } else if term.is_ref() {
+            Ok(Value::Reference(opaque()))
+        } else if term.is_port() {
+            Ok(Value::Port(opaque()))
+        } else if term.is_fun() {
+            Ok(Value::Fun(opaque()))
+        } else {
+            Err(Error::BadArg)
+        }
+    }
+}
+
+impl Encoder for Value {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            Value::Atom(name) => Atom::from_str(env, name)
+                .expect("atom name too long")
+                .to_term(env),
+            Value::Integer(integer) => integer.encode(env),
+            Value::BigInt(int) => match (int.to_i64(), int.to_u64()) {
+                (Some(integer), _) => integer.encode(env),
+                (None, Some(integer)) => integer.encode(env),
+                (None, None) => decode_etf(env, &int.to_etf()),
+            },
+            Value::Float(float) => float.encode(env),
+            Value::Binary(bytes) => {
+                let mut bin = OwnedBinary::new(bytes.len()).expect("binary term allocation fail");
+                bin.as_mut_slice()
+                    .write_all(bytes)
+                    .expect("memory copy of binary failed");
+                bin.release(env).to_term(env)
+            }
+            Value::List(items) => encode_list(env, items, Term::list_new_empty(env)),
+            Value::ImproperList(items, tail) => encode_list(env, items, tail.encode(env)),
+            Value::Tuple(items) => {
+                let terms: Vec<Term> = items.iter().map(|item| item.encode(env)).collect();
+                make_tuple(env, &terms)
+            }
+            Value::Map(map) => {
+                let (keys, values): (Vec<_>, Vec<_>) = map
+                    .iter()
+                    .map(|(key, value)| (key.encode(env), value.encode(env)))
+                    .unzip();
+                Term::map_from_arrays(env, &keys, &values).unwrap()
+            }
+            Value::Pid(opaque)
+            | Value::Reference(opaque)
+            | Value::Port(opaque)
+            | Value::Fun(opaque) => decode_etf(env, &opaque.0),
+        }
+    }
+}
+
+fn encode_list<'a>(env: Env<'a>, items: &[Value], tail: Term<'a>) -> Term<'a> {
+    items
+        .iter()
+        .rev()
+        .fold(tail, |list, item| list.list_prepend(item.encode(env)))
+}
+
+fn decode_etf<'a>(env: Env<'a>, bytes: &[u8]) -> Term<'a> {
+    env.binary_to_term(bytes)
+        .expect("invalid external term format")
+        .0
+}
+
+/// Conversion into a [`Value`]. This is the counterpart of `Encoder` that does not need an
+/// `Env`, used by [`etf::to_vec`](crate::etf::to_vec).
+pub trait ToValue {
+    fn to_value(&self) -> Value;
+}
+
+/// Conversion from a [`Value`]. This is the counterpart of `Decoder` that does not need an
+/// `Env`, used by [`etf::from_slice`](crate::etf::from_slice).
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> NifResult<Self>;
+}
+
+impl ToValue for Value {
+    fn to_value(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> NifResult<Self> {
+        Ok(value)
+    }
+}
+
+impl<T: ToValue + ?Sized> ToValue for &T {
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(&self) -> Value {
+        Value::Atom(self.to_string())
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> NifResult<Self> {
+        match value {
+            Value::Atom(ref name) if name == "true" => Ok(true),
+            Value::Atom(ref name) if name == "false" => Ok(false),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+macro_rules! impl_integer_value {
+    ($($int:ty),*) => {
+        $(
+            impl ToValue for $int {
+                fn to_value(&self) -> Value {
+                    match i64::try_from(*self) {
+                        Ok(integer) => Value::Integer(integer),
+                        Err(_) => Value::BigInt(BigInt::from(*self as u64)),
+                    }
+                }
+            }
+
+            impl FromValue for $int {
+                fn from_value(value: Value) -> NifResult<Self> {
+                    let converted = match value {
+                        Value::Integer(integer) => <$int>::try_from(integer).ok(),
+                        Value::BigInt(int) => int.to_u64().and_then(|int| <$int>::try_from(int).ok()),
+                        _ => None,
+                    };
+                    converted.ok_or(Error::BadArg)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_value!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl ToValue for f64 {
+    fn to_value(&self) -> Value {
+        Value::Float(*self)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> NifResult<Self> {
+        match value {
+            Value::Float(float) => Ok(float),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl ToValue for f32 {
+    fn to_value(&self) -> Value {
+        Value::Float(f64::from(*self))
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: Value) -> NifResult<Self> {
+        f64::from_value(value).map(|float| float as f32)
+    }
+}
+
+impl ToValue for str {
+    fn to_value(&self) -> Value {
+        Value::Binary(self.as_bytes().to_vec())
+    }
+}
+
+impl ToValue for String {
+    fn to_value(&self) -> Value {
+        self.as_str().to_value()
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> NifResult<Self> {
+        match value {
+            Value::Binary(bytes) => String::from_utf8(bytes).map_err(|_| Error::BadArg),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for [T] {
+    fn to_value(&self) -> Value {
+        Value::List(self.iter().map(ToValue::to_value).collect())
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self) -> Value {
+        self.as_slice().to_value()
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> NifResult<Self> {
+        match value {
+            Value::List(items) => items.into_iter().map(T::from_value).collect(),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Value {
+        match self {
+            Some(value) => value.to_value(),
+            None => Value::Atom("nil".to_string()),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> NifResult<Self> {
+        match value {
+            Value::Atom(ref name) if name == "nil" => match T::from_value(value.clone()) {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Ok(None),
+            },
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: ToValue, E: ToValue> ToValue for Result<T, E> {
+    fn to_value(&self) -> Value {
+        let (tag, value) = match self {
+            Ok(value) => ("ok", value.to_value()),
+            Err(err) => ("error", err.to_value()),
+        };
+        Value::Tuple(vec![Value::Atom(tag.to_string()), value])
+    }
+}
+
+impl<T: FromValue, E: FromValue> FromValue for Result<T, E> {
+    fn from_value(value: Value) -> NifResult<Self> {
+        let (tag, value): (Value, Value) = FromValue::from_value(value)?;
+        match tag {
+            Value::Atom(ref name) if name == "ok" => T::from_value(value).map(Ok),
+            Value::Atom(ref name) if name == "error" => E::from_value(value).map(Err),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl<K: ToValue, V: ToValue, S> ToValue for HashMap<K, V, S> {
+    fn to_value(&self) -> Value {
+        Value::Map(
+            self.iter()
+                .map(|(key, value)| (key.to_value(), value.to_value()))
+                .collect(),
+        )
+    }
+}
+
+impl<K: FromValue + Eq + Hash, V: FromValue> FromValue for HashMap<K, V> {
+    fn from_value(value: Value) -> NifResult<Self> {
+        match value {
+            Value::Map(map) => map
+                .into_iter()
+                .map(|(key, value)| Ok((K::from_value(key)?, V::from_value(value)?)))
+                .collect(),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl<K: ToValue, V: ToValue> ToValue for BTreeMap<K, V> {
+    fn to_value(&self) -> Value {
+        Value::Map(
+            self.iter()
+                .map(|(key, value)| (key.to_value(), value.to_value()))
+                .collect(),
+        )
+    }
+}
+
+impl<K: FromValue + Ord, V: FromValue> FromValue for BTreeMap<K, V> {
+    fn from_value(value: Value) -> NifResult<Self> {
+        match value {
+            Value::Map(map) => map
+                .into_iter()
+                .map(|(key, value)| Ok((K::from_value(key)?, V::from_value(value)?)))
+                .collect(),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+macro_rules! impl_tuple_value {
+    ($len:expr; $($index:tt: $ty:ident),*) => {
+        impl<$($ty: ToValue),*> ToValue for ($($ty,)*) {
+            fn to_value(&self) -> Value {
+                Value::Tuple(vec![$(self.$index.to_value()),*])
+            }
+        }
+
+        impl<$($ty: FromValue),*> FromValue for ($($ty,)*) {
+            fn from_value(value: Value) -> NifResult<Self> {
+                match value {
+                    Value::Tuple(items) if items.len() == $len => {
+                        let mut items = items.into_iter();
+                        Ok(($($ty::from_value(items.next().unwrap())?,)*))
+                    }
+                    _ => Err(Error::BadArg),
+                }
+            }
+        }
+    };
+}
+
+impl_tuple_value!(1; 0: A);
+impl_tuple_value!(2; 0: A, 1: B);
+impl_tuple_value!(3; 0: A, 1: B, 2: C);
+impl_tuple_value!(4; 0: A, 1: B, 2: C, 3: D);
+impl_tuple_value!(5; 0: A, 1: B, 2: C, 3: D, 4: E);
+impl_tuple_value!(6; 0: A, 1: B, 2: C, 3: D, 4: E, 5: F);
+impl_tuple_value!(7; 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G);
@@ -1,7 +1,14 @@
 use crate::wrapper::NIF_TERM;
 use std::fmt;
+#[cfg(not(feature = "testing"))]
 use std::os::raw::c_char;
 
+#[cfg(feature = "testing")]
+pub fn fmt(term: NIF_TERM, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    crate::testing::fmt(term, f)
+}
+
+#[cfg(not(feature = "testing"))]
 pub fn fmt(term: NIF_TERM, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
     const SIZE: usize = 1024;
     let mut bytes: Vec<u8> = Vec::with_capacity(SIZE);
@@ -0,0 +1,78 @@
+use rustler::beam::{Beam, Builder, Error};
+use rustler::types::value::ToValue;
+use rustler::Value;
+
+#[path = "beam_nif/src/lib.rs"]
+mod beam_nif;
+
+// These tests need `erl` in the `PATH`, and are run with `cargo test --features beam -- --ignored`.
+fn start() -> Beam {
+    let crate_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/beam_nif");
+    Builder::new(crate_dir, &beam_nif::RUSTLER_NIF_MODULE)
+        .load_info(&42)
+        .start()
+        .expect("could not start the node, is erl in the PATH?")
+}
+
+fn atom(name: &str) -> Value {
+    Value::Atom(name.to_string())
+}
+
+#[test]
+#[ignore = "needs erl"]
+fn runs_nifs_in_the_beam() {
+    let mut beam = start();
+
+    let result = beam
+        .call("add", &[1i64.to_value(), 2i64.to_value()])
+        .unwrap();
+    assert_eq!(result, 3i64.to_value());
+    assert_eq!(
+        beam.eval("beam_nif:add(1, 2) * 2").unwrap(),
+        6i64.to_value()
+    );
+    assert_eq!(beam.call("loaded_with", &[]).unwrap(), 42i64.to_value());
+
+    assert_eq!(beam.eval("beam_nif:greet(self())").unwrap(), atom("ok"));
+    let message = beam
+        .eval("receive M -> M after 1000 -> timeout end.")
+        .unwrap();
+    assert_eq!(message, atom("hello"));
+
+    let result = beam
+        .eval("C = beam_nif:counter_new(41), beam_nif:counter_incr(C), beam_nif:counter_incr(C)")
+        .unwrap();
+    assert_eq!(result, 43i64.to_value());
+}
+
+#[test]
+#[ignore = "needs erl"]
+fn returns_exceptions() {
+    let mut beam = start();
+
+    match beam.call("fail", &[]) {
+        Err(Error::Exception { class, reason, .. }) => {
+            assert_eq!(class, "error");
+            assert_eq!(reason, atom("failed"));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    match beam.call("add", &[1i64.to_value(), "two".to_value()]) {
+        Err(Error::Exception { reason, .. }) => assert_eq!(reason, atom("badarg")),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    match beam.eval("1 +") {
+        Err(Error::Exception {
+            reason: Value::Tuple(items),
+            ..
+        }) => {
+            assert_eq!(items[0], atom("syntax_error"))
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    // The node is still usable after an exception.
+    assert_eq!(beam.eval("beam_nif:add(2, 2)").unwrap(), 4i64.to_value());
+}
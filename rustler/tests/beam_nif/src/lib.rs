@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::OnceLock;
+
+use rustler::resource::{self, ResourceType, ResourceTypeProvider};
+use rustler::{Atom, Encoder, Env, Error, LocalPid, NifResult, ResourceArc, Term};
+
+mod atoms {
+    rustler::atoms! {
+        ok,
+        hello,
+    }
+}
+
+static LOAD_INFO: AtomicI64 = AtomicI64::new(0);
+
+#[rustler::nif]
+fn add(a: i64, b: i64) -> i64 {
+    a + b
+}
+
+#[rustler::nif]
+fn fail() -> NifResult<Atom> {
+    Err(Error::RaiseAtom("failed"))
+}
+
+#[rustler::nif]
+fn greet(env: Env, pid: LocalPid) -> Atom {
+    env.send(&pid, atoms::hello().encode(env));
+    atoms::ok()
+}
+
+#[rustler::nif]
+fn loaded_with() -> i64 {
+    LOAD_INFO.load(Ordering::SeqCst)
+}
+
+pub struct Counter(AtomicI64);
+
+static COUNTER_TYPE: OnceLock<ResourceType<Counter>> = OnceLock::new();
+
+impl ResourceTypeProvider for Counter {
+    fn get_type() -> &'static ResourceType<Self> {
+        COUNTER_TYPE.get().expect("the library was not loaded")
+    }
+}
+
+#[rustler::nif]
+fn counter_new(count: i64) -> ResourceArc<Counter> {
+    ResourceArc::new(Counter(AtomicI64::new(count)))
+}
+
+#[rustler::nif]
+fn counter_incr(counter: ResourceArc<Counter>) -> i64 {
+    counter.0.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+fn load(env: Env, info: Term) -> bool {
+    let opened = match resource::open_struct_resource_type::<Counter>(
+        env,
+        "Counter\x00",
+        resource::NIF_RESOURCE_FLAGS::ERL_NIF_RT_CREATE,
+    ) {
+        Some(counter_type) => COUNTER_TYPE.set(counter_type).is_ok(),
+        None => false,
+    };
+    if !opened {
+        return false;
+    }
+
+    LOAD_INFO.store(info.decode().unwrap_or(-1), Ordering::SeqCst);
+    true
+}
+
+rustler::init!("beam_nif", load = load);
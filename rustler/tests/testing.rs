@@ -0,0 +1,736 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, Once, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use rustler::channel::{self, Sender};
+use rustler::env::OwnedEnv;
+use rustler::resource::{self, ResourceType, ResourceTypeProvider};
+use rustler::stubs::TypeSpec;
+use rustler::testing::{self, Exception};
+use rustler::thread::{CancellationToken, JobHandle, PoolConfig, PoolSpawner, ThreadSpawner};
+use rustler::types::atom;
+use rustler::{
+    Atom, Binary, Encoder, Env, Error, LocalPid, Nif, NifMap, NifResult, NifStruct, NifTuple,
+    NifUnitEnum, NifUntaggedEnum, OwnedBinary, Reference, ResourceArc, Term,
+};
+
+mod atoms {
+    rustler::atoms! {
+        ok,
+        error,
+        overflow,
+        hello,
+    }
+}
+
+#[rustler::nif]
+fn add(a: i64, b: i64) -> NifResult<i64> {
+    a.checked_add(b).ok_or(Error::RaiseAtom("overflow"))
+}
+
+// Exported with the same name and arity as `add`, so the registered NIFs clash.
+#[rustler::nif(name = "add")]
+fn wrapping_add(a: i64, b: i64) -> i64 {
+    a.wrapping_add(b)
+}
+
+#[rustler::nif]
+fn echo<'a>(term: Term<'a>) -> Term<'a> {
+    term
+}
+
+#[rustler::nif]
+fn sum_list(items: Vec<i64>) -> i64 {
+    items.iter().sum()
+}
+
+#[rustler::nif]
+fn map_keys(map: HashMap<String, i64>) -> Vec<String> {
+    let mut keys: Vec<String> = map.into_keys().collect();
+    keys.sort();
+    keys
+}
+
+#[rustler::nif]
+fn reverse_binary(binary: Binary) -> OwnedBinary {
+    let mut reversed = OwnedBinary::new(binary.len()).unwrap();
+    reversed.as_mut_slice().copy_from_slice(binary.as_slice());
+    reversed.as_mut_slice().reverse();
+    reversed
+}
+
+#[rustler::nif]
+fn echo_bytes(bytes: &[u8]) -> &[u8] {
+    bytes
+}
+
+#[rustler::nif]
+#[allow(clippy::just_underscores_and_digits)]
+fn unnamed_args(_: i64, (_a, _b): (i64, i64), __: i64, _rest: i64) {}
+
+#[rustler::nif]
+fn always_panics() -> Atom {
+    panic!("oops")
+}
+
+#[rustler::nif]
+fn send_hello(env: Env, pid: LocalPid) -> Atom {
+    env.send(&pid, atoms::hello().encode(env));
+    atoms::ok()
+}
+
+static STARTED_JOBS: AtomicUsize = AtomicUsize::new(0);
+static CANCELLED_JOBS: AtomicUsize = AtomicUsize::new(0);
+
+#[rustler::nif]
+fn spawn_sum(env: Env, items: Vec<i64>) -> Reference {
+    rustler::thread::spawn::<ThreadSpawner, _>(env, move |env| {
+        items.iter().sum::<i64>().encode(env)
+    })
+}
+
+#[rustler::nif]
+fn spawn_echo<'a>(env: Env<'a>, term: Term<'a>, wait: bool) -> (Reference<'a>, JobHandle) {
+    rustler::thread::spawn_with::<ThreadSpawner, _>(env, term, move |_env, term, token| {
+        STARTED_JOBS.fetch_add(1, Ordering::SeqCst);
+        while wait && !token.is_cancelled() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        if token.is_cancelled() {
+            CANCELLED_JOBS.fetch_add(1, Ordering::SeqCst);
+        }
+        term
+    })
+}
+
+#[rustler::nif]
+fn pooled_add(env: Env, a: i64, b: i64) -> Reference {
+    rustler::thread::spawn::<PoolSpawner, _>(env, move |env| {
+        thread::sleep(Duration::from_millis(100));
+        (a + b, thread::current().name().map(String::from)).encode(env)
+    })
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn dirty_add(a: i64, b: i64) -> i64 {
+    a + b
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn dirty_liveness(
+    env: Env,
+    token: rustler::thread::CancellationToken,
+    pid: LocalPid,
+) -> (bool, bool, bool) {
+    (
+        token.is_cancelled(),
+        env.is_current_process_alive(),
+        env.is_process_alive(&pid),
+    )
+}
+
+#[derive(NifStruct, Debug, PartialEq)]
+#[module = "User"]
+struct User {
+    name: String,
+    age: u32,
+}
+
+#[derive(NifMap, Debug, PartialEq)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+#[derive(NifTuple, Debug, PartialEq)]
+struct Pair(i64, String);
+
+#[derive(NifUnitEnum, Debug, PartialEq)]
+enum Color {
+    Red,
+    Green,
+}
+
+#[derive(NifUntaggedEnum, Debug, PartialEq)]
+enum Shape {
+    Circle(f64),
+    Rect(Point),
+}
+
+#[rustler::nif]
+fn grow_user(user: User) -> User {
+    User {
+        age: user.age + 1,
+        ..user
+    }
+}
+
+#[rustler::nif]
+fn swap_point(point: Point) -> Point {
+    Point {
+        x: point.y,
+        y: point.x,
+    }
+}
+
+struct Counter {
+    count: Mutex<i64>,
+}
+
+static COUNTER_TYPE: OnceLock<ResourceType<Counter>> = OnceLock::new();
+
+impl ResourceTypeProvider for Counter {
+    fn get_type() -> &'static ResourceType<Self> {
+        COUNTER_TYPE.get().expect("the library was not loaded")
+    }
+}
+
+fn load(env: Env, _info: Term) -> bool {
+    match resource::open_struct_resource_type::<Counter>(
+        env,
+        "Counter\x00",
+        resource::NIF_RESOURCE_FLAGS::ERL_NIF_RT_CREATE,
+    ) {
+        Some(resource_type) => COUNTER_TYPE.set(resource_type).is_ok(),
+        None => false,
+    }
+}
+
+fn load_resources() {
+    static LOAD: Once = Once::new();
+    LOAD.call_once(|| testing::with_env(|env| assert!(load(env, atom::nil().encode(env)))));
+}
+
+#[rustler::nif]
+fn counter_new(count: i64) -> ResourceArc<Counter> {
+    ResourceArc::new(Counter {
+        count: Mutex::new(count),
+    })
+}
+
+#[rustler::nif]
+fn counter_incr(counter: ResourceArc<Counter>) -> i64 {
+    let mut count = counter.count.lock().unwrap();
+    *count += 1;
+    *count
+}
+
+pub struct Gauge {
+    level: i64,
+}
+
+#[rustler::resource_impl]
+impl Gauge {
+    pub fn new(level: i64) -> Self {
+        Gauge { level }
+    }
+
+    pub fn level(&self) -> i64 {
+        self.clamped()
+    }
+
+    fn clamped(&self) -> i64 {
+        self.level.max(0)
+    }
+}
+
+#[test]
+fn exports_the_pub_methods_of_resource_impls() {
+    let names: Vec<_> = rustler::codegen_runtime::registered_nifs(module_path!())
+        .map(|nif| nif.metadata.name)
+        .filter(|name| name.starts_with("gauge_"))
+        .collect();
+    assert_eq!(names.len(), 2);
+    assert!(names.contains(&"gauge_new"));
+    assert!(names.contains(&"gauge_level"));
+}
+
+#[test]
+fn decodes_byte_slices_from_binaries() {
+    testing::with_env(|env| {
+        let result = testing::call(env, echo_bytes, &["abc".encode(env)]).unwrap();
+        assert_eq!(result.decode::<Vec<u8>>().unwrap(), b"abc");
+
+        let result = testing::call(env, echo_bytes, &[vec![1u8, 2].encode(env)]);
+        assert!(matches!(result, Err(Exception::BadArg)));
+    });
+
+    // The specs follow the asymmetry: a binary is expected, and a list is returned.
+    let metadata = <echo_bytes as Nif>::METADATA;
+    assert_eq!(metadata.args[0].spec, TypeSpec::Binary);
+    assert_eq!(metadata.returns, TypeSpec::List(&TypeSpec::NonNegInteger));
+}
+
+#[test]
+fn names_unnamed_arguments_by_position() {
+    let names: Vec<_> = <unnamed_args as Nif>::METADATA
+        .args
+        .iter()
+        .map(|arg| arg.name)
+        .collect();
+    assert_eq!(names, ["arg0", "arg1", "arg2", "rest"]);
+
+    static NIFS: [rustler::stubs::NifMetadata; 1] = [<unnamed_args as Nif>::METADATA];
+    let module = rustler::stubs::NifModule {
+        name: "Elixir.Unnamed",
+        nifs: Some(&NIFS),
+        module_path: module_path!(),
+    };
+    let stubs = rustler::stubs::Elixir::new().generate(&module);
+    assert!(stubs.contains("def unnamed_args(_arg0, _arg1, _arg2, _rest),"));
+}
+
+#[test]
+fn looks_up_the_keys_of_large_maps() {
+    use rustler::codegen_runtime::{map_values, map_values_and_rest};
+    use std::convert::TryInto;
+
+    testing::with_env(|env| {
+        let atoms: Vec<Atom> = (0..50)
+            .map(|i| Atom::from_str(env, &format!("field_{}", i)).unwrap())
+            .collect();
+        let mut keys: Vec<Term> = atoms.iter().map(|atom| atom.encode(env)).collect();
+        keys.push("name".encode(env));
+        keys.push(1.encode(env));
+        let keys: [Term; 52] = keys.try_into().unwrap();
+
+        // Every key but `field_0`, and two entries that are not keys.
+        let mut entries: Vec<(Term, Term)> = (1..52).map(|i| (keys[i], i.encode(env))).collect();
+        entries.push((atoms::ok().encode(env), 52.encode(env)));
+        entries.push(("other".encode(env), 53.encode(env)));
+        let (entry_keys, entry_values): (Vec<Term>, Vec<Term>) = entries.into_iter().unzip();
+        let map = Term::map_from_arrays(env, &entry_keys, &entry_values).unwrap();
+
+        let (values, rest) = map_values_and_rest(map, &keys, true).unwrap();
+        assert!(values[0].is_none());
+        for (i, value) in values.iter().enumerate().skip(1) {
+            assert_eq!(value.unwrap().decode::<usize>().unwrap(), i);
+        }
+        let mut rest: Vec<usize> = rest.iter().map(|(_, v)| v.decode().unwrap()).collect();
+        rest.sort_unstable();
+        assert_eq!(rest, [52, 53]);
+
+        // Without extra entries, keys are looked up one by one in a larger map.
+        assert_eq!(map_values(map, &keys).unwrap(), values);
+
+        // And the map is walked when it has fewer entries than there are keys.
+        let map = Term::map_from_arrays(env, &entry_keys[..10], &entry_values[..10]).unwrap();
+        let values = map_values(map, &keys).unwrap();
+        assert_eq!(values.iter().filter(|value| value.is_some()).count(), 10);
+        assert_eq!(values[10].unwrap().decode::<usize>().unwrap(), 10);
+    });
+}
+
+#[test]
+fn reports_clashing_registered_nifs() {
+    let clash = rustler::codegen_runtime::registered_nif_clash(module_path!()).unwrap();
+    assert_eq!(
+        clash,
+        "two functions annotated with `#[rustler::nif]` are exported as add/2"
+    );
+}
+
+#[test]
+fn registers_the_nifs_of_the_calling_crate_only() {
+    use rustler::codegen_runtime::{registered_nif_clash, registered_nifs};
+
+    let names: Vec<_> = registered_nifs("testing::submodule")
+        .map(|nif| nif.metadata.name)
+        .collect();
+    assert!(names.contains(&"add"));
+    assert!(names.contains(&"echo_bytes"));
+
+    assert_eq!(registered_nifs("dependency").count(), 0);
+    assert_eq!(registered_nif_clash("dependency"), None);
+}
+
+#[test]
+fn calls_nifs() {
+    testing::with_env(|env| {
+        let result = testing::call(env, add, &[1.encode(env), 2.encode(env)]).unwrap();
+        assert_eq!(result.decode::<i64>().unwrap(), 3);
+
+        let list = vec![1, 2, 3, 4].encode(env);
+        let result = testing::call(env, sum_list, &[list]).unwrap();
+        assert_eq!(result.decode::<i64>().unwrap(), 10);
+
+        let mut map = HashMap::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        let result = testing::call(env, map_keys, &[map.encode(env)]).unwrap();
+        assert_eq!(result.decode::<Vec<String>>().unwrap(), vec!["a", "b"]);
+
+        let result = testing::call(env, reverse_binary, &["abc".encode(env)]).unwrap();
+        assert_eq!(result.decode::<String>().unwrap(), "cba");
+    });
+}
+
+#[test]
+fn round_trips_terms() {
+    testing::with_env(|env| {
+        let terms = vec![
+            atoms::ok().encode(env),
+            (-1i64).encode(env),
+            u64::MAX.encode(env),
+            1.5f64.encode(env),
+            "héllo".encode(env),
+            (atoms::error(), vec![1, 2], "three").encode(env),
+            Some(true).encode(env),
+        ];
+
+        for term in terms {
+            let result = testing::call(env, echo, &[term]).unwrap();
+            assert_eq!(result, term);
+        }
+
+        let term = (atoms::ok(), vec![1, 2]).encode(env);
+        assert_eq!(format!("{:?}", term), "{ok,[1,2]}");
+    });
+}
+
+#[test]
+fn builds_terms_with_the_term_macro() {
+    testing::with_env(|env| {
+        let term = rustler::term!(env, {:ok, HashMap::<String, i64>::new(), nil, [a: 1]});
+        assert_eq!(format!("{:?}", term), "{ok,#{},nil,[{a,1}]}");
+    });
+}
+
+static COUNTED_DECODES: AtomicUsize = AtomicUsize::new(0);
+
+/// A string that counts how often it is decoded.
+struct Counted;
+
+impl<'a> rustler::Decoder<'a> for Counted {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        COUNTED_DECODES.fetch_add(1, Ordering::SeqCst);
+        term.decode::<String>().map(|_| Counted)
+    }
+}
+
+#[test]
+fn match_term_decodes_typed_bindings_last() {
+    testing::with_env(|env| {
+        let matches = |term: Term| {
+            rustler::match_term!(term, {
+                {_name: Counted, :ok} => true,
+                _ => false,
+            })
+        };
+
+        // The atom after the typed binding does not match, so nothing is decoded.
+        assert!(!matches(rustler::term!(env, {"name", :error})));
+        assert_eq!(COUNTED_DECODES.load(Ordering::SeqCst), 0);
+
+        assert!(matches(rustler::term!(env, {"name", :ok})));
+        assert_eq!(COUNTED_DECODES.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn derived_encoders_and_decoders() {
+    testing::with_env(|env| {
+        let user = User {
+            name: "Jane".to_string(),
+            age: 41,
+        };
+        let result = testing::call(env, grow_user, &[user.encode(env)]).unwrap();
+        assert_eq!(
+            result.decode::<User>().unwrap(),
+            User {
+                name: "Jane".to_string(),
+                age: 42
+            }
+        );
+
+        let result = testing::call(env, swap_point, &[Point { x: 1, y: 2 }.encode(env)]).unwrap();
+        assert_eq!(result.decode::<Point>().unwrap(), Point { x: 2, y: 1 });
+
+        let pair = Pair(1, "one".to_string());
+        assert_eq!(pair.encode(env).decode::<Pair>().unwrap(), pair);
+        assert_eq!(
+            Color::Green.encode(env).decode::<Color>().unwrap(),
+            Color::Green
+        );
+        assert!(Color::Red.encode(env).decode::<Shape>().is_err());
+
+        let shape = Shape::Rect(Point { x: 2, y: 3 });
+        assert_eq!(shape.encode(env).decode::<Shape>().unwrap(), shape);
+        let shape = Shape::Circle(1.0);
+        assert_eq!(shape.encode(env).decode::<Shape>().unwrap(), shape);
+
+        let result = testing::call(env, swap_point, &[Pair(1, "x".into()).encode(env)]);
+        assert!(matches!(result, Err(Exception::BadArg)));
+    });
+}
+
+#[test]
+fn returns_exceptions() {
+    testing::with_env(|env| {
+        let result = testing::call(env, add, &[1.encode(env), "two".encode(env)]);
+        assert!(matches!(result, Err(Exception::BadArg)));
+
+        match testing::call(env, add, &[i64::MAX.encode(env), 1.encode(env)]) {
+            Err(Exception::Raise(reason)) => assert_eq!(reason, atoms::overflow().encode(env)),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match testing::call(env, always_panics, &[]) {
+            Err(Exception::Raise(reason)) => {
+                assert_eq!(reason, atom::nif_panicked().encode(env))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let result = testing::call(env, add, &[1.encode(env), 2.encode(env)]);
+        assert!(result.is_ok());
+    });
+}
+
+#[test]
+#[should_panic(expected = "NIF add/1 is not exported")]
+fn rejects_wrong_arity() {
+    testing::with_env(|env| {
+        let _ = testing::call(env, add, &[1.encode(env)]);
+    });
+}
+
+#[test]
+fn receives_messages() {
+    testing::with_env(|env| {
+        let pid = env.pid();
+        testing::call(env, send_hello, &[pid.encode(env)]).unwrap();
+        let message = testing::receive(env, Duration::from_secs(1)).unwrap();
+        assert_eq!(message, atoms::hello().encode(env));
+        assert!(testing::receive(env, Duration::from_millis(10)).is_none());
+
+        let reference = testing::call(env, spawn_sum, &[vec![1, 2, 3].encode(env)]).unwrap();
+        let message = testing::receive(env, Duration::from_secs(5)).unwrap();
+        assert_eq!(message, (reference, 6).encode(env));
+    });
+}
+
+#[test]
+fn sends_from_owned_envs() {
+    testing::with_env(|env| {
+        let owned_env = OwnedEnv::new();
+        let saved = owned_env.save((atoms::hello(), vec![1, 2]).encode(env));
+
+        for _ in 0..2 {
+            assert!(owned_env.send_from(env, &env.pid(), |env| saved.load(env)));
+        }
+        for _ in 0..2 {
+            let message = testing::receive(env, Duration::from_secs(1)).unwrap();
+            assert_eq!(
+                message.decode::<(Atom, Vec<i64>)>().unwrap(),
+                (atoms::hello(), vec![1, 2])
+            );
+        }
+
+        // The environment was not cleared.
+        let payload = owned_env.run(|env| saved.load(env).decode::<(Atom, Vec<i64>)>());
+        assert_eq!(payload.unwrap(), (atoms::hello(), vec![1, 2]));
+    });
+}
+
+#[test]
+fn sends_to_registered_names() {
+    let exited = testing::with_env(|env| {
+        let name = Atom::from_str(env, "sends_to_registered_names").unwrap();
+        testing::register(env, name);
+        let pid = env.whereis(name).unwrap();
+        assert_eq!(pid.encode(env), env.pid().encode(env));
+
+        assert!(env.send_to_name(name, atoms::hello().encode(env)));
+        let message = testing::receive(env, Duration::from_secs(1)).unwrap();
+        assert_eq!(message.decode::<Atom>().unwrap(), atoms::hello());
+
+        let sent = thread::spawn(move || {
+            let mut owned_env = OwnedEnv::new();
+            owned_env.send_to_name_and_clear(name, |env| atoms::ok().encode(env))
+        });
+        assert!(sent.join().unwrap());
+        let message = testing::receive(env, Duration::from_secs(1)).unwrap();
+        assert_eq!(message.decode::<Atom>().unwrap(), atoms::ok());
+        name
+    });
+
+    // The name is unregistered when the process exits.
+    testing::with_env(|env| {
+        assert!(env.whereis(exited).is_none());
+        assert!(!env.send_to_name(exited, atoms::hello().encode(env)));
+    });
+}
+
+#[test]
+fn runs_jobs_on_a_pool() {
+    let config = PoolConfig::new()
+        .threads(1)
+        .queue_len(0)
+        .name("test_pool")
+        .vm_threads(true);
+    assert!(rustler::thread::configure_pool(config));
+
+    testing::with_env(|env| {
+        let first = testing::call(env, pooled_add, &[1.encode(env), 2.encode(env)]).unwrap();
+        let second = testing::call(env, pooled_add, &[3.encode(env), 4.encode(env)]).unwrap();
+
+        let receive = || testing::receive(env, Duration::from_secs(1)).unwrap();
+        let overloaded = (atoms::error(), atom::overloaded());
+        assert_eq!(receive(), (second, overloaded).encode(env));
+        assert_eq!(receive(), (first, (3, "test_pool_0")).encode(env));
+    });
+}
+
+#[test]
+fn cancels_jobs() {
+    testing::with_env(|env| {
+        let payload = (atoms::hello(), vec![1, 2]).encode(env);
+        let result = testing::call(env, spawn_echo, &[payload, false.encode(env)]).unwrap();
+        let (reference, _) = result.decode::<(Reference, JobHandle)>().unwrap();
+        let message = testing::receive(env, Duration::from_secs(1)).unwrap();
+        assert_eq!(message, (reference, payload).encode(env));
+
+        let result = testing::call(env, spawn_echo, &[payload, true.encode(env)]).unwrap();
+        let (reference, handle) = result.decode::<(Reference, JobHandle)>().unwrap();
+        handle.cancel();
+        let message = testing::receive(env, Duration::from_secs(1)).unwrap();
+        let cancelled = (atoms::error(), atom::cancelled());
+        assert_eq!(message, (reference, cancelled).encode(env));
+    });
+
+    // Jobs are cancelled when the calling process exits.
+    let cancelled = CANCELLED_JOBS.load(Ordering::SeqCst);
+    testing::with_env(|env| {
+        let started = STARTED_JOBS.load(Ordering::SeqCst);
+        let args = [atoms::hello().encode(env), true.encode(env)];
+        testing::call(env, spawn_echo, &args).unwrap();
+        wait_until(|| STARTED_JOBS.load(Ordering::SeqCst) > started);
+    });
+    wait_until(|| CANCELLED_JOBS.load(Ordering::SeqCst) > cancelled);
+}
+
+fn wait_until(condition: impl Fn() -> bool) {
+    for _ in 0..1000 {
+        if condition() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("timed out");
+}
+
+#[test]
+fn runs_dirty_nifs() {
+    testing::with_env(|env| {
+        let result = testing::call(env, dirty_add, &[1.encode(env), 2.encode(env)]).unwrap();
+        assert_eq!(result.decode::<i64>().unwrap(), 3);
+    });
+}
+
+#[test]
+fn checks_process_liveness() {
+    let (exited, token) = testing::with_env(|env| (env.pid(), CancellationToken::new(env)));
+    assert!(token.is_cancelled());
+
+    testing::with_env(|env| {
+        let args = [env.pid().encode(env)];
+        let result = testing::call(env, dirty_liveness, &args).unwrap();
+        assert_eq!(
+            result.decode::<(bool, bool, bool)>().unwrap(),
+            (false, true, true)
+        );
+
+        let args = [exited.encode(env)];
+        let result = testing::call(env, dirty_liveness, &args).unwrap();
+        assert_eq!(
+            result.decode::<(bool, bool, bool)>().unwrap(),
+            (false, true, false)
+        );
+    });
+}
+
+#[test]
+fn uses_resources() {
+    load_resources();
+
+    testing::with_env(|env| {
+        let counter = testing::call(env, counter_new, &[41.encode(env)]).unwrap();
+        let result = testing::call(env, counter_incr, &[counter]).unwrap();
+        assert_eq!(result.decode::<i64>().unwrap(), 42);
+        let result = testing::call(env, counter_incr, &[counter]).unwrap();
+        assert_eq!(result.decode::<i64>().unwrap(), 43);
+
+        let result = testing::call(env, counter_incr, &[1.encode(env)]);
+        assert!(matches!(result, Err(Exception::BadArg)));
+    });
+}
+
+#[test]
+fn streams_over_channels() {
+    testing::with_env(|env| {
+        let reference = Reference::new(env);
+        let mut sender = Sender::new(env.pid()).tagged(reference);
+        thread::spawn(move || {
+            sender.send(1).unwrap();
+            sender.send_batch(vec![2, 3]).unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let message = testing::receive(env, Duration::from_secs(1)).unwrap();
+        assert_eq!(
+            message.decode::<(Reference, i64)>().unwrap(),
+            (reference, 1)
+        );
+        let message = testing::receive(env, Duration::from_secs(1)).unwrap();
+        let (tag, batch) = message.decode::<(Reference, Vec<i64>)>().unwrap();
+        assert_eq!((tag, batch), (reference, vec![2, 3]));
+    });
+}
+
+#[test]
+fn bounded_channels_wait_for_acknowledgements() {
+    testing::with_env(|env| {
+        let (mut sender, acknowledger) = channel::bounded(env.pid(), 2);
+        let producer = thread::spawn(move || {
+            for i in 0..3 {
+                sender.send(i).unwrap();
+            }
+        });
+
+        let receive = || testing::receive(env, Duration::from_millis(100));
+        assert_eq!(receive().unwrap().decode::<i64>().unwrap(), 0);
+        assert_eq!(receive().unwrap().decode::<i64>().unwrap(), 1);
+        assert!(receive().is_none());
+
+        acknowledger.ack();
+        assert_eq!(receive().unwrap().decode::<i64>().unwrap(), 2);
+        producer.join().unwrap();
+    });
+}
+
+#[test]
+fn channels_close_when_the_process_exits() {
+    let pid = testing::with_env(|env| env.pid());
+    let mut sender = Sender::new(pid);
+    let result = thread::spawn(move || (sender.send(1), sender.is_closed()))
+        .join()
+        .unwrap();
+    assert_eq!(result, (Err(channel::SendError(1)), true));
+
+    testing::with_env(|env| {
+        let (mut sender, acknowledger) = channel::bounded(env.pid(), 1);
+        let producer = thread::spawn(move || {
+            sender.send(1).unwrap();
+            // Blocks until the acknowledger is dropped.
+            sender.send(2)
+        });
+
+        assert!(testing::receive(env, Duration::from_secs(1)).is_some());
+        drop(acknowledger);
+        assert_eq!(producer.join().unwrap(), Err(channel::SendError(2)));
+    });
+}
@@ -1,11 +1,67 @@
 // TODO When we settle for a minimum version of Rust >= 1.42, remove this.
 #![allow(clippy::match_like_matches_macro)]
 
+use heck::{KebabCase, MixedCase, ShoutyKebabCase, ShoutySnakeCase, SnakeCase};
 use proc_macro2::{Span, TokenStream};
-use syn::{Data, Field, Fields, Ident, Lit, Meta, NestedMeta, Variant};
+use syn::punctuated::Punctuated;
+use syn::{
+    Data, Field, Fields, GenericParam, Generics, Ident, Lifetime, LifetimeDef, Lit, Meta,
+    NestedMeta, Variant, WherePredicate,
+};
 
 use super::RustlerAttr;
 
+/// The rules accepted by `#[rustler(rename_all = "...")]`.
+const RENAME_RULES: [&str; 8] = [
+    "lowercase",
+    "UPPERCASE",
+    "PascalCase",
+    "camelCase",
+    "snake_case",
+    "SCREAMING_SNAKE_CASE",
+    "kebab-case",
+    "SCREAMING-KEBAB-CASE",
+];
+
+/// The attributes accepted by `#[rustler(...)]` on a struct or enum.
+const CONTAINER_ATTRS: [&str; 8] = [
+    "encode",
+    "decode",
+    "bound",
+    "keys",
+    "repr",
+    "rename_all",
+    "hrl",
+    "deny_unknown_keys",
+];
+
+/// The value used for a field that is missing from the decoded term.
+pub(crate) enum FieldDefault {
+    /// `#[rustler(default)]`, using `Default::default()`.
+    Trait,
+    /// `#[rustler(default = "path")]`, calling the given function.
+    Function(syn::Path),
+}
+
+/// Options set on a struct field with `#[rustler(...)]`.
+#[derive(Default)]
+pub(crate) struct FieldAttrs {
+    pub default: Option<FieldDefault>,
+    /// `#[rustler(key = "...")]`, the key used instead of the field name.
+    pub key: Option<String>,
+    /// `#[rustler(extra)]`, the field collecting the entries of a map that match no other field.
+    pub extra: bool,
+}
+
+/// Options set on an enum variant with `#[rustler(...)]`.
+#[derive(Default)]
+pub(crate) struct VariantAttrs {
+    /// `#[rustler(rename = "...")]`, the atom used instead of the one derived from the name.
+    pub rename: Option<String>,
+    /// `#[rustler(other)]`, the variant any unknown value decodes to.
+    pub other: bool,
+}
+
 ///
 /// A parsing context struct.
 ///
@@ -14,20 +70,18 @@ use super::RustlerAttr;
 pub(crate) struct Context<'a> {
     pub attrs: Vec<RustlerAttr>,
     pub ident: &'a proc_macro2::Ident,
-    pub ident_with_lifetime: proc_macro2::TokenStream,
+    pub generics: &'a Generics,
     pub variants: Option<Vec<&'a Variant>>,
     pub struct_fields: Option<Vec<&'a Field>>,
     pub is_tuple_struct: bool,
 }
 
 impl<'a> Context<'a> {
-    pub fn from_ast(ast: &'a syn::DeriveInput) -> Self {
-        let mut attrs: Vec<_> = ast
-            .attrs
-            .iter()
-            .map(Context::get_rustler_attrs)
-            .flatten()
-            .collect();
+    pub fn from_ast(ast: &'a syn::DeriveInput) -> syn::Result<Self> {
+        let mut attrs = Vec::new();
+        for attr in ast.attrs.iter() {
+            attrs.extend(Context::get_rustler_attrs(attr)?);
+        }
 
         //
         // Default: generate encoder and decoder
@@ -37,18 +91,12 @@ impl<'a> Context<'a> {
             attrs.push(RustlerAttr::Decode);
         }
 
-        let has_lifetime = match ast.generics.lifetimes().count() {
-            0 => false,
-            1 => true,
-            _ => panic!("Struct can only have one lifetime argument"),
-        };
-
-        let ident = &ast.ident;
-        let ident_with_lifetime = if has_lifetime {
-            quote! { #ident <'a> }
-        } else {
-            quote! { #ident }
-        };
+        if let Some(lifetime) = ast.generics.lifetimes().nth(1) {
+            return Err(syn::Error::new_spanned(
+                lifetime,
+                "Struct can only have one lifetime argument",
+            ));
+        }
 
         let variants = match ast.data {
             Data::Enum(ref data_enum) => Some(data_enum.variants.iter().collect()),
@@ -68,13 +116,49 @@ impl<'a> Context<'a> {
             _ => false,
         };
 
-        Self {
+        Ok(Self {
             attrs,
-            ident,
-            ident_with_lifetime,
+            ident: &ast.ident,
+            generics: &ast.generics,
             variants,
             struct_fields,
             is_tuple_struct,
+        })
+    }
+
+    /// The fields of the annotated struct, or an error naming `derive` if it is not a struct.
+    pub fn struct_fields(&self, derive: &str) -> syn::Result<&[&'a Field]> {
+        match self.struct_fields {
+            Some(ref fields) => Ok(fields),
+            None => Err(syn::Error::new(
+                self.ident.span(),
+                format!("{} can only be used with structs", derive),
+            )),
+        }
+    }
+
+    /// The fields of the annotated struct, which have to be named.
+    pub fn named_struct_fields(&self, derive: &str) -> syn::Result<&[&'a Field]> {
+        let fields = self.struct_fields(derive)?;
+
+        if self.is_tuple_struct {
+            return Err(syn::Error::new(
+                self.ident.span(),
+                format!("{} can only be used with structs with named fields", derive),
+            ));
+        }
+
+        Ok(fields)
+    }
+
+    /// The variants of the annotated enum, or an error naming `derive` if it is not an enum.
+    pub fn variants(&self, derive: &str) -> syn::Result<&[&'a Variant]> {
+        match self.variants {
+            Some(ref variants) => Ok(variants),
+            None => Err(syn::Error::new(
+                self.ident.span(),
+                format!("{} can only be used with enums", derive),
+            )),
         }
     }
 
@@ -89,6 +173,52 @@ impl<'a> Context<'a> {
         })
     }
 
+    pub fn deny_unknown_keys(&self) -> bool {
+        self.attrs
+            .iter()
+            .any(|attr| matches!(attr, RustlerAttr::DenyUnknownKeys))
+    }
+
+    /// Whether map keys are binaries rather than atoms, set with `#[rustler(keys = "string")]`.
+    pub fn string_keys(&self) -> bool {
+        self.attrs
+            .iter()
+            .any(|attr| matches!(attr, RustlerAttr::StringKeys))
+    }
+
+    /// Whether enum variants are encoded as their discriminant, set with
+    /// `#[rustler(repr = "integer")]`.
+    pub fn integer_repr(&self) -> bool {
+        self.attrs
+            .iter()
+            .any(|attr| matches!(attr, RustlerAttr::IntegerRepr))
+    }
+
+    /// The atom of an enum variant: its `#[rustler(rename = "...")]`, or its name converted by the
+    /// `#[rustler(rename_all = "...")]` rule of the enum, which defaults to `snake_case`.
+    pub fn variant_atom(&self, variant: &Variant, variant_attrs: &VariantAttrs) -> String {
+        if let Some(ref rename) = variant_attrs.rename {
+            return rename.clone();
+        }
+
+        let rule = self.attrs.iter().find_map(|attr| match attr {
+            RustlerAttr::RenameAll(ref rule) => Some(rule.as_str()),
+            _ => None,
+        });
+
+        let name = variant.ident.to_string();
+        match rule.unwrap_or("snake_case") {
+            "lowercase" => name.to_lowercase(),
+            "UPPERCASE" => name.to_uppercase(),
+            "PascalCase" => name,
+            "camelCase" => name.to_mixed_case(),
+            "SCREAMING_SNAKE_CASE" => name.to_shouty_snake_case(),
+            "kebab-case" => name.to_kebab_case(),
+            "SCREAMING-KEBAB-CASE" => name.to_shouty_kebab_case(),
+            _ => name.to_snake_case(),
+        }
+    }
+
     pub fn decode(&self) -> bool {
         self.attrs.iter().any(|attr| match attr {
             RustlerAttr::Decode => true,
@@ -96,23 +226,93 @@ impl<'a> Context<'a> {
         })
     }
 
-    pub fn field_atoms(&self) -> Option<Vec<TokenStream>> {
-        self.struct_fields.as_ref().map(|struct_fields| {
-            struct_fields
-                .iter()
-                .map(|field| {
-                    let atom_fun = Self::field_to_atom_fun(field);
+    /// The lifetime used in the generated `Decoder<'a>` implementation.
+    ///
+    /// If the annotated type has a lifetime parameter, the decoded value borrows from the term
+    /// for that lifetime. Otherwise, a fresh `'a` lifetime is introduced.
+    pub fn decode_lifetime(&self) -> Lifetime {
+        self.generics
+            .lifetimes()
+            .next()
+            .map(|def| def.lifetime.clone())
+            .unwrap_or_else(|| Lifetime::new("'a", Span::call_site()))
+    }
+
+    /// Generics, type generics and where clause for the generated `Encoder` implementation.
+    ///
+    /// Unless overridden with `#[rustler(bound = "...")]`, every type parameter is required to
+    /// implement `Encoder`.
+    pub fn encoder_generics(&self) -> (TokenStream, TokenStream, TokenStream) {
+        let bounds = self.bounds(
+            |attr| match attr {
+                RustlerAttr::EncodeBound(ref bound) => Some(bound),
+                _ => None,
+            },
+            quote! { ::rustler::Encoder },
+        );
 
-                    let ident = field.ident.as_ref().unwrap();
-                    let ident_str = ident.to_string();
-                    let ident_str = Self::remove_raw(&ident_str);
+        self.split_generics(None, bounds)
+    }
 
-                    quote! {
-                        #atom_fun = #ident_str,
-                    }
-                })
-                .collect()
-        })
+    /// Generics, type generics and where clause for the generated `Decoder` implementation.
+    ///
+    /// Unless overridden with `#[rustler(bound = "...")]`, every type parameter is required to
+    /// implement `Decoder` for the lifetime returned by `decode_lifetime()`.
+    pub fn decoder_generics(&self) -> (TokenStream, TokenStream, TokenStream) {
+        let lifetime = self.decode_lifetime();
+        let bounds = self.bounds(
+            |attr| match attr {
+                RustlerAttr::DecodeBound(ref bound) => Some(bound),
+                _ => None,
+            },
+            quote! { ::rustler::Decoder<#lifetime> },
+        );
+
+        if self.generics.lifetimes().next().is_some() {
+            self.split_generics(None, bounds)
+        } else {
+            self.split_generics(Some(lifetime), bounds)
+        }
+    }
+
+    fn bounds<F>(&self, find_override: F, default_bound: TokenStream) -> Vec<WherePredicate>
+    where
+        F: Fn(&RustlerAttr) -> Option<&Vec<WherePredicate>>,
+    {
+        if let Some(bounds) = self.attrs.iter().find_map(find_override) {
+            return bounds.clone();
+        }
+
+        self.generics
+            .type_params()
+            .map(|param| {
+                let ident = &param.ident;
+                syn::parse_quote! { #ident: #default_bound }
+            })
+            .collect()
+    }
+
+    fn split_generics(
+        &self,
+        extra_lifetime: Option<Lifetime>,
+        bounds: Vec<WherePredicate>,
+    ) -> (TokenStream, TokenStream, TokenStream) {
+        let mut generics = self.generics.clone();
+        if let Some(lifetime) = extra_lifetime {
+            generics
+                .params
+                .insert(0, GenericParam::Lifetime(LifetimeDef::new(lifetime)));
+        }
+        generics.make_where_clause().predicates.extend(bounds);
+
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+        let (_, ty_generics, _) = self.generics.split_for_impl();
+
+        (
+            quote! { #impl_generics },
+            quote! { #ty_generics },
+            quote! { #where_clause },
+        )
     }
 
     pub fn field_to_atom_fun(field: &Field) -> Ident {
@@ -142,7 +342,7 @@ impl<'a> Context<'a> {
         )
     }
 
-    fn remove_raw(ident_str: &str) -> &str {
+    pub fn remove_raw(ident_str: &str) -> &str {
         ident_str
             .split("r#")
             .last()
@@ -157,63 +357,304 @@ impl<'a> Context<'a> {
         })
     }
 
-    fn get_rustler_attrs(attr: &syn::Attribute) -> Vec<RustlerAttr> {
-        attr.path
-            .segments
-            .iter()
-            .filter_map(|segment| {
-                let meta = attr.parse_meta().expect("can parse meta");
-                match segment.ident.to_string().as_ref() {
-                    "rustler" => Some(Context::parse_rustler(&meta)),
-                    "tag" => Context::try_parse_tag(&meta),
-                    "module" => Context::try_parse_module(&meta),
-                    _ => None,
-                }
-            })
-            .flatten()
-            .collect()
+    fn get_rustler_attrs(attr: &syn::Attribute) -> syn::Result<Vec<RustlerAttr>> {
+        let parse: fn(&Meta) -> syn::Result<Vec<RustlerAttr>> = if attr.path.is_ident("rustler") {
+            Context::parse_rustler
+        } else if attr.path.is_ident("tag") {
+            Context::parse_tag
+        } else if attr.path.is_ident("module") {
+            Context::parse_module
+        } else {
+            return Ok(Vec::new());
+        };
+
+        parse(&attr.parse_meta()?)
     }
 
-    fn parse_rustler(meta: &Meta) -> Vec<RustlerAttr> {
+    fn parse_rustler(meta: &Meta) -> syn::Result<Vec<RustlerAttr>> {
         if let Meta::List(ref list) = meta {
-            return list
-                .nested
-                .iter()
-                .map(Context::parse_nested_rustler)
-                .collect();
+            let mut attrs = Vec::new();
+            for nested in list.nested.iter() {
+                attrs.extend(Context::parse_nested_rustler(nested)?);
+            }
+            return Ok(attrs);
         }
 
-        panic!("Expected encode and/or decode in rustler attribute");
+        Err(syn::Error::new_spanned(
+            meta,
+            format!(
+                "Expected `#[rustler(...)]` with any of the attributes {:?}",
+                CONTAINER_ATTRS
+            ),
+        ))
     }
 
-    fn parse_nested_rustler(nested: &NestedMeta) -> RustlerAttr {
-        if let NestedMeta::Meta(Meta::Path(ref path)) = nested {
-            match path.segments[0].ident.to_string().as_ref() {
-                "encode" => return RustlerAttr::Encode,
-                "decode" => return RustlerAttr::Decode,
-                other => panic!("Unexpected literal {}", other),
+    fn parse_nested_rustler(nested: &NestedMeta) -> syn::Result<Vec<RustlerAttr>> {
+        match nested {
+            NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("encode") => {
+                return Ok(vec![RustlerAttr::Encode])
+            }
+            NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("decode") => {
+                return Ok(vec![RustlerAttr::Decode])
+            }
+            NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("deny_unknown_keys") => {
+                return Ok(vec![RustlerAttr::DenyUnknownKeys])
+            }
+            NestedMeta::Meta(Meta::NameValue(ref name_value))
+                if name_value.path.is_ident("rename_all") =>
+            {
+                let rule = Context::parse_str_lit(&name_value.lit)?;
+                if !RENAME_RULES.contains(&rule.value().as_str()) {
+                    return Err(syn::Error::new_spanned(
+                        rule,
+                        format!("Unknown rename rule. Expected one of {:?}", RENAME_RULES),
+                    ));
+                }
+                return Ok(vec![RustlerAttr::RenameAll(rule.value())]);
+            }
+            NestedMeta::Meta(Meta::NameValue(ref name_value))
+                if name_value.path.is_ident("repr") =>
+            {
+                return match name_value.lit {
+                    Lit::Str(ref repr) if repr.value() == "atom" => Ok(vec![]),
+                    Lit::Str(ref repr) if repr.value() == "integer" => {
+                        Ok(vec![RustlerAttr::IntegerRepr])
+                    }
+                    ref lit => Err(syn::Error::new_spanned(
+                        lit,
+                        "Expected `repr = \"atom\"` or `repr = \"integer\"`",
+                    )),
+                };
             }
+            NestedMeta::Meta(Meta::NameValue(ref name_value))
+                if name_value.path.is_ident("keys") =>
+            {
+                return match name_value.lit {
+                    Lit::Str(ref keys) if keys.value() == "atom" => Ok(vec![]),
+                    Lit::Str(ref keys) if keys.value() == "string" => {
+                        Ok(vec![RustlerAttr::StringKeys])
+                    }
+                    ref lit => Err(syn::Error::new_spanned(
+                        lit,
+                        "Expected `keys = \"atom\"` or `keys = \"string\"`",
+                    )),
+                };
+            }
+            NestedMeta::Meta(Meta::NameValue(ref name_value))
+                if name_value.path.is_ident("hrl") =>
+            {
+                let path = Context::parse_str_lit(&name_value.lit)?;
+                return Ok(vec![RustlerAttr::Hrl(path.clone())]);
+            }
+            NestedMeta::Meta(Meta::NameValue(ref name_value))
+                if name_value.path.is_ident("bound") =>
+            {
+                let bounds = Context::parse_where_predicates(&name_value.lit)?;
+                return Ok(vec![
+                    RustlerAttr::EncodeBound(bounds.clone()),
+                    RustlerAttr::DecodeBound(bounds),
+                ]);
+            }
+            NestedMeta::Meta(Meta::List(ref list)) if list.path.is_ident("bound") => {
+                return list.nested.iter().map(Context::parse_bound).collect();
+            }
+            _ => (),
         }
 
-        panic!("Expected encode and/or decode in rustler attribute");
+        Err(syn::Error::new_spanned(
+            nested,
+            format!(
+                "Unknown rustler attribute. Allowed attributes: {:?}",
+                CONTAINER_ATTRS
+            ),
+        ))
     }
 
-    fn try_parse_tag(meta: &Meta) -> Option<Vec<RustlerAttr>> {
+    fn parse_bound(nested: &NestedMeta) -> syn::Result<RustlerAttr> {
+        if let NestedMeta::Meta(Meta::NameValue(ref name_value)) = nested {
+            if name_value.path.is_ident("encode") {
+                let bounds = Context::parse_where_predicates(&name_value.lit)?;
+                return Ok(RustlerAttr::EncodeBound(bounds));
+            }
+            if name_value.path.is_ident("decode") {
+                let bounds = Context::parse_where_predicates(&name_value.lit)?;
+                return Ok(RustlerAttr::DecodeBound(bounds));
+            }
+        }
+
+        Err(syn::Error::new_spanned(
+            nested,
+            "Expected `bound(encode = \"...\", decode = \"...\")` in rustler attribute",
+        ))
+    }
+
+    fn parse_where_predicates(lit: &Lit) -> syn::Result<Vec<WherePredicate>> {
+        match lit {
+            Lit::Str(ref bound) => Ok(bound
+                .parse_with(Punctuated::<WherePredicate, syn::Token![,]>::parse_terminated)?
+                .into_iter()
+                .collect()),
+            _ => Err(syn::Error::new_spanned(lit, "Expected a string literal")),
+        }
+    }
+
+    fn parse_tag(meta: &Meta) -> syn::Result<Vec<RustlerAttr>> {
         if let Meta::NameValue(ref name_value) = meta {
             if let Lit::Str(ref tag) = name_value.lit {
-                return Some(vec![RustlerAttr::Tag(tag.value())]);
+                return Ok(vec![RustlerAttr::Tag(tag.value())]);
             }
         }
-        panic!("Cannot parse module")
+
+        Err(syn::Error::new_spanned(
+            meta,
+            "Cannot parse tag, expected `#[tag = \"...\"]`",
+        ))
     }
 
-    fn try_parse_module(meta: &Meta) -> Option<Vec<RustlerAttr>> {
+    fn parse_module(meta: &Meta) -> syn::Result<Vec<RustlerAttr>> {
         if let Meta::NameValue(name_value) = meta {
             if let Lit::Str(ref module) = name_value.lit {
                 let ident = format!("Elixir.{}", module.value());
-                return Some(vec![RustlerAttr::Module(ident)]);
+                return Ok(vec![RustlerAttr::Module(ident)]);
             }
         }
-        panic!("Cannot parse tag")
+
+        Err(syn::Error::new_spanned(
+            meta,
+            "Cannot parse module, expected `#[module = \"...\"]`",
+        ))
+    }
+
+    pub fn field_attrs(field: &Field) -> syn::Result<FieldAttrs> {
+        let mut field_attrs = FieldAttrs::default();
+
+        for nested in Context::nested_rustler_attrs(&field.attrs, "field")? {
+            match nested {
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("default") => {
+                    field_attrs.default = Some(FieldDefault::Trait)
+                }
+                NestedMeta::Meta(Meta::NameValue(ref name_value))
+                    if name_value.path.is_ident("default") =>
+                {
+                    let path = Context::parse_str_lit(&name_value.lit)?.parse()?;
+                    field_attrs.default = Some(FieldDefault::Function(path))
+                }
+                NestedMeta::Meta(Meta::NameValue(ref name_value))
+                    if name_value.path.is_ident("key") =>
+                {
+                    field_attrs.key = Some(Context::parse_str_lit(&name_value.lit)?.value())
+                }
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("extra") => {
+                    field_attrs.extra = true
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "Unknown field attribute. Allowed attributes: [\"default\", \"key\", \"extra\"]",
+                    ))
+                }
+            }
+        }
+
+        Ok(field_attrs)
+    }
+
+    pub fn variant_attrs(variant: &Variant) -> syn::Result<VariantAttrs> {
+        let mut variant_attrs = VariantAttrs::default();
+
+        for nested in Context::nested_rustler_attrs(&variant.attrs, "variant")? {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(ref name_value))
+                    if name_value.path.is_ident("rename") =>
+                {
+                    variant_attrs.rename = Some(Context::parse_str_lit(&name_value.lit)?.value())
+                }
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("other") => {
+                    variant_attrs.other = true
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "Unknown variant attribute. Allowed attributes: [\"rename\", \"other\"]",
+                    ))
+                }
+            }
+        }
+
+        Ok(variant_attrs)
+    }
+
+    /// The options of all `#[rustler(...)]` attributes on a field or variant.
+    fn nested_rustler_attrs(attrs: &[syn::Attribute], item: &str) -> syn::Result<Vec<NestedMeta>> {
+        let mut nested = Vec::new();
+
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("rustler")) {
+            match attr.parse_meta()? {
+                Meta::List(list) => nested.extend(list.nested),
+                meta => {
+                    return Err(syn::Error::new_spanned(
+                        meta,
+                        format!("Expected `#[rustler(...)]` on {}", item),
+                    ))
+                }
+            }
+        }
+
+        Ok(nested)
+    }
+
+    fn parse_str_lit(lit: &Lit) -> syn::Result<&syn::LitStr> {
+        match lit {
+            Lit::Str(ref lit) => Ok(lit),
+            _ => Err(syn::Error::new_spanned(lit, "Expected a string literal")),
+        }
+    }
+
+    /// Splits the fields of a map-like struct into the ones mapped to a key and the
+    /// `#[rustler(extra)]` field, if any.
+    pub fn split_extra_field<'b>(
+        fields: &[&'b Field],
+    ) -> syn::Result<(Vec<&'b Field>, Option<&'b Field>)> {
+        let mut keyed = Vec::new();
+        let mut extra = None;
+
+        for field in fields.iter() {
+            if !Context::field_attrs(field)?.extra {
+                keyed.push(*field);
+            } else if extra.is_some() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "Only one field can be marked with `#[rustler(extra)]`",
+                ));
+            } else {
+                extra = Some(*field);
+            }
+        }
+
+        Ok((keyed, extra))
+    }
+
+    /// Fails, pointing at `field`, if its key is one of the keys already in use.
+    pub fn check_unique_key<'k, I>(field: &Field, key: &str, used: I) -> syn::Result<()>
+    where
+        I: IntoIterator<Item = &'k str>,
+    {
+        if used.into_iter().any(|other| other == key) {
+            return Err(syn::Error::new_spanned(
+                field,
+                format!("Duplicate key `{}`", key),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The key of a field in a map or keyword list: the name given with `#[rustler(key = "...")]`,
+    /// or the field name.
+    pub fn field_key(field: &Field, field_attrs: &FieldAttrs) -> String {
+        match field_attrs.key {
+            Some(ref key) => key.clone(),
+            None => Self::remove_raw(&field.ident.as_ref().unwrap().to_string()).to_string(),
+        }
     }
 }
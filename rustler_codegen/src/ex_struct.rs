@@ -5,18 +5,32 @@ use syn::{self, spanned::Spanned, Field, Ident};
 use super::context::Context;
 use super::RustlerAttr;
 
-pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
-    let ctx = Context::from_ast(ast);
-
-    let elixir_module = get_module(&ctx);
+pub fn transcoder_decorator(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let ctx = Context::from_ast(ast)?;
+
+    let struct_fields = ctx.named_struct_fields("NifStruct")?;
+    let (struct_fields, extra_field) = Context::split_extra_field(struct_fields)?;
+    let elixir_module = get_module(&ctx)?;
+
+    let mut field_atoms = Vec::new();
+    let mut keys = Vec::new();
+    for field in struct_fields.iter() {
+        let field_attrs = Context::field_attrs(field)?;
+        if field_attrs.default.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`default` is not supported by NifStruct",
+            ));
+        }
 
-    let struct_fields = ctx
-        .struct_fields
-        .as_ref()
-        .expect("NifStruct can only be used with structs");
+        let key = Context::field_key(field, &field_attrs);
+        let used = keys.iter().map(String::as_str).chain(Some("__struct__"));
+        Context::check_unique_key(field, &key, used)?;
 
-    // Unwrap is ok here, as we already determined that struct_fields is not None
-    let field_atoms = ctx.field_atoms().unwrap();
+        let atom_fun = Context::field_to_atom_fun(field);
+        field_atoms.push(quote! { #atom_fun = #key, });
+        keys.push(key);
+    }
 
     let atom_defs = quote! {
         rustler::atoms! {
@@ -29,13 +43,13 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
     let atoms_module_name = ctx.atoms_module_name(Span::call_site());
 
     let decoder = if ctx.decode() {
-        gen_decoder(&ctx, &struct_fields, &atoms_module_name)
+        gen_decoder(&ctx, &struct_fields, extra_field, &atoms_module_name)
     } else {
         quote! {}
     };
 
     let encoder = if ctx.encode() {
-        gen_encoder(&ctx, &struct_fields, &atoms_module_name)
+        gen_encoder(&ctx, &struct_fields, extra_field, &atoms_module_name)
     } else {
         quote! {}
     };
@@ -49,29 +63,37 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
         #encoder
     };
 
-    gen
+    Ok(gen)
 }
 
-fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> TokenStream {
-    let struct_type = &ctx.ident_with_lifetime;
+fn gen_decoder(
+    ctx: &Context,
+    fields: &[&Field],
+    extra_field: Option<&Field>,
+    atoms_module_name: &Ident,
+) -> TokenStream {
     let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.decoder_generics();
+    let lifetime = ctx.decode_lifetime();
     let struct_name_str = struct_name.to_string();
 
-    let idents: Vec<_> = fields
+    let atom_funs: Vec<_> = fields
         .iter()
-        .map(|field| field.ident.as_ref().unwrap())
+        .map(|field| Context::field_to_atom_fun(field))
         .collect();
 
-    let (assignments, field_defs): (Vec<TokenStream>, Vec<TokenStream>) = fields
+    let (assignments, mut field_defs): (Vec<TokenStream>, Vec<TokenStream>) = fields
         .iter()
-        .zip(idents.iter())
+        .zip(atom_funs.iter())
         .enumerate()
-        .map(|(index, (field, ident))| {
-            let atom_fun = Context::field_to_atom_fun(field);
+        .map(|(index, (field, atom_fun))| {
+            let ident = field.ident.as_ref().unwrap();
             let variable = Context::escape_ident_with_index(&ident.to_string(), index, "struct");
+            // The first value is the one of `__struct__`.
+            let value_index = index + 1;
 
             let assignment = quote_spanned! { field.span() =>
-                let #variable = try_decode_field(env, term, #atom_fun())?;
+                let #variable = try_decode_field(values[#value_index], #atom_fun())?;
             };
 
             let field_def = quote! {
@@ -82,24 +104,38 @@ fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
         })
         .unzip();
 
+    let lookup = match extra_field {
+        Some(field) => {
+            let ident = field.ident.as_ref().unwrap();
+            field_defs.push(quote_spanned! { field.span() =>
+                #ident: ::rustler::codegen_runtime::decode_extra(rest)?
+            });
+
+            quote! {
+                let (values, rest) = ::rustler::codegen_runtime::map_values_and_rest(term, &keys, true)?;
+            }
+        }
+        None => quote! {
+            let values = ::rustler::codegen_runtime::map_values(term, &keys)?;
+        },
+    };
+
     let gen = quote! {
-        impl<'a> ::rustler::Decoder<'a> for #struct_type {
-            fn decode(term: ::rustler::Term<'a>) -> Result<Self, ::rustler::Error> {
+        impl #impl_generics ::rustler::Decoder<#lifetime> for #struct_name #ty_generics #where_clause {
+            fn decode(term: ::rustler::Term<#lifetime>) -> Result<Self, ::rustler::Error> {
                 use #atoms_module_name::*;
                 use ::rustler::Encoder;
 
                 let env = term.get_env();
 
                 fn try_decode_field<'a, T>(
-                    env: rustler::Env<'a>,
-                    term: rustler::Term<'a>,
+                    value: Option<rustler::Term<'a>>,
                     field: rustler::Atom,
                     ) -> Result<T, rustler::Error>
                     where
                         T: rustler::Decoder<'a>,
                     {
-                        use rustler::Encoder;
-                        match ::rustler::Decoder::decode(term.map_get(field.encode(env))?) {
+                        match ::rustler::Decoder::decode(value.ok_or(::rustler::Error::BadArg)?) {
                             Err(_) => Err(::rustler::Error::RaiseTerm(Box::new(format!(
                                             "Could not decode field :{:?} on %{}{{}}",
                                             field, #struct_name_str
@@ -108,7 +144,10 @@ fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
                         }
                     };
 
-                let module: ::rustler::types::atom::Atom = term.map_get(atom_struct().to_term(env))?.decode()?;
+                let keys = [atom_struct().encode(env), #(#atom_funs().encode(env)),*];
+                #lookup
+
+                let module: ::rustler::types::atom::Atom = values[0].ok_or(::rustler::Error::BadArg)?.decode()?;
                 if module != atom_module() {
                     return Err(::rustler::Error::Atom("invalid_struct"));
                 }
@@ -123,28 +162,47 @@ fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
     gen
 }
 
-fn gen_encoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> TokenStream {
-    let struct_type = &ctx.ident_with_lifetime;
+fn gen_encoder(
+    ctx: &Context,
+    fields: &[&Field],
+    extra_field: Option<&Field>,
+    atoms_module_name: &Ident,
+) -> TokenStream {
+    let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.encoder_generics();
+
+    let atom_funs = fields.iter().map(|field| Context::field_to_atom_fun(field));
+    let values = fields.iter().map(|field| {
+        let field_ident = field.ident.as_ref().unwrap();
 
-    let field_defs: Vec<TokenStream> = fields
-        .iter()
-        .map(|field| {
-            let field_ident = field.ident.as_ref().unwrap();
-            let atom_fun = Context::field_to_atom_fun(field);
-            quote_spanned! { field.span() =>
-                map = map.map_put(#atom_fun().encode(env), self.#field_ident.encode(env)).unwrap();
+        quote_spanned! { field.span() =>
+            self.#field_ident.encode(env)
+        }
+    });
+
+    let build = match extra_field {
+        Some(field) => {
+            let ident = field.ident.as_ref().unwrap();
+            quote! {
+                let extra = self.#ident.encode(env);
+                ::rustler::codegen_runtime::map_with_extra(env, &keys, &values, extra).unwrap()
             }
-        })
-        .collect();
+        }
+        // The keys are distinct field names.
+        None => quote! {
+            ::rustler::Term::map_from_arrays(env, &keys, &values).unwrap()
+        },
+    };
 
     let gen = quote! {
-        impl<'b> ::rustler::Encoder for #struct_type {
-            fn encode<'a>(&self, env: ::rustler::Env<'a>) -> ::rustler::Term<'a> {
+        impl #impl_generics ::rustler::Encoder for #struct_name #ty_generics #where_clause {
+            fn encode<'__rustler_encode>(&self, env: ::rustler::Env<'__rustler_encode>) -> ::rustler::Term<'__rustler_encode> {
                 use #atoms_module_name::*;
-                let mut map = ::rustler::types::map::map_new(env);
-                map = map.map_put(atom_struct().encode(env), atom_module().encode(env)).unwrap();
-                #(#field_defs)*
-                map
+
+                let keys = [atom_struct().encode(env), #(#atom_funs().encode(env)),*];
+                let values = [atom_module().encode(env), #(#values),*];
+
+                #build
             }
         }
     };
@@ -152,12 +210,17 @@ fn gen_encoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
     gen
 }
 
-fn get_module(ctx: &Context) -> String {
+fn get_module(ctx: &Context) -> syn::Result<String> {
     ctx.attrs
         .iter()
         .find_map(|attr| match attr {
             RustlerAttr::Module(ref module) => Some(module.clone()),
             _ => None,
         })
-        .expect("NifStruct requires a 'module' attribute")
+        .ok_or_else(|| {
+            syn::Error::new(
+                ctx.ident.span(),
+                "NifStruct requires a `#[module = \"...\"]` attribute",
+            )
+        })
 }
@@ -0,0 +1,189 @@
+//! Reading record definitions from Erlang header files, used to check `NifRecord` structs
+//! annotated with `#[rustler(hrl = "...")]`.
+
+/// Returns the field names of the record `name` defined in the Erlang source `source`, in the
+/// order of the definition, or `None` if the record is not defined there.
+pub fn record_fields(source: &str, name: &str) -> Option<Vec<String>> {
+    let source: Vec<char> = strip_comments(source).chars().collect();
+    let mut pos = 0;
+
+    while let Some(start) = find(&source, pos, "-record") {
+        pos = start + "-record".len();
+
+        let mut cursor = skip_whitespace(&source, pos);
+        if source.get(cursor) != Some(&'(') {
+            continue;
+        }
+        cursor = skip_whitespace(&source, cursor + 1);
+
+        let (record_name, end) = match read_atom(&source, cursor) {
+            Some(atom) => atom,
+            None => continue,
+        };
+        if record_name != name {
+            continue;
+        }
+
+        cursor = skip_whitespace(&source, end);
+        if source.get(cursor) != Some(&',') {
+            continue;
+        }
+        cursor = skip_whitespace(&source, cursor + 1);
+        if source.get(cursor) != Some(&'{') {
+            continue;
+        }
+
+        return Some(
+            split_fields(&source, cursor + 1)?
+                .iter()
+                .filter_map(|field| read_atom(field, skip_whitespace(field, 0)))
+                .map(|(field, _)| field)
+                .collect(),
+        );
+    }
+
+    None
+}
+
+/// Removes `%` comments, leaving strings, quoted atoms and character literals intact.
+fn strip_comments(source: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut chars = source.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        result.push('\n');
+                        break;
+                    }
+                }
+            }
+            '"' | '\'' => {
+                result.push(c);
+                let mut escaped = false;
+                for inner in chars.by_ref() {
+                    result.push(inner);
+                    match inner {
+                        '\\' if !escaped => escaped = true,
+                        _ if inner == c && !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            '$' => {
+                // A character literal such as `$%` or `$\n`.
+                result.push(c);
+                if let Some(next) = chars.next() {
+                    result.push(next);
+                    if next == '\\' {
+                        result.extend(chars.next());
+                    }
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Splits the fields of a record definition at top-level commas, starting right after the `{`.
+fn split_fields(source: &[char], start: usize) -> Option<Vec<Vec<char>>> {
+    let mut fields = Vec::new();
+    let mut current = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut pos = start;
+
+    loop {
+        let c = *source.get(pos)?;
+        let next = source.get(pos + 1).copied();
+        pos += 1;
+
+        if let Some(q) = quote {
+            match c {
+                '\\' if !escaped => escaped = true,
+                _ if c == q && !escaped => quote = None,
+                _ => escaped = false,
+            }
+            current.push(c);
+            continue;
+        }
+
+        match (c, next) {
+            ('"', _) | ('\'', _) => quote = Some(c),
+            ('$', Some(literal)) => {
+                current.push(c);
+                current.push(literal);
+                pos += 1;
+                continue;
+            }
+            ('<', Some('<')) | ('>', Some('>')) => {
+                depth += if c == '<' { 1 } else { -1 };
+                current.extend([c, c]);
+                pos += 1;
+                continue;
+            }
+            ('(', _) | ('[', _) | ('{', _) => depth += 1,
+            ('}', _) if depth == 0 => {
+                if current.iter().any(|c| !c.is_whitespace()) {
+                    fields.push(current);
+                }
+                return Some(fields);
+            }
+            (')', _) | (']', _) | ('}', _) => depth -= 1,
+            (',', _) if depth == 0 => {
+                fields.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => (),
+        }
+
+        current.push(c);
+    }
+}
+
+/// Reads a bare or quoted atom at `pos`, returning it and the position after it.
+fn read_atom(source: &[char], pos: usize) -> Option<(String, usize)> {
+    let first = *source.get(pos)?;
+
+    if first == '\'' {
+        let mut atom = String::new();
+        let mut chars = source[pos + 1..].iter().enumerate();
+        while let Some((offset, &c)) = chars.next() {
+            match c {
+                '\\' => atom.extend(chars.next().map(|(_, &c)| c)),
+                '\'' => return Some((atom, pos + offset + 2)),
+                _ => atom.push(c),
+            }
+        }
+        return None;
+    }
+
+    if !first.is_lowercase() {
+        return None;
+    }
+
+    let end = source[pos..]
+        .iter()
+        .position(|&c| !(c.is_alphanumeric() || c == '_' || c == '@'))
+        .map_or(source.len(), |len| pos + len);
+
+    Some((source[pos..end].iter().collect(), end))
+}
+
+fn skip_whitespace(source: &[char], pos: usize) -> usize {
+    source[pos.min(source.len())..]
+        .iter()
+        .position(|c| !c.is_whitespace())
+        .map_or(source.len(), |len| pos + len)
+}
+
+fn find(source: &[char], from: usize, needle: &str) -> Option<usize> {
+    let needle: Vec<char> = needle.chars().collect();
+    (from..source.len().saturating_sub(needle.len() - 1))
+        .find(|&start| source[start..start + needle.len()] == needle[..])
+}
@@ -1,39 +1,97 @@
 use proc_macro2::{Span, TokenStream};
-use quote::quote;
+use quote::{quote, quote_spanned};
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::token::Comma;
 use syn::{Expr, Ident, Result, Token};
 
 #[derive(Debug)]
 pub struct InitMacroInput {
     name: syn::Lit,
-    funcs: syn::ExprArray,
+    funcs: Option<syn::ExprArray>,
     load: TokenStream,
 }
 
 impl Parse for InitMacroInput {
     fn parse(input: ParseStream) -> Result<Self> {
         let name = syn::Lit::parse(input)?;
-        let _comma = <syn::Token![,]>::parse(input)?;
-        let funcs = syn::ExprArray::parse(input)?;
-        let options = parse_expr_assigns(input);
+        let funcs = if input.peek(Token![,]) && input.peek2(syn::token::Bracket) {
+            let _comma = <syn::Token![,]>::parse(input)?;
+            let funcs = syn::ExprArray::parse(input)?;
+            validate_funcs(&funcs.elems)?;
+            Some(funcs)
+        } else {
+            None
+        };
+        let options = parse_expr_assigns(input)?;
         let load = extract_option(options, "load");
 
         Ok(InitMacroInput { name, funcs, load })
     }
 }
 
-fn parse_expr_assigns(input: ParseStream) -> Vec<syn::ExprAssign> {
+fn parse_expr_assigns(input: ParseStream) -> Result<Vec<syn::ExprAssign>> {
+    let known_options = ["load"];
     let mut vec = Vec::new();
 
     while <Token![,]>::parse(input).is_ok() {
-        match syn::ExprAssign::parse(input) {
-            Ok(expr) => vec.push(expr),
-            Err(err) => panic!("{} (i.e. `load = load`)", err),
+        if input.is_empty() {
+            break;
+        }
+
+        let expr = syn::ExprAssign::parse(input)
+            .map_err(|err| syn::Error::new(err.span(), format!("{} (i.e. `load = load`)", err)))?;
+
+        let known = match &*expr.left {
+            Expr::Path(syn::ExprPath { path, .. }) => {
+                known_options.iter().any(|known| path.is_ident(known))
+            }
+            _ => false,
+        };
+        if !known {
+            return Err(syn::Error::new_spanned(
+                &expr.left,
+                format!("Unknown option. Allowed options: {:?}", known_options),
+            ));
+        }
+
+        vec.push(expr);
+    }
+
+    if !input.is_empty() {
+        return Err(input.error("Expected `,`"));
+    }
+
+    Ok(vec)
+}
+
+/// Checks that every entry is the path of a NIF and that no NIF is listed twice.
+fn validate_funcs(funcs: &Punctuated<Expr, Comma>) -> Result<()> {
+    let mut seen = Vec::new();
+
+    for func in funcs.iter() {
+        match func {
+            Expr::Path(syn::ExprPath { path, .. }) => {
+                let name = quote!(#path).to_string();
+                if seen.contains(&name) {
+                    return Err(syn::Error::new_spanned(
+                        func,
+                        "This NIF is listed more than once",
+                    ));
+                }
+                seen.push(name);
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    func,
+                    "Expected the path of a function annotated with `#[rustler::nif]`",
+                ))
+            }
         }
     }
-    vec
+
+    Ok(())
 }
 
 fn extract_option(args: Vec<syn::ExprAssign>, name: &str) -> TokenStream {
@@ -55,26 +113,61 @@ fn extract_option(args: Vec<syn::ExprAssign>, name: &str) -> TokenStream {
 impl From<InitMacroInput> for proc_macro2::TokenStream {
     fn from(input: InitMacroInput) -> Self {
         let name = input.name;
-        let num_of_funcs = input.funcs.elems.len();
-        let funcs = nif_funcs(input.funcs.elems);
         let load = input.load;
 
+        let (funcs, nifs, check) = match input.funcs {
+            Some(funcs) => {
+                let metadata = nif_metadata(&funcs.elems);
+                let checks = unique_nif_checks(&funcs.elems);
+                let len = nif_funcs_len(&funcs.elems);
+                let funcs = nif_funcs(funcs.elems);
+                (
+                    quote!({
+                        static FUNCS: rustler::codegen_runtime::NifFuncs<{ #len }> =
+                            rustler::codegen_runtime::NifFuncs::concat(&[#funcs]);
+                        FUNCS.as_slice()
+                    }),
+                    quote!({
+                        #checks
+                        Some(&[#metadata])
+                    }),
+                    quote!(),
+                )
+            }
+            None => (
+                quote!(rustler::codegen_runtime::registered_nif_funcs(
+                    module_path!()
+                )),
+                quote!(None),
+                // Only a status can be returned to the BEAM, which reports a failed load call.
+                quote! {
+                    if rustler::codegen_runtime::registered_nif_clash(module_path!()).is_some() {
+                        return 1;
+                    }
+                },
+            ),
+        };
+
         let inner = quote! {
             static mut NIF_ENTRY: Option<rustler::codegen_runtime::DEF_NIF_ENTRY> = None;
             use rustler::Nif;
 
+            let funcs = #funcs;
+
             let entry = rustler::codegen_runtime::DEF_NIF_ENTRY {
                 major: rustler::codegen_runtime::NIF_MAJOR_VERSION,
                 minor: rustler::codegen_runtime::NIF_MINOR_VERSION,
                 name: concat!(#name, "\0").as_ptr() as *const u8,
-                num_of_funcs: #num_of_funcs as rustler::codegen_runtime::c_int,
-                funcs: [#funcs].as_ptr(),
+                num_of_funcs: funcs.len() as rustler::codegen_runtime::c_int,
+                funcs: funcs.as_ptr(),
                 load: {
                     extern "C" fn nif_load(
                         env: rustler::codegen_runtime::NIF_ENV,
                         _priv_data: *mut *mut rustler::codegen_runtime::c_void,
                         load_info: rustler::codegen_runtime::NIF_TERM
                     ) -> rustler::codegen_runtime::c_int {
+                        #check
+
                         unsafe {
                             // TODO: If an unwrap ever happens, we will unwind right into C! Fix this!
                             rustler::codegen_runtime::handle_nif_init_call(#load, env, load_info)
@@ -84,7 +177,15 @@ impl From<InitMacroInput> for proc_macro2::TokenStream {
                 },
                 reload: None,
                 upgrade: None,
-                unload: None,
+                unload: {
+                    extern "C" fn nif_unload(
+                        _env: rustler::codegen_runtime::NIF_ENV,
+                        _priv_data: *mut rustler::codegen_runtime::c_void
+                    ) {
+                        rustler::codegen_runtime::handle_nif_unload_call()
+                    }
+                    Some(nif_unload)
+                },
                 vm_variant: b"beam.vanilla\0".as_ptr(),
                 options: 0,
                 sizeof_ErlNifResourceTypeInit: rustler::codegen_runtime::get_nif_resource_type_init_size(),
@@ -97,6 +198,23 @@ impl From<InitMacroInput> for proc_macro2::TokenStream {
         };
 
         quote! {
+            /// Description of the NIFs exported by `rustler::init!`, for use with
+            /// `rustler::stubs`.
+            #[allow(dead_code)]
+            pub const RUSTLER_NIF_MODULE: rustler::stubs::NifModule = rustler::stubs::NifModule {
+                name: #name,
+                nifs: #nifs,
+                module_path: module_path!(),
+            };
+
+            // The NIF API of the `testing` feature would replace the one of the BEAM. Unit tests
+            // of the NIF crate are never loaded by the BEAM, so they may enable it.
+            #[cfg(not(test))]
+            const _: () = assert!(
+                !rustler::codegen_runtime::TESTING,
+                "A library using `rustler::init!` cannot be built with the `testing` feature of rustler. Enable it in `[dev-dependencies]` only, with `resolver = \"2\"`"
+            );
+
             #[cfg(unix)]
             #[no_mangle]
             extern "C" fn nif_init() -> *const rustler::codegen_runtime::DEF_NIF_ENTRY {
@@ -116,15 +234,61 @@ impl From<InitMacroInput> for proc_macro2::TokenStream {
     }
 }
 
-fn nif_funcs(funcs: Punctuated<Expr, Comma>) -> TokenStream {
+fn nif_metadata(funcs: &Punctuated<Expr, Comma>) -> TokenStream {
     let mut tokens = TokenStream::new();
 
     for func in funcs.iter() {
-        if let Expr::Path(_) = *func {
-            tokens.extend(quote!(#func::FUNC,));
-        } else {
-            panic!("Expected an expression, found: {}", stringify!(func));
+        tokens.extend(quote!(<#func as rustler::Nif>::METADATA,));
+    }
+
+    tokens
+}
+
+/// Fails the build, pointing at the NIF, when a NIF is exported with the same name and arity as
+/// one listed before it. The exported names are only known from `Nif::METADATA`, since they can
+/// be changed with `#[rustler::nif(name = "...")]`.
+fn unique_nif_checks(funcs: &Punctuated<Expr, Comma>) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    let mut previous = TokenStream::new();
+
+    for func in funcs.iter() {
+        if !previous.is_empty() {
+            let message = format!(
+                "`{}` is exported with the same name and arity as another NIF passed to `rustler::init!`",
+                quote!(#func).to_string().replace(' ', "")
+            );
+            tokens.extend(quote_spanned! {func.span()=>
+                const _: () = assert!(
+                    !rustler::codegen_runtime::clashes_with_any(
+                        &[#previous],
+                        &<#func as rustler::Nif>::METADATA
+                    ),
+                    #message
+                );
+            });
         }
+        previous.extend(quote!(<#func as rustler::Nif>::METADATA,));
+    }
+
+    tokens
+}
+
+/// The total number of functions exported for the listed NIFs, counting each arity.
+fn nif_funcs_len(funcs: &Punctuated<Expr, Comma>) -> TokenStream {
+    let mut tokens = quote!(0);
+
+    for func in funcs.iter() {
+        tokens.extend(quote!(+ <#func as rustler::Nif>::FUNCS.len()));
+    }
+
+    tokens
+}
+
+fn nif_funcs(funcs: Punctuated<Expr, Comma>) -> TokenStream {
+    let mut tokens = TokenStream::new();
+
+    for func in funcs.iter() {
+        tokens.extend(quote!(#func::FUNCS,));
     }
 
     tokens
@@ -0,0 +1,193 @@
+use proc_macro2::{Span, TokenStream};
+
+use syn::{self, spanned::Spanned, Field, Ident};
+
+use super::context::{Context, FieldDefault};
+
+pub fn transcoder_decorator(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let ctx = Context::from_ast(ast)?;
+
+    let struct_fields = ctx.named_struct_fields("NifKeywordList")?;
+
+    let mut field_atoms = Vec::new();
+    let mut keys: Vec<String> = Vec::new();
+    for field in struct_fields.iter() {
+        let atom_fun = Context::field_to_atom_fun(field);
+        let key = Context::field_key(field, &Context::field_attrs(field)?);
+        Context::check_unique_key(field, &key, keys.iter().map(String::as_str))?;
+        field_atoms.push(quote! { #atom_fun = #key, });
+        keys.push(key);
+    }
+
+    let atom_defs = quote! {
+        rustler::atoms! {
+            #(#field_atoms)*
+        }
+    };
+
+    let atoms_module_name = ctx.atoms_module_name(Span::call_site());
+
+    let decoder = if ctx.decode() {
+        gen_decoder(&ctx, struct_fields, &atoms_module_name)?
+    } else {
+        quote! {}
+    };
+
+    let encoder = if ctx.encode() {
+        gen_encoder(&ctx, struct_fields, &atoms_module_name)
+    } else {
+        quote! {}
+    };
+
+    let gen = quote! {
+        mod #atoms_module_name {
+            #atom_defs
+        }
+
+        #decoder
+        #encoder
+    };
+
+    Ok(gen)
+}
+
+fn gen_decoder(
+    ctx: &Context,
+    fields: &[&Field],
+    atoms_module_name: &Ident,
+) -> syn::Result<TokenStream> {
+    let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.decoder_generics();
+    let lifetime = ctx.decode_lifetime();
+
+    let mut variables = Vec::new();
+    let mut matches = Vec::new();
+    let mut field_defs = Vec::new();
+
+    for (index, field) in fields.iter().enumerate() {
+        let ident = field.ident.as_ref().unwrap();
+        let ident_str = ident.to_string();
+        let field_attrs = Context::field_attrs(field)?;
+        if field_attrs.extra {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`extra` is not supported by NifKeywordList",
+            ));
+        }
+
+        let key = Context::field_key(field, &field_attrs);
+        let ty = &field.ty;
+        let atom_fun = Context::field_to_atom_fun(field);
+        let variable = Context::escape_ident_with_index(&ident_str, index, "keyword");
+
+        let decode_error = format!("Could not decode key :{} in keyword list", key);
+        let missing = match field_attrs.default {
+            Some(FieldDefault::Trait) => quote! { ::std::default::Default::default() },
+            Some(FieldDefault::Function(path)) => quote! { #path() },
+            None if is_option(ty) => quote! { None },
+            None => {
+                let missing_error = format!("Missing key :{} in keyword list", key);
+                quote! {
+                    return Err(::rustler::Error::RaiseTerm(Box::new(#missing_error)))
+                }
+            }
+        };
+
+        variables.push(quote_spanned! { field.span() =>
+            let mut #variable: Option<#ty> = None;
+        });
+
+        // Like `Keyword.get/2`, the first entry of a repeated key wins. The later ones are not
+        // decoded.
+        matches.push(quote! {
+            if key == #atom_fun() {
+                if #variable.is_none() {
+                    #variable = Some(value.decode().map_err(|_| {
+                        ::rustler::Error::RaiseTerm(Box::new(#decode_error))
+                    })?);
+                }
+            }
+        });
+
+        field_defs.push(quote! {
+            #ident: match #variable {
+                Some(value) => value,
+                None => #missing,
+            }
+        });
+    }
+
+    let unknown = if ctx.deny_unknown_keys() {
+        quote! {
+            return Err(::rustler::Error::RaiseTerm(Box::new(format!(
+                "Unknown key :{:?} in keyword list",
+                key
+            ))));
+        }
+    } else {
+        quote! {
+            let _ = (key, value);
+        }
+    };
+
+    let gen = quote! {
+        impl #impl_generics ::rustler::Decoder<#lifetime> for #struct_name #ty_generics #where_clause {
+            fn decode(term: ::rustler::Term<#lifetime>) -> Result<Self, ::rustler::Error> {
+                use #atoms_module_name::*;
+
+                let keyword: ::rustler::Keyword<#lifetime> = term.decode()?;
+
+                #(#variables)*
+
+                for (key, value) in keyword.iter() {
+                    #(#matches else)* {
+                        #unknown
+                    }
+                }
+
+                Ok(#struct_name { #(#field_defs),* })
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
+fn gen_encoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> TokenStream {
+    let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.encoder_generics();
+
+    let entries: Vec<TokenStream> = fields
+        .iter()
+        .map(|field| {
+            let field_ident = field.ident.as_ref().unwrap();
+            let atom_fun = Context::field_to_atom_fun(field);
+
+            quote_spanned! { field.span() =>
+                (#atom_fun(), self.#field_ident.encode(env))
+            }
+        })
+        .collect();
+
+    let gen = quote! {
+        impl #impl_generics ::rustler::Encoder for #struct_name #ty_generics #where_clause {
+            fn encode<'__rustler_encode>(&self, env: ::rustler::Env<'__rustler_encode>) -> ::rustler::Term<'__rustler_encode> {
+                use #atoms_module_name::*;
+
+                let keyword: ::rustler::Keyword = vec![#(#entries),*].into_iter().collect();
+                keyword.encode(env)
+            }
+        }
+    };
+
+    gen
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            matches!(path.segments.last(), Some(segment) if segment.ident == "Option")
+        }
+        _ => false,
+    }
+}
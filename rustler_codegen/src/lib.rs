@@ -12,10 +12,15 @@ extern crate quote;
 
 mod context;
 mod ex_struct;
+mod hrl;
 mod init;
+mod keyword_list;
 mod map;
+mod match_term;
 mod nif;
 mod record;
+mod resource_impl;
+mod term;
 mod tuple;
 mod unit_enum;
 mod untagged_enum;
@@ -26,6 +31,13 @@ enum RustlerAttr {
     Decode,
     Module(String),
     Tag(String),
+    EncodeBound(Vec<syn::WherePredicate>),
+    DecodeBound(Vec<syn::WherePredicate>),
+    DenyUnknownKeys,
+    StringKeys,
+    RenameAll(String),
+    IntegerRepr,
+    Hrl(syn::LitStr),
 }
 
 /// Implementation of a Native Implementated Function (NIF) macro that lets the user annotate
@@ -54,6 +66,17 @@ enum RustlerAttr {
 ///
 /// rustler::init!("Elixir.Math", [add, sub, mul, div], Some(load));
 /// ```
+///
+/// The list of functions is optional. Without it, every function annotated with
+/// `#[rustler::nif]` in the crate calling `rustler::init!` is exported. NIFs defined in other
+/// crates linked into the library, such as dependencies, are not:
+///
+/// ```ignore
+/// rustler::init!("Elixir.Math", load = load);
+/// ```
+///
+/// Two listed NIFs exported with the same name and arity fail the build. Without a list, the
+/// clash is only found when the library is loaded, and loading fails.
 #[proc_macro]
 pub fn init(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as init::InitMacroInput);
@@ -61,6 +84,54 @@ pub fn init(input: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// Builds a term from Elixir-like syntax.
+///
+/// The first argument is the `Env` to build the term in. Atoms, tuples, lists, maps, keyword
+/// entries and integer, float and string literals are written as in Elixir, and anything else is a
+/// Rust expression encoded with `Encoder`. Atoms are created once and cached, like the ones
+/// declared with `rustler::atoms!`.
+///
+/// ```ignore
+/// let reply = rustler::term!(env, {:ok, %{name: name, tags: [:a, :b], "raw" => bin}});
+/// ```
+///
+/// Structs are written as `%Module.Name{field: value}`, and `nil` is an atom as in Elixir. Rust
+/// expressions containing a comma outside of parentheses, brackets or generic arguments such as
+/// `HashMap::<String, i64>::new()`, comparisons with `<` and blocks have to be wrapped in
+/// parentheses.
+#[proc_macro]
+pub fn term(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as term::TermMacroInput);
+    term::expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Matches a term against patterns written in Elixir-like syntax.
+///
+/// Patterns can contain atoms, integer, float and string literals, tuples, lists with an optional
+/// `| tail`, and maps with atom or literal keys, which match maps containing at least these keys.
+/// `name` binds the term itself, `name: Type` binds the term decoded as `Type` and does not match
+/// if decoding fails, and `_` matches anything. Typed bindings are only decoded once the rest of
+/// the pattern matched. Arms can have an `if` guard. The last arm has to match any term.
+///
+/// ```ignore
+/// let reply = rustler::match_term!(term, {
+///     {:ok, count: i64} if count > 0 => count,
+///     {:error, reason: String} => return Err(Error::Term(Box::new(reason))),
+///     [first: i64 | _] => first,
+///     %{"count" => count: i64} => count,
+///     _ => 0,
+/// });
+/// ```
+#[proc_macro]
+pub fn match_term(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as match_term::MatchTermInput);
+    match_term::expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
 /// Implementation of a Native Implementated Function (NIF) macro that lets the user annotate
 /// a function that will be wrapped in higer-level NIF implementation.
 ///
@@ -70,12 +141,83 @@ pub fn init(input: TokenStream) -> TokenStream {
 ///     a + b
 /// }
 /// ```
+///
+/// Trailing `Option` arguments can be made optional, in which case the NIF is exported once per
+/// arity and the omitted arguments are `None`. `#[nif(defaults)]` makes all trailing `Option`
+/// arguments optional, while `#[default]` marks individual ones:
+///
+/// ```ignore
+/// // Exported as both `parse/1` and `parse/2`.
+/// #[nif]
+/// fn parse(input: String, #[default] base: Option<u32>) -> NifResult<i64> {
+///     // ...
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn nif(args: TokenStream, input: TokenStream) -> TokenStream {
     let args = syn::parse_macro_input!(args as syn::AttributeArgs);
     let input = syn::parse_macro_input!(input as syn::ItemFn);
 
-    nif::transcoder_decorator(args, input).into()
+    nif::transcoder_decorator(args, input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Exports the `pub` methods of a type as NIFs operating on a resource of that type.
+///
+/// Methods that are not `pub` are left out, as are `pub` methods marked with
+/// `#[rustler(skip)]`. Methods taking `&self` or `&mut self` become NIFs taking the resource as their first argument,
+/// called with a read or write lock on the value held. Associated functions returning `Self` or
+/// a `Result` of `Self` become constructors returning a new resource, and other associated
+/// functions become plain NIFs. The NIFs are named after the type and the method, e.g.
+/// `counter_incr`; the prefix can be changed with `#[rustler::resource_impl(prefix = "...")]`.
+///
+/// The resource type is registered automatically when the library is loaded. Other NIFs can
+/// accept the resource as a `ResourceArc<ResourceLock<T>>`.
+///
+/// ```ignore
+/// pub struct Counter {
+///     value: i64,
+/// }
+///
+/// #[rustler::resource_impl]
+/// impl Counter {
+///     pub fn new(value: i64) -> Self {
+///         Counter { value }
+///     }
+///
+///     pub fn get(&self) -> i64 {
+///         self.value
+///     }
+///
+///     #[rustler(name = "counter_add")]
+///     pub fn incr(&mut self, by: i64) -> i64 {
+///         self.value += by;
+///         self.value
+///     }
+///
+///     #[rustler(skip)]
+///     pub fn reset(&mut self) {
+///         self.value = 0;
+///     }
+///
+///     fn is_zero(&self) -> bool {
+///         self.value == 0
+///     }
+/// }
+/// ```
+///
+/// Methods accept the same `#[rustler(name = "...", schedule = "...")]` options as
+/// `#[rustler::nif]`. Since the lock is released when the NIF returns, methods cannot return
+/// references into the value.
+#[proc_macro_attribute]
+pub fn resource_impl(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(args as syn::AttributeArgs);
+    let input = syn::parse_macro_input!(input as syn::ItemImpl);
+
+    resource_impl::resource_impl(args, input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 /// Implementation of the `NifStruct` macro that lets the user annotate a struct that will
@@ -98,10 +240,16 @@ pub fn nif(args: TokenStream, input: TokenStream) -> TokenStream {
 ///   defstruct lhs: 0, rhs: 0
 /// end
 /// ```
+///
+/// Like with `NifMap`, `#[rustler(key = "...")]` changes the key of a field and a
+/// `#[rustler(extra)]` field keeps the keys of the Elixir struct that the Rust struct does not
+/// declare.
 #[proc_macro_derive(NifStruct, attributes(module, rustler))]
 pub fn nif_struct(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
-    ex_struct::transcoder_decorator(&ast).into()
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    ex_struct::transcoder_decorator(&ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 /// Implementation of a macro that lets the user annotate a struct with `NifMap` so that the
@@ -122,10 +270,80 @@ pub fn nif_struct(input: TokenStream) -> TokenStream {
 /// ```elixir
 /// %{lhs: 33, rhs: 21}
 /// ```
+///
+/// Keys are atoms named after the fields. `#[rustler(keys = "string")]` on the struct uses
+/// binary keys instead, as in maps decoded from JSON, without creating atoms. The key of a single
+/// field can be changed with `#[rustler(key = "...")]`:
+///
+/// ```ignore
+/// #[derive(NifMap)]
+/// #[rustler(keys = "string")]
+/// struct User {
+///     id: u64,
+///     #[rustler(key = "displayName")]
+///     display_name: String,
+/// }
+/// ```
+///
+/// This maps to `%{"id" => 1, "displayName" => "Jane"}`.
+///
+/// A field marked with `#[rustler(extra)]` collects the entries that match no other field when
+/// decoding, and adds them back when encoding, so that maps round-trip through Rust without losing
+/// data. Its type can be any collection of pairs implementing `FromIterator` and `Encoder`, such
+/// as `HashMap<Atom, Term<'a>>` or `Vec<(Term<'a>, Term<'a>)>`. The same attribute is supported
+/// by `NifStruct`.
+///
+/// Like all derives in this crate, `NifMap` supports type and const generics. Every type
+/// parameter is required to implement `Encoder` for the encoder and `Decoder<'a>` for the
+/// decoder. These bounds can be replaced using `#[rustler(bound = "...")]`, or separately with
+/// `#[rustler(bound(encode = "...", decode = "..."))]`. The decoder lifetime is the lifetime
+/// parameter of the annotated type if it has one, and `'a` otherwise.
+///
+/// ```ignore
+/// #[derive(NifMap)]
+/// struct Page<T> {
+///     items: Vec<T>,
+///     total: u64,
+/// }
+/// ```
 #[proc_macro_derive(NifMap, attributes(rustler))]
 pub fn nif_map(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
-    map::transcoder_decorator(&ast).into()
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    map::transcoder_decorator(&ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Implementation of a macro that lets the user annotate a struct with `NifKeywordList` so that
+/// the struct can be encoded or decoded from an Elixir keyword list or an Erlang proplist. For
+/// example, the following struct annotated as such:
+///
+/// ```ignore
+/// #[derive(NifKeywordList)]
+/// struct Options {
+///     timeout: u64,
+///     #[rustler(default)]
+///     retries: u32,
+///     name: Option<String>,
+/// }
+/// ```
+///
+/// decodes `[timeout: 5000, retries: 3]`. Keys of `Option` fields may be left out and decode to
+/// `None`. Fields marked with `#[rustler(default)]` fall back to `Default::default()`, and with
+/// `#[rustler(default = "path")]` to the result of calling `path()`. Any other missing key is an
+/// error. When a key is repeated in the input, its first value is used, as with `Keyword.get/2`,
+/// and the later values are ignored without being decoded.
+///
+/// `#[rustler(key = "...")]` changes the key of a field. Two fields with the same key are a
+/// compile error. Unknown keys are ignored unless the
+/// struct is annotated with `#[rustler(deny_unknown_keys)]`. When encoded, every field is emitted
+/// in declaration order, with `None` encoded as `nil`.
+#[proc_macro_derive(NifKeywordList, attributes(rustler))]
+pub fn nif_keyword_list(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    keyword_list::transcoder_decorator(&ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 /// Implementation of a macro that lets the user annotate a struct with `NifTuple` so that the
@@ -150,8 +368,10 @@ pub fn nif_map(input: TokenStream) -> TokenStream {
 /// The size of the tuple will depend on the number of elements in the struct.
 #[proc_macro_derive(NifTuple, attributes(rustler))]
 pub fn nif_tuple(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
-    tuple::transcoder_decorator(&ast).into()
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    tuple::transcoder_decorator(&ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 /// Implementation of the `NifRecord` macro that lets the user annotate a struct that will
@@ -175,10 +395,27 @@ pub fn nif_tuple(input: TokenStream) -> TokenStream {
 ///   defrecord :record, [lhs: 1, rhs: 2]
 /// end
 /// ```
+///
+/// If the record is defined in an Erlang header, `#[rustler(hrl = "include/file.hrl")]` checks
+/// at compile time that the struct has the same fields, in the same order, as the record of the
+/// same tag. The path is relative to the crate's `Cargo.toml`. Tuple structs are only checked
+/// for the number of fields.
+///
+/// ```ignore
+/// #[derive(NifRecord)]
+/// #[tag = "user"]
+/// #[rustler(hrl = "include/user.hrl")]
+/// struct User {
+///    name: String,
+///    age: u32,
+/// }
+/// ```
 #[proc_macro_derive(NifRecord, attributes(tag, rustler))]
 pub fn nif_record(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
-    record::transcoder_decorator(&ast).into()
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    record::transcoder_decorator(&ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 /// Implementation of the `NifUnitEnum` macro that lets the user annotate an enum with a unit type
@@ -203,11 +440,34 @@ pub fn nif_record(input: TokenStream) -> TokenStream {
 /// ```
 ///
 /// Note that the `:invalid_variant` atom is returned if the user tries to encode something
-/// that isn't in the Rust enum.
+/// that isn't in the Rust enum, unless a variant is marked with `#[rustler(other)]`, in which
+/// case any unknown value decodes to that variant.
+///
+/// Atoms are the variant names in `snake_case`. Another convention can be chosen for the whole
+/// enum with `#[rustler(rename_all = "...")]`, one of `"lowercase"`, `"UPPERCASE"`,
+/// `"PascalCase"`, `"camelCase"`, `"snake_case"`, `"SCREAMING_SNAKE_CASE"`, `"kebab-case"` and
+/// `"SCREAMING-KEBAB-CASE"`, and single variants can be renamed with `#[rustler(rename = "...")]`.
+///
+/// With `#[rustler(repr = "integer")]`, variants are encoded as their discriminant instead:
+///
+/// ```ignore
+/// #[derive(NifUnitEnum)]
+/// #[rustler(repr = "integer")]
+/// enum Status {
+///     Ok = 200,
+///     NotFound = 404,
+///     #[rustler(other)]
+///     Unknown = -1,
+/// }
+/// ```
+///
+/// Two variants encoded as the same atom, or as the same integer, are a compile error.
 #[proc_macro_derive(NifUnitEnum, attributes(rustler))]
 pub fn nif_unit_enum(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
-    unit_enum::transcoder_decorator(&ast).into()
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    unit_enum::transcoder_decorator(&ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 /// Implementation of the `NifUntaggedEnum` macro that lets the user annotate an enum that will
@@ -243,6 +503,8 @@ pub fn nif_unit_enum(input: TokenStream) -> TokenStream {
 /// type is lost in the translation because Elixir has no such concept.
 #[proc_macro_derive(NifUntaggedEnum, attributes(rustler))]
 pub fn nif_untagged_enum(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
-    untagged_enum::transcoder_decorator(&ast).into()
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    untagged_enum::transcoder_decorator(&ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
@@ -4,16 +4,50 @@ use syn::{self, spanned::Spanned, Field, Ident};
 
 use super::context::Context;
 
-pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
-    let ctx = Context::from_ast(ast);
+/// The key of a field, and how it is shown in error messages.
+struct Key {
+    name: String,
+    term: TokenStream,
+    display: String,
+}
+
+pub fn transcoder_decorator(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let ctx = Context::from_ast(ast)?;
 
-    let struct_fields = ctx
-        .struct_fields
-        .as_ref()
-        .expect("NifMap can only be used with structs");
+    let struct_fields = ctx.named_struct_fields("NifMap")?;
+    let (struct_fields, extra_field) = Context::split_extra_field(struct_fields)?;
 
-    // Unwrap is ok here, as we already determined that struct_fields is not None
-    let field_atoms = ctx.field_atoms().unwrap();
+    let mut field_atoms = Vec::new();
+    let mut keys = Vec::new();
+
+    for field in struct_fields.iter() {
+        let field_attrs = Context::field_attrs(field)?;
+        if field_attrs.default.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`default` is not supported by NifMap",
+            ));
+        }
+
+        let key = Context::field_key(field, &field_attrs);
+        Context::check_unique_key(field, &key, keys.iter().map(|other: &Key| &*other.name))?;
+
+        if ctx.string_keys() {
+            keys.push(Key {
+                term: quote! { #key.encode(env) },
+                display: format!("{:?}", key),
+                name: key,
+            });
+        } else {
+            let atom_fun = Context::field_to_atom_fun(field);
+            field_atoms.push(quote! { #atom_fun = #key, });
+            keys.push(Key {
+                term: quote! { #atom_fun().encode(env) },
+                display: format!(":{}", key),
+                name: key,
+            });
+        }
+    }
 
     let atom_defs = quote! {
         rustler::atoms! {
@@ -24,13 +58,13 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
     let atoms_module_name = ctx.atoms_module_name(Span::call_site());
 
     let decoder = if ctx.decode() {
-        gen_decoder(&ctx, &struct_fields, &atoms_module_name)
+        gen_decoder(&ctx, &struct_fields, extra_field, &keys, &atoms_module_name)
     } else {
         quote! {}
     };
 
     let encoder = if ctx.encode() {
-        gen_encoder(&ctx, &struct_fields, &atoms_module_name)
+        gen_encoder(&ctx, &struct_fields, extra_field, &keys, &atoms_module_name)
     } else {
         quote! {}
     };
@@ -44,28 +78,33 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
         #encoder
     };
 
-    gen
+    Ok(gen)
 }
 
-fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> TokenStream {
-    let struct_type = &ctx.ident_with_lifetime;
+fn gen_decoder(
+    ctx: &Context,
+    fields: &[&Field],
+    extra_field: Option<&Field>,
+    keys: &[Key],
+    atoms_module_name: &Ident,
+) -> TokenStream {
     let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.decoder_generics();
+    let lifetime = ctx.decode_lifetime();
 
-    let idents: Vec<_> = fields
-        .iter()
-        .map(|field| field.ident.as_ref().unwrap())
-        .collect();
+    let key_terms = keys.iter().map(|key| &key.term);
 
-    let (assignments, field_defs): (Vec<TokenStream>, Vec<TokenStream>) = fields
+    let (assignments, mut field_defs): (Vec<TokenStream>, Vec<TokenStream>) = fields
         .iter()
-        .zip(idents.iter())
+        .zip(keys.iter())
         .enumerate()
-        .map(|(index, (field, ident))| {
-            let atom_fun = Context::field_to_atom_fun(field);
+        .map(|(index, (field, key))| {
+            let ident = field.ident.as_ref().unwrap();
+            let display = &key.display;
             let variable = Context::escape_ident_with_index(&ident.to_string(), index, "map");
 
             let assignment = quote_spanned! { field.span() =>
-            let #variable = try_decode_field(env, term, #atom_fun())?;
+            let #variable = try_decode_field(values[#index], #display)?;
             };
 
             let field_def = quote! {
@@ -75,31 +114,50 @@ fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
         })
         .unzip();
 
+    let lookup = match extra_field {
+        Some(field) => {
+            let ident = field.ident.as_ref().unwrap();
+            field_defs.push(quote_spanned! { field.span() =>
+                #ident: ::rustler::codegen_runtime::decode_extra(rest)?
+            });
+
+            quote! {
+                let (values, rest) = ::rustler::codegen_runtime::map_values_and_rest(term, &keys, true)?;
+            }
+        }
+        None => quote! {
+            let values = ::rustler::codegen_runtime::map_values(term, &keys)?;
+        },
+    };
+
     let gen = quote! {
-        impl<'a> ::rustler::Decoder<'a> for #struct_type {
-            fn decode(term: ::rustler::Term<'a>) -> Result<Self, ::rustler::Error> {
+        impl #impl_generics ::rustler::Decoder<#lifetime> for #struct_name #ty_generics #where_clause {
+            fn decode(term: ::rustler::Term<#lifetime>) -> Result<Self, ::rustler::Error> {
                 use #atoms_module_name::*;
 
+                use ::rustler::Encoder;
+
                 let env = term.get_env();
 
                 fn try_decode_field<'a, T>(
-                    env: rustler::Env<'a>,
-                    term: rustler::Term<'a>,
-                    field: rustler::Atom,
+                    value: Option<rustler::Term<'a>>,
+                    display: &str,
                     ) -> Result<T, rustler::Error>
                     where
                         T: rustler::Decoder<'a>,
                     {
-                        use rustler::Encoder;
-                        match ::rustler::Decoder::decode(term.map_get(field.encode(env))?) {
+                        match ::rustler::Decoder::decode(value.ok_or(::rustler::Error::BadArg)?) {
                             Err(_) => Err(::rustler::Error::RaiseTerm(Box::new(format!(
-                                            "Could not decode field :{:?} on %{{}}",
-                                            field
+                                            "Could not decode field {} on %{{}}",
+                                            display
                             )))),
                             Ok(value) => Ok(value),
                         }
                     };
 
+                let keys = [#(#key_terms),*];
+                #lookup
+
                 #(#assignments);*
 
                 Ok(#struct_name { #(#field_defs),* })
@@ -110,29 +168,48 @@ fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
     gen
 }
 
-fn gen_encoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> TokenStream {
-    let struct_type = &ctx.ident_with_lifetime;
+fn gen_encoder(
+    ctx: &Context,
+    fields: &[&Field],
+    extra_field: Option<&Field>,
+    keys: &[Key],
+    atoms_module_name: &Ident,
+) -> TokenStream {
+    let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.encoder_generics();
 
-    let field_defs: Vec<TokenStream> = fields
-        .iter()
-        .map(|field| {
-            let field_ident = field.ident.as_ref().unwrap();
-            let atom_fun = Context::field_to_atom_fun(field);
+    let key_terms = keys.iter().map(|key| &key.term);
+    let values = fields.iter().map(|field| {
+        let field_ident = field.ident.as_ref().unwrap();
 
-            quote_spanned! { field.span() =>
-                map = map.map_put(#atom_fun().encode(env), self.#field_ident.encode(env)).unwrap();
+        quote_spanned! { field.span() =>
+            self.#field_ident.encode(env)
+        }
+    });
+
+    let build = match extra_field {
+        Some(field) => {
+            let ident = field.ident.as_ref().unwrap();
+            quote! {
+                let extra = self.#ident.encode(env);
+                ::rustler::codegen_runtime::map_with_extra(env, &keys, &values, extra).unwrap()
             }
-        })
-        .collect();
+        }
+        // The keys are distinct, which is checked when deriving.
+        None => quote! {
+            ::rustler::Term::map_from_arrays(env, &keys, &values).unwrap()
+        },
+    };
 
     let gen = quote! {
-        impl<'b> ::rustler::Encoder for #struct_type {
-            fn encode<'a>(&self, env: ::rustler::Env<'a>) -> ::rustler::Term<'a> {
+        impl #impl_generics ::rustler::Encoder for #struct_name #ty_generics #where_clause {
+            fn encode<'__rustler_encode>(&self, env: ::rustler::Env<'__rustler_encode>) -> ::rustler::Term<'__rustler_encode> {
                 use #atoms_module_name::*;
 
-                let mut map = ::rustler::types::map::map_new(env);
-                #(#field_defs)*
-                map
+                let keys = [#(#key_terms),*];
+                let values = [#(#values),*];
+
+                #build
             }
         }
     };
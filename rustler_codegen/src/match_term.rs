@@ -0,0 +1,510 @@
+use proc_macro2::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, Lit, Token};
+
+use super::term::{atom, atom_defs, atom_name, is_colon, literal_expr};
+
+/// The input of `rustler::match_term!`: a term expression and a block of arms.
+pub struct MatchTermInput {
+    term: Expr,
+    arms: Vec<Arm>,
+}
+
+struct Arm {
+    pattern: Vec<TokenTree>,
+    guard: Option<Expr>,
+    body: Expr,
+}
+
+impl Parse for MatchTermInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let term = input.parse()?;
+        input.parse::<Token![,]>()?;
+
+        let content;
+        syn::braced!(content in input);
+        let mut arms = Vec::new();
+        while !content.is_empty() {
+            arms.push(content.parse()?);
+        }
+
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(MatchTermInput { term, arms })
+    }
+}
+
+impl Parse for Arm {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut pattern = Vec::new();
+        while !input.peek(Token![=>]) && !input.peek(Token![if]) {
+            if input.is_empty() {
+                return Err(input.error("Expected `=>`"));
+            }
+            pattern.push(input.parse()?);
+        }
+
+        if pattern.is_empty() {
+            return Err(input.error("Expected a pattern"));
+        }
+
+        let guard = if input.peek(Token![if]) {
+            input.parse::<Token![if]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
+        input.parse::<Token![=>]>()?;
+
+        // Like in `match`, a block body does not need a comma, and is not the start of a larger
+        // expression such as `{ ... } [a] => ...`.
+        let body = if input.peek(syn::token::Brace) {
+            let body = Expr::Block(syn::ExprBlock {
+                attrs: Vec::new(),
+                label: None,
+                block: input.parse()?,
+            });
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
+            body
+        } else {
+            let body = input.parse()?;
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+            body
+        };
+
+        Ok(Arm {
+            pattern,
+            guard,
+            body,
+        })
+    }
+}
+
+enum Pattern {
+    Wildcard,
+    /// A binding to the term itself.
+    Bind(Ident),
+    /// `name: Type`, a binding to the term decoded as `Type`.
+    Typed(Ident, Box<syn::Type>),
+    Atom(String),
+    /// An integer, float or string literal.
+    Literal(Lit, TokenStream),
+    Tuple(Vec<Pattern>),
+    /// The head patterns and the pattern of the tail, which is the empty list if not given.
+    List(Vec<Pattern>, Option<Box<Pattern>>),
+    Map(Vec<(MapKey, Pattern)>),
+}
+
+enum MapKey {
+    Atom(String),
+    Literal(TokenStream),
+}
+
+pub fn expand(input: MatchTermInput) -> syn::Result<TokenStream> {
+    let term_ident = Ident::new("__rustler_match_term", Span::call_site());
+    let mut atoms = Vec::new();
+    let mut branches = Vec::new();
+    let mut fallback = None;
+
+    let arm_count = input.arms.len();
+    for (index, arm) in input.arms.iter().enumerate() {
+        let pattern = parse_pattern(&arm.pattern)?;
+        let body = &arm.body;
+
+        if index + 1 == arm_count && arm.guard.is_none() {
+            match pattern {
+                Pattern::Wildcard => {
+                    fallback = Some(quote! { #body });
+                    break;
+                }
+                Pattern::Bind(ref ident) => {
+                    fallback = Some(quote! {
+                        let #ident = #term_ident;
+                        #body
+                    });
+                    break;
+                }
+                _ => (),
+            }
+        }
+
+        let mut matcher = Matcher {
+            atoms: &mut atoms,
+            statements: Vec::new(),
+            decodes: Vec::new(),
+            bindings: Vec::new(),
+            temporaries: 0,
+        };
+        matcher.gen(&pattern, &quote! { #term_ident })?;
+
+        let Matcher {
+            statements,
+            decodes,
+            bindings,
+            ..
+        } = matcher;
+
+        let guard = arm.guard.as_ref().map(|guard| {
+            quote! {
+                if !(#guard) {
+                    return None;
+                }
+            }
+        });
+
+        branches.push(quote! {
+            if let Some((#(#bindings,)*)) = (|| -> Option<_> {
+                #(#statements)*
+                #(#decodes)*
+                #guard
+                Some((#(#bindings,)*))
+            })() {
+                #body
+            }
+        });
+    }
+
+    let fallback = fallback.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "The last arm of match_term! has to match any term, such as `_ => ...`",
+        )
+    })?;
+
+    let atom_defs = atom_defs(&atoms);
+    let term = &input.term;
+
+    Ok(quote! {
+        {
+            #atom_defs
+
+            let #term_ident: ::rustler::Term = #term;
+            #(#branches else)* {
+                #fallback
+            }
+        }
+    })
+}
+
+/// Generates the statements of the closure checking a pattern, which returns `None` as soon as a
+/// part of the term does not match.
+struct Matcher<'a> {
+    atoms: &'a mut Vec<String>,
+    /// The structural checks: shapes, atoms and literals.
+    statements: Vec<TokenStream>,
+    /// The typed bindings, decoded only once the whole structure matched, since decoding can be
+    /// much more expensive than the checks.
+    decodes: Vec<TokenStream>,
+    bindings: Vec<Ident>,
+    temporaries: usize,
+}
+
+impl<'a> Matcher<'a> {
+    fn temporary(&mut self) -> Ident {
+        self.temporaries += 1;
+        Ident::new(
+            &format!("__rustler_match_term_{}", self.temporaries),
+            Span::call_site(),
+        )
+    }
+
+    fn bind(&mut self, ident: &Ident) -> syn::Result<()> {
+        if self.bindings.contains(ident) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!(
+                    "Identifier `{}` is bound more than once in this pattern",
+                    ident
+                ),
+            ));
+        }
+        self.bindings.push(ident.clone());
+        Ok(())
+    }
+
+    fn gen(&mut self, pattern: &Pattern, term: &TokenStream) -> syn::Result<()> {
+        match pattern {
+            Pattern::Wildcard => (),
+            Pattern::Bind(ident) => {
+                self.bind(ident)?;
+                self.statements.push(quote! { let #ident = #term; });
+            }
+            Pattern::Typed(ident, ty) => {
+                self.bind(ident)?;
+                self.decodes.push(quote! {
+                    let #ident: #ty = #term.decode().ok()?;
+                });
+            }
+            Pattern::Atom(name) => {
+                let atom_fun = atom(self.atoms, name);
+                self.statements.push(quote! {
+                    if #atom_fun() != #term {
+                        return None;
+                    }
+                });
+            }
+            Pattern::Literal(Lit::Str(string), _) => {
+                self.statements.push(quote! {
+                    if #term.decode::<&[u8]>().ok() != Some(#string.as_bytes()) {
+                        return None;
+                    }
+                });
+            }
+            Pattern::Literal(Lit::Float(_), value) => {
+                self.statements.push(quote! {
+                    if #term.decode::<f64>().ok() != Some(#value) {
+                        return None;
+                    }
+                });
+            }
+            Pattern::Literal(_, value) => {
+                self.statements.push(quote! {
+                    if #term.decode::<i64>().ok() != Some(#value) {
+                        return None;
+                    }
+                });
+            }
+            Pattern::Tuple(items) => {
+                let elements: Vec<Ident> = items.iter().map(|_| self.temporary()).collect();
+                let arity = items.len();
+                self.statements.push(quote! {
+                    let [#(#elements),*] =
+                        ::rustler::codegen_runtime::match_tuple::<#arity>(#term)?;
+                });
+                for (item, element) in items.iter().zip(elements.iter()) {
+                    self.gen(item, &quote! { #element })?;
+                }
+            }
+            Pattern::List(items, tail) => {
+                let mut rest = term.clone();
+                for item in items {
+                    let (head, next) = (self.temporary(), self.temporary());
+                    self.statements.push(quote! {
+                        let (#head, #next) = #rest.list_get_cell().ok()?;
+                    });
+                    self.gen(item, &quote! { #head })?;
+                    rest = quote! { #next };
+                }
+
+                match tail {
+                    Some(tail) => self.gen(tail, &rest)?,
+                    None => self.statements.push(quote! {
+                        if !#rest.is_empty_list() {
+                            return None;
+                        }
+                    }),
+                }
+            }
+            Pattern::Map(entries) => {
+                self.statements.push(quote! {
+                    if !#term.is_map() {
+                        return None;
+                    }
+                });
+                for (key, value) in entries {
+                    let key = match key {
+                        MapKey::Atom(name) => {
+                            let atom_fun = atom(self.atoms, name);
+                            quote! { #atom_fun().to_term(#term.get_env()) }
+                        }
+                        MapKey::Literal(literal) => {
+                            quote! { ::rustler::Encoder::encode(&(#literal), #term.get_env()) }
+                        }
+                    };
+                    let element = self.temporary();
+                    self.statements.push(quote! {
+                        let #element = #term.map_get(#key).ok()?;
+                    });
+                    self.gen(value, &quote! { #element })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_pattern(tokens: &[TokenTree]) -> syn::Result<Pattern> {
+    match tokens {
+        [TokenTree::Ident(ident)] if ident == "_" => Ok(Pattern::Wildcard),
+        [TokenTree::Ident(ident)] if ident == "true" || ident == "false" || ident == "nil" => {
+            Ok(Pattern::Atom(ident.to_string()))
+        }
+        [TokenTree::Ident(ident)] => Ok(Pattern::Bind(ident.clone())),
+        [TokenTree::Ident(ident), TokenTree::Punct(colon), ty @ ..]
+            if colon.as_char() == ':' && !ty.is_empty() && !is_colon(&ty[0]) =>
+        {
+            let ty: TokenStream = ty.iter().cloned().collect();
+            Ok(Pattern::Typed(ident.clone(), Box::new(syn::parse2(ty)?)))
+        }
+        // `:atom` or `:"quoted atom"`
+        [colon, name] if is_colon(colon) => match atom_name(name) {
+            Some(name) => Ok(Pattern::Atom(name)),
+            None => Err(syn::Error::new_spanned(name, "Expected an atom name")),
+        },
+        [TokenTree::Literal(literal)] => parse_literal(literal, false),
+        [TokenTree::Punct(minus), TokenTree::Literal(literal)] if minus.as_char() == '-' => {
+            parse_literal(literal, true)
+        }
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Brace => {
+            let items = split_patterns(group.stream())?
+                .iter()
+                .map(|item| parse_pattern(item))
+                .collect::<syn::Result<_>>()?;
+            Ok(Pattern::Tuple(items))
+        }
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Bracket => parse_list(group),
+        [TokenTree::Punct(percent), TokenTree::Group(group)]
+            if percent.as_char() == '%' && group.delimiter() == Delimiter::Brace =>
+        {
+            parse_map(group.stream())
+        }
+        _ => {
+            let tokens: TokenStream = tokens.iter().cloned().collect();
+            Err(syn::Error::new_spanned(tokens, "Unsupported pattern"))
+        }
+    }
+}
+
+fn parse_literal(literal: &proc_macro2::Literal, negative: bool) -> syn::Result<Pattern> {
+    let lit = Lit::new(literal.clone());
+    match lit {
+        Lit::Str(_) if negative => Err(syn::Error::new_spanned(literal, "Expected a number")),
+        Lit::Str(_) | Lit::Int(_) | Lit::Float(_) => {
+            Ok(Pattern::Literal(lit, literal_expr(literal, negative)))
+        }
+        _ => Err(syn::Error::new_spanned(
+            literal,
+            "Only integer, float and string literals are supported in patterns",
+        )),
+    }
+}
+
+/// Parses the contents of `[a, b | tail]`.
+fn parse_list(group: &Group) -> syn::Result<Pattern> {
+    let mut items = split_patterns(group.stream())?;
+    let mut tail = None;
+
+    if let Some(last) = items.last_mut() {
+        let bar = last
+            .iter()
+            .position(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == '|'));
+        if let Some(index) = bar {
+            let tail_tokens = last.split_off(index + 1);
+            last.pop();
+            if last.is_empty() || tail_tokens.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    group,
+                    "Expected `[head | tail]` in list pattern",
+                ));
+            }
+            tail = Some(Box::new(parse_pattern(&tail_tokens)?));
+        }
+    }
+
+    let items = items
+        .iter()
+        .map(|item| parse_pattern(item))
+        .collect::<syn::Result<_>>()?;
+    Ok(Pattern::List(items, tail))
+}
+
+/// Parses the contents of `%{key: pattern, "key" => pattern}`.
+fn parse_map(tokens: TokenStream) -> syn::Result<Pattern> {
+    let mut entries = Vec::new();
+
+    for entry in split_patterns(tokens)? {
+        if let [key, TokenTree::Punct(colon), value @ ..] = entry.as_slice() {
+            if colon.as_char() == ':' && !value.is_empty() && !is_colon(&value[0]) {
+                if let Some(key) = atom_name(key) {
+                    entries.push((MapKey::Atom(key), parse_pattern(value)?));
+                    continue;
+                }
+            }
+        }
+
+        let arrow = entry.windows(2).position(|pair| match pair {
+            [TokenTree::Punct(eq), TokenTree::Punct(gt)] => {
+                eq.as_char() == '=' && gt.as_char() == '>'
+            }
+            _ => false,
+        });
+
+        let (key, value) = match arrow {
+            Some(index) if index > 0 && index + 2 < entry.len() => {
+                (&entry[..index], &entry[index + 2..])
+            }
+            _ => {
+                let tokens: TokenStream = entry.iter().cloned().collect();
+                return Err(syn::Error::new_spanned(
+                    tokens,
+                    "Expected `key => pattern` or `key: pattern` in map pattern",
+                ));
+            }
+        };
+
+        let key = match parse_pattern(key)? {
+            Pattern::Atom(name) => MapKey::Atom(name),
+            Pattern::Literal(_, literal) => MapKey::Literal(literal),
+            _ => {
+                let tokens: TokenStream = key.iter().cloned().collect();
+                return Err(syn::Error::new_spanned(
+                    tokens,
+                    "Map keys in patterns have to be atoms or literals",
+                ));
+            }
+        };
+        entries.push((key, parse_pattern(value)?));
+    }
+
+    Ok(Pattern::Map(entries))
+}
+
+/// Splits the elements of a pattern at commas, ignoring the ones between the angle brackets of
+/// types such as `HashMap<String, i64>`.
+fn split_patterns(tokens: TokenStream) -> syn::Result<Vec<Vec<TokenTree>>> {
+    let mut chunks = vec![Vec::new()];
+    let mut depth = 0;
+    let mut previous: Option<char> = None;
+
+    for token in tokens {
+        if let TokenTree::Punct(ref punct) = token {
+            match punct.as_char() {
+                ',' if depth == 0 => {
+                    if chunks.last().unwrap().is_empty() {
+                        return Err(syn::Error::new_spanned(
+                            punct,
+                            "Expected a pattern before `,`",
+                        ));
+                    }
+                    chunks.push(Vec::new());
+                    previous = None;
+                    continue;
+                }
+                '<' => depth += 1,
+                // Not the end of `=>` or `->`.
+                '>' if previous != Some('=') && previous != Some('-') => depth -= 1,
+                _ => (),
+            }
+            previous = Some(punct.as_char());
+        } else {
+            previous = None;
+        }
+
+        chunks.last_mut().unwrap().push(token);
+    }
+
+    // A trailing comma is allowed.
+    if chunks.last().unwrap().is_empty() {
+        chunks.pop();
+    }
+
+    Ok(chunks)
+}
@@ -3,23 +3,41 @@ use quote::{quote, ToTokens};
 use syn::punctuated::Punctuated;
 use syn::token::Comma;
 
-pub fn transcoder_decorator(args: syn::AttributeArgs, fun: syn::ItemFn) -> TokenStream {
+pub fn transcoder_decorator(
+    args: syn::AttributeArgs,
+    mut fun: syn::ItemFn,
+) -> syn::Result<TokenStream> {
+    validate_attributes(&args)?;
+
+    let optional = optional_arity(&args, &mut fun.sig.inputs)?;
+
     let sig = &fun.sig;
     let name = &sig.ident;
     let inputs = &sig.inputs;
 
-    validate_attributes(args.clone());
-
-    let flags = schedule_flag(args.to_owned());
+    let flags = schedule_flag(&args)?;
     let function = fun.to_owned().into_token_stream();
-    let arity = arity(inputs.clone());
-    let decoded_terms = extract_inputs(inputs.clone());
-    let argument_names = create_function_params(inputs.clone());
-    let erl_func_name = extract_attr_value(args, "name")
-        .map(|ref n| syn::Ident::new(n, Span::call_site()))
-        .unwrap_or_else(|| name.clone());
-
-    quote! {
+    let arity = arity(inputs)?;
+    let arities = (arity - optional..=arity).rev();
+    let decoded_terms = extract_inputs(inputs, (arity - optional) as usize);
+    let argument_names = create_function_params(inputs);
+    let schedule = extract_attr_value(&args, "schedule")
+        .map(|lit| lit.value())
+        .unwrap_or_else(|| "Normal".into());
+    let erl_func_name = match extract_attr_value(&args, "name") {
+        Some(lit) => lit.parse::<syn::Ident>().map_err(|_| {
+            syn::Error::new(
+                lit.span(),
+                format!("`{}` is not a valid NIF name", lit.value()),
+            )
+        })?,
+        None => name.clone(),
+    };
+    let erl_func_name_str = erl_func_name.to_string();
+    let argument_metadata = argument_metadata(inputs);
+    let returns = return_spec(&sig.output);
+
+    Ok(quote! {
         #[allow(non_camel_case_types)]
         pub struct #name;
 
@@ -67,164 +85,528 @@ pub fn transcoder_decorator(args: syn::AttributeArgs, fun: syn::ItemFn) -> Token
                 function: Self::RAW_FUNC,
                 name: Self::NAME
             };
+            const FUNCS: &'static [rustler::codegen_runtime::DEF_NIF_FUNC] = &[
+                #(
+                    rustler::codegen_runtime::DEF_NIF_FUNC {
+                        arity: #arities,
+                        flags: Self::FLAGS,
+                        function: Self::RAW_FUNC,
+                        name: Self::NAME
+                    },
+                )*
+            ];
+            const METADATA: rustler::stubs::NifMetadata = rustler::stubs::NifMetadata {
+                name: #erl_func_name_str,
+                arity: Self::ARITY,
+                args: &[#argument_metadata],
+                returns: #returns,
+                schedule: #schedule,
+                optional: #optional,
+            };
         }
-    }
-}
 
-fn schedule_flag(args: syn::AttributeArgs) -> TokenStream {
-    let mut tokens = TokenStream::new();
+        #[cfg(not(test))]
+        rustler::codegen_runtime::inventory::submit!(
+            rustler::codegen_runtime::NifRegistration {
+                module_path: module_path!(),
+                funcs: <#name as rustler::Nif>::FUNCS,
+                metadata: <#name as rustler::Nif>::METADATA,
+            }
+        );
+
+        #[cfg(test)]
+        rustler::codegen_runtime::inventory::submit!(
+            rustler::codegen_runtime::NifRegistration {
+                module_path: module_path!(),
+                funcs: &[],
+                metadata: <#name as rustler::Nif>::METADATA,
+            }
+        );
+    })
+}
 
+fn schedule_flag(args: &[syn::NestedMeta]) -> syn::Result<TokenStream> {
     let valid = ["DirtyCpu", "DirtyIo", "Normal"];
 
     let flag = match extract_attr_value(args, "schedule") {
-        Some(value) => {
-            if valid.contains(&value.as_str()) {
-                syn::Ident::new(value.as_str(), Span::call_site())
-            } else {
-                panic!("Invalid schedule option `{}`", value);
-            }
+        Some(lit) if valid.contains(&lit.value().as_str()) => {
+            syn::Ident::new(&lit.value(), Span::call_site())
+        }
+        Some(lit) => {
+            return Err(syn::Error::new(
+                lit.span(),
+                format!(
+                    "Invalid schedule option `{}`. Expected one of {:?}",
+                    lit.value(),
+                    valid
+                ),
+            ))
         }
         None => syn::Ident::new("Normal", Span::call_site()),
     };
 
-    tokens.extend(quote! { rustler::SchedulerFlags::#flag });
-    tokens
+    Ok(quote! { rustler::SchedulerFlags::#flag })
 }
 
-fn extract_attr_value(args: syn::AttributeArgs, name: &str) -> Option<String> {
+fn extract_attr_value<'a>(args: &'a [syn::NestedMeta], name: &str) -> Option<&'a syn::LitStr> {
     use syn::{Lit, Meta, MetaNameValue, NestedMeta};
 
-    for arg in args.iter() {
-        if let NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. })) = arg {
-            if path.is_ident(name) {
-                if let Lit::Str(lit) = lit {
-                    return Some(lit.value());
-                }
-            }
-        }
-    }
-
-    None
+    args.iter().find_map(|arg| match arg {
+        NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+            path,
+            lit: Lit::Str(lit),
+            ..
+        })) if path.is_ident(name) => Some(lit),
+        _ => None,
+    })
 }
 
-fn extract_inputs(inputs: Punctuated<syn::FnArg, Comma>) -> TokenStream {
+/// Decodes the NIF arguments into variables named by `argument_ident`. The variables are passed
+/// to the function as a whole, so any type implementing `Decoder` and any pattern can be used.
+fn extract_inputs(inputs: &Punctuated<syn::FnArg, Comma>, required: usize) -> TokenStream {
     let mut tokens = TokenStream::new();
     let mut idx: usize = 0;
 
     for item in inputs.iter() {
         if let syn::FnArg::Typed(ref typed) = item {
-            let name = &typed.pat;
+            let typ = &typed.ty;
 
-            match &*typed.ty {
-                syn::Type::Reference(typ) => {
-                    let decoder = quote! {
-                        let #name: #typ = match args[#idx].decode() {
+            if is_env(typ) || is_cancellation_token(typ) {
+                continue;
+            }
+
+            let name = argument_ident(idx);
+
+            let decoder = if is_term(typ) {
+                quote! {
+                    let #name: #typ = args[#idx];
+                }
+            } else if idx >= required && is_option(typ) {
+                quote! {
+                    let #name: #typ = match args.get(#idx) {
+                        Some(term) => match term.decode() {
                             Ok(value) => value,
                             Err(err) => return Err(err)
-                        };
+                        },
+                        None => None
                     };
-
-                    tokens.extend(decoder);
-                }
-                syn::Type::Path(syn::TypePath { path, .. }) => {
-                    let typ = &typed.ty;
-                    let ident = path.segments.last().unwrap().ident.to_string();
-
-                    match ident.as_ref() {
-                        "Env" => {
-                            continue;
-                        }
-                        "Term" => {
-                            let arg = quote! {
-                                let #name: #typ = args[#idx];
-                            };
-
-                            tokens.extend(arg);
-                        }
-                        _ => {
-                            let decoder = quote! {
-                                let #name: #typ = match args[#idx].decode() {
-                                    Ok(value) => value,
-                                    Err(err) => return Err(err)
-                                };
-                            };
-
-                            tokens.extend(decoder);
-                        }
-                    }
                 }
-                other => {
-                    panic!("unsupported input given: {:?}", other);
+            } else {
+                quote! {
+                    let #name: #typ = match args[#idx].decode() {
+                        Ok(value) => value,
+                        Err(err) => return Err(err)
+                    };
                 }
-            }
-        } else {
-            panic!("unsupported input given: {:?}", stringify!(&item));
-        };
-        idx += 1;
+            };
+
+            tokens.extend(decoder);
+            idx += 1;
+        }
     }
 
     tokens
 }
 
-fn create_function_params(inputs: Punctuated<syn::FnArg, Comma>) -> TokenStream {
+fn create_function_params(inputs: &Punctuated<syn::FnArg, Comma>) -> TokenStream {
     let mut tokens = TokenStream::new();
+    let mut idx: usize = 0;
 
     for item in inputs.iter() {
-        let name = if let syn::FnArg::Typed(ref typed) = item {
-            &typed.pat
-        } else {
-            panic!("unsupported input given: {:?}", stringify!(&item));
-        };
+        if let syn::FnArg::Typed(ref typed) = item {
+            if is_env(&typed.ty) {
+                tokens.extend(quote!(env,));
+            } else if is_cancellation_token(&typed.ty) {
+                tokens.extend(quote!(rustler::thread::CancellationToken::new(env),));
+            } else {
+                let name = argument_ident(idx);
+                tokens.extend(quote!(#name,));
+                idx += 1;
+            }
+        }
+    }
 
-        tokens.extend(quote!(#name,));
+    tokens
+}
+
+/// The variable holding the decoded argument at `idx`. It is hygienic, so it cannot clash with
+/// the names used in the function signature.
+fn argument_ident(idx: usize) -> syn::Ident {
+    syn::Ident::new(&format!("arg{}", idx), Span::mixed_site())
+}
+
+fn argument_metadata(inputs: &Punctuated<syn::FnArg, Comma>) -> TokenStream {
+    let mut tokens = TokenStream::new();
+
+    let decoded = inputs.iter().filter_map(|item| match item {
+        syn::FnArg::Typed(typed) if !is_env(&typed.ty) && !is_cancellation_token(&typed.ty) => {
+            Some(typed)
+        }
+        _ => None,
+    });
+
+    for (idx, typed) in decoded.enumerate() {
+        // Arguments without a usable name (`_`, `__`, patterns) are named by their position.
+        let name = match &*typed.pat {
+            syn::Pat::Ident(pat) => pat.ident.to_string(),
+            _ => String::new(),
+        };
+        let name = match name.trim_start_matches("r#").trim_start_matches('_') {
+            "" => format!("arg{}", idx),
+            name => name.to_string(),
+        };
+        let ty = &typed.ty;
+        let rust_type = type_name(ty);
+        let spec = type_spec(ty, true);
+
+        tokens.extend(quote! {
+            rustler::stubs::NifArgument {
+                name: #name,
+                rust_type: #rust_type,
+                spec: #spec,
+            },
+        });
     }
 
     tokens
 }
 
-fn arity(inputs: Punctuated<syn::FnArg, Comma>) -> u32 {
-    let mut arity: u32 = 0;
+/// Renders a type like it is usually written, e.g. `Vec<(u8, String)>` instead of the
+/// `Vec < (u8 , String) >` produced by the token stream.
+fn type_name(ty: &syn::Type) -> String {
+    let spaced = quote!(#ty).to_string();
+    let chars: Vec<char> = spaced.chars().collect();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+
+    let mut name = String::new();
+    for (idx, &c) in chars.iter().enumerate() {
+        if c == ' ' {
+            let prev = idx.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(idx + 1);
+            match (prev, next) {
+                (Some(prev), Some(&next)) if is_word(prev) && is_word(next) => name.push(' '),
+                _ => (),
+            }
+        } else {
+            name.push(c);
+            if c == ',' {
+                name.push(' ');
+            }
+        }
+    }
 
-    for (i, item) in inputs.iter().enumerate() {
-        if let syn::FnArg::Typed(ref typed) = item {
-            if let syn::Type::Path(syn::TypePath { path, .. }) = &*typed.ty {
-                let ident = path.segments.last().unwrap().ident.to_string();
+    name
+}
 
-                if i == 0 && ident == "Env" {
-                    continue;
-                }
+fn is_env(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            matches!(path.segments.last(), Some(segment) if segment.ident == "Env")
+        }
+        _ => false,
+    }
+}
 
-                if ident == "Env" {
-                    panic!("Env must be the first argument in NIF functions");
+/// A `CancellationToken` argument is created from the environment instead of being decoded, so
+/// that dirty NIFs can tell when their caller has exited. Imports can't be resolved here, so the
+/// type has to be written `thread::CancellationToken` or `rustler::thread::CancellationToken` to
+/// tell it apart from other types with the same name.
+fn is_cancellation_token(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => {
+            let idents: Vec<&syn::Ident> =
+                path.segments.iter().map(|segment| &segment.ident).collect();
+            match idents.as_slice() {
+                [thread, token] => *thread == "thread" && *token == "CancellationToken",
+                [rustler, thread, token] => {
+                    *rustler == "rustler" && *thread == "thread" && *token == "CancellationToken"
                 }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Whether `ty` is `CancellationToken` without a path, or a reference to it. It is rejected rather
+/// than decoded, since it most likely names the imported `rustler::thread::CancellationToken`.
+fn is_bare_cancellation_token(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => path.is_ident("CancellationToken"),
+        syn::Type::Reference(reference) => is_bare_cancellation_token(&reference.elem),
+        _ => false,
+    }
+}
+
+/// The typespec of the term a NIF returns. `NifResult<T>` and `Result<T, Error>` raise or return
+/// an error term on failure, so only `T` is part of the spec.
+fn return_spec(output: &syn::ReturnType) -> TokenStream {
+    let ty = match output {
+        syn::ReturnType::Default => return quote!(rustler::stubs::TypeSpec::Tuple(&[])),
+        syn::ReturnType::Type(_, ty) => ty,
+    };
+
+    if let syn::Type::Path(syn::TypePath { path, .. }) = &**ty {
+        if let Some(segment) = path.segments.last() {
+            let args = generic_args(segment);
+            match (segment.ident.to_string().as_ref(), args.as_slice()) {
+                ("NifResult", [ok]) => return type_spec(ok, false),
+                ("Result", [ok, error]) if is_rustler_error(error) => return type_spec(ok, false),
+                _ => (),
+            }
+        }
+    }
+
+    type_spec(ty, false)
+}
+
+fn is_rustler_error(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            matches!(path.segments.last(), Some(segment) if segment.ident == "Error")
+        }
+        _ => false,
+    }
+}
+
+/// Maps a Rust type to the typespec of the terms it decodes from and encodes to, based on how it
+/// is spelled. Types that are not known to Rustler are specified as `term()`. `argument` is set
+/// for the types of arguments, which are only decoded.
+fn type_spec(ty: &syn::Type, argument: bool) -> TokenStream {
+    match ty {
+        syn::Type::Reference(reference) => type_spec(&reference.elem, argument),
+        syn::Type::Paren(paren) => type_spec(&paren.elem, argument),
+        syn::Type::Group(group) => type_spec(&group.elem, argument),
+        // `&[u8]` is decoded from a binary, but encoded as a list like any other slice.
+        syn::Type::Slice(slice) if argument && quote!(#slice).to_string() == "[u8]" => {
+            quote!(rustler::stubs::TypeSpec::Binary)
+        }
+        syn::Type::Slice(slice) => {
+            let elem = type_spec(&slice.elem, argument);
+            quote!(rustler::stubs::TypeSpec::List(&#elem))
+        }
+        syn::Type::Array(array) => {
+            let elem = type_spec(&array.elem, argument);
+            quote!(rustler::stubs::TypeSpec::List(&#elem))
+        }
+        syn::Type::Tuple(tuple) => {
+            let elems = tuple.elems.iter().map(|elem| type_spec(elem, argument));
+            quote!(rustler::stubs::TypeSpec::Tuple(&[#(#elems),*]))
+        }
+        syn::Type::Path(syn::TypePath { qself: None, path }) => {
+            let segment = match path.segments.last() {
+                Some(segment) => segment,
+                None => return quote!(rustler::stubs::TypeSpec::Any),
             };
-        } else {
-            panic!("unsupported input given: {:?}", stringify!(&item));
-        };
-        arity += 1;
+            let args = generic_args(segment);
+
+            match (segment.ident.to_string().as_ref(), args.as_slice()) {
+                ("i8", [])
+                | ("i16", [])
+                | ("i32", [])
+                | ("i64", [])
+                | ("i128", [])
+                | ("isize", []) => quote!(rustler::stubs::TypeSpec::Integer),
+                ("u8", [])
+                | ("u16", [])
+                | ("u32", [])
+                | ("u64", [])
+                | ("u128", [])
+                | ("usize", []) => quote!(rustler::stubs::TypeSpec::NonNegInteger),
+                ("f32", []) | ("f64", []) => quote!(rustler::stubs::TypeSpec::Float),
+                ("bool", []) => quote!(rustler::stubs::TypeSpec::Boolean),
+                ("Atom", []) => quote!(rustler::stubs::TypeSpec::Atom),
+                ("String", []) | ("str", []) => quote!(rustler::stubs::TypeSpec::String),
+                ("Binary", _) | ("OwnedBinary", []) => quote!(rustler::stubs::TypeSpec::Binary),
+                ("LocalPid", []) | ("Pid", []) => quote!(rustler::stubs::TypeSpec::Pid),
+                ("ResourceArc", _) => quote!(rustler::stubs::TypeSpec::Reference),
+                ("ListIterator", _) => {
+                    quote!(rustler::stubs::TypeSpec::List(
+                        &rustler::stubs::TypeSpec::Any
+                    ))
+                }
+                ("MapIterator", _) => quote!(rustler::stubs::TypeSpec::Map(
+                    &rustler::stubs::TypeSpec::Any,
+                    &rustler::stubs::TypeSpec::Any
+                )),
+                ("Vec", [elem]) => {
+                    let elem = type_spec(elem, argument);
+                    quote!(rustler::stubs::TypeSpec::List(&#elem))
+                }
+                ("Option", [inner]) => {
+                    let inner = type_spec(inner, argument);
+                    quote!(rustler::stubs::TypeSpec::Option(&#inner))
+                }
+                ("Result", [ok, error]) => {
+                    let ok = type_spec(ok, argument);
+                    let error = type_spec(error, argument);
+                    quote!(rustler::stubs::TypeSpec::Result(&#ok, &#error))
+                }
+                ("HashMap", [key, value]) => {
+                    let key = type_spec(key, argument);
+                    let value = type_spec(value, argument);
+                    quote!(rustler::stubs::TypeSpec::Map(&#key, &#value))
+                }
+                _ => quote!(rustler::stubs::TypeSpec::Any),
+            }
+        }
+        _ => quote!(rustler::stubs::TypeSpec::Any),
+    }
+}
+
+/// The type arguments of a path segment, e.g. `[T]` for `Vec<T>`. Lifetimes are skipped.
+fn generic_args(segment: &syn::PathSegment) -> Vec<&syn::Type> {
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn arity(inputs: &Punctuated<syn::FnArg, Comma>) -> syn::Result<u32> {
+    let mut arity: u32 = 0;
+
+    for (i, item) in inputs.iter().enumerate() {
+        match item {
+            syn::FnArg::Typed(typed) if is_env(&typed.ty) => {
+                if i != 0 {
+                    return Err(syn::Error::new_spanned(
+                        typed,
+                        "Env must be the first argument in NIF functions",
+                    ));
+                }
+            }
+            syn::FnArg::Typed(typed) if is_cancellation_token(&typed.ty) => (),
+            syn::FnArg::Typed(typed) => match &*typed.ty {
+                syn::Type::Reference(reference) if is_cancellation_token(&reference.elem) => {
+                    return Err(syn::Error::new_spanned(
+                        &typed.ty,
+                        "NIFs take a `CancellationToken` by value",
+                    ));
+                }
+                ty if is_bare_cancellation_token(ty) => {
+                    return Err(syn::Error::new_spanned(
+                        &typed.ty,
+                        "Write `thread::CancellationToken` or `rustler::thread::CancellationToken`, a bare `CancellationToken` would be decoded as an argument",
+                    ));
+                }
+                _ => arity += 1,
+            },
+            syn::FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "NIF functions cannot take `self`",
+                ));
+            }
+        }
+    }
+
+    Ok(arity)
+}
+
+/// Returns the number of trailing arguments that can be left out, and removes the `#[default]`
+/// attributes marking them.
+///
+/// With `#[nif(defaults)]`, all trailing `Option` arguments are optional. Otherwise, only the
+/// arguments marked with `#[default]` are, which have to be trailing `Option` arguments.
+fn optional_arity(
+    args: &[syn::NestedMeta],
+    inputs: &mut Punctuated<syn::FnArg, Comma>,
+) -> syn::Result<u32> {
+    use syn::{Meta, NestedMeta};
+
+    let defaults = args.iter().any(|arg| match arg {
+        NestedMeta::Meta(Meta::Path(path)) => path.is_ident("defaults"),
+        _ => false,
+    });
+
+    let mut optional: u32 = 0;
+    let mut any_marked = false;
+
+    for item in inputs.iter_mut() {
+        if let syn::FnArg::Typed(ref mut typed) = item {
+            if is_env(&typed.ty) || is_cancellation_token(&typed.ty) {
+                continue;
+            }
+
+            let len = typed.attrs.len();
+            typed.attrs.retain(|attr| !attr.path.is_ident("default"));
+            let marked = typed.attrs.len() != len;
+
+            if marked && !is_option(&typed.ty) {
+                return Err(syn::Error::new_spanned(
+                    &typed.ty,
+                    "Only `Option` arguments can be marked with `#[default]`",
+                ));
+            }
+
+            if marked || (defaults && is_option(&typed.ty)) {
+                optional += 1;
+            } else if any_marked {
+                return Err(syn::Error::new_spanned(
+                    &*typed,
+                    "Arguments marked with `#[default]` must come after all other arguments",
+                ));
+            } else {
+                optional = 0;
+            }
+
+            any_marked |= marked;
+        }
+    }
+
+    Ok(optional)
+}
+
+fn is_term(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            matches!(path.segments.last(), Some(segment) if segment.ident == "Term")
+        }
+        _ => false,
     }
+}
 
-    arity
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            matches!(path.segments.last(), Some(segment) if segment.ident == "Option")
+        }
+        _ => false,
+    }
 }
 
-fn validate_attributes(args: syn::AttributeArgs) {
-    use syn::{Meta, MetaNameValue, NestedMeta};
+fn validate_attributes(args: &[syn::NestedMeta]) -> syn::Result<()> {
+    use syn::{Lit, Meta, MetaNameValue, NestedMeta};
     let known_attrs = ["schedule", "name"];
 
     for arg in args.iter() {
-        if let NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, .. })) = arg {
-            if known_attrs.iter().all(|known| !path.is_ident(known)) {
-                match path.get_ident() {
-                    Some(path) => panic!(
-                        "Unknown attribute '{}'. Allowed attributes: {:?}",
-                        path, known_attrs
-                    ),
-                    None => panic!(
-                        "Cannot parse attribute. Allowed attributes: {:?}",
-                        known_attrs
-                    ),
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. }))
+                if known_attrs.iter().any(|known| path.is_ident(known)) =>
+            {
+                if !matches!(lit, Lit::Str(_)) {
+                    return Err(syn::Error::new_spanned(lit, "Expected a string literal"));
                 }
             }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("defaults") => (),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    format!(
+                        "Unknown attribute. Allowed attributes: {:?}",
+                        ["schedule", "name", "defaults"]
+                    ),
+                ))
+            }
         }
     }
+
+    Ok(())
 }
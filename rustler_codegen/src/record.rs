@@ -1,19 +1,19 @@
+use std::{env, fs, path::Path};
+
 use proc_macro2::{Span, TokenStream};
 
 use syn::{self, spanned::Spanned, Field, Ident, Index};
 
 use super::context::Context;
+use super::hrl;
 use super::RustlerAttr;
 
-pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
-    let ctx = Context::from_ast(ast);
-
-    let record_tag = get_tag(&ctx);
+pub fn transcoder_decorator(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let ctx = Context::from_ast(ast)?;
 
-    let struct_fields = ctx
-        .struct_fields
-        .as_ref()
-        .expect("NifRecord can only be used with structs");
+    let struct_fields = ctx.struct_fields("NifRecord")?;
+    let record_tag = get_tag(&ctx)?;
+    let hrl_check = check_hrl(&ctx, struct_fields, &record_tag)?;
 
     let atom_defs = quote! {
         rustler::atoms! {
@@ -24,13 +24,13 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
     let atoms_module_name = ctx.atoms_module_name(Span::call_site());
 
     let decoder = if ctx.decode() {
-        gen_decoder(&ctx, &struct_fields, &atoms_module_name)
+        gen_decoder(&ctx, struct_fields, &atoms_module_name)
     } else {
         quote! {}
     };
 
     let encoder = if ctx.encode() {
-        gen_encoder(&ctx, &struct_fields, &atoms_module_name)
+        gen_encoder(&ctx, struct_fields, &atoms_module_name)
     } else {
         quote! {}
     };
@@ -40,16 +40,18 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
             #atom_defs
         }
 
+        #hrl_check
         #decoder
         #encoder
     };
 
-    gen
+    Ok(gen)
 }
 
 fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> TokenStream {
-    let struct_type = &ctx.ident_with_lifetime;
     let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.decoder_generics();
+    let lifetime = ctx.decode_lifetime();
 
     // Make a decoder for each of the fields in the struct.
     let (assignments, field_defs): (Vec<TokenStream>, Vec<TokenStream>) = fields
@@ -97,8 +99,8 @@ fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
         }
     };
     let gen = quote! {
-        impl<'a> ::rustler::Decoder<'a> for #struct_type {
-            fn decode(term: ::rustler::Term<'a>) -> Result<Self, ::rustler::Error> {
+        impl #impl_generics ::rustler::Decoder<#lifetime> for #struct_name #ty_generics #where_clause {
+            fn decode(term: ::rustler::Term<#lifetime>) -> Result<Self, ::rustler::Error> {
                 use #atoms_module_name::*;
 
                 let terms = match ::rustler::types::tuple::get_tuple(term) {
@@ -137,7 +139,8 @@ fn gen_decoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
 }
 
 fn gen_encoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> TokenStream {
-    let struct_type = &ctx.ident_with_lifetime;
+    let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.encoder_generics();
 
     // Make a field encoder expression for each of the items in the struct.
     let field_encoders: Vec<TokenStream> = fields
@@ -164,8 +167,8 @@ fn gen_encoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
 
     // The implementation itself
     let gen = quote! {
-        impl<'b> ::rustler::Encoder for #struct_type {
-            fn encode<'a>(&self, env: ::rustler::Env<'a>) -> ::rustler::Term<'a> {
+        impl #impl_generics ::rustler::Encoder for #struct_name #ty_generics #where_clause {
+            fn encode<'__rustler_encode>(&self, env: ::rustler::Env<'__rustler_encode>) -> ::rustler::Term<'__rustler_encode> {
                 use #atoms_module_name::*;
 
                 use ::rustler::Encoder;
@@ -178,12 +181,97 @@ fn gen_encoder(ctx: &Context, fields: &[&Field], atoms_module_name: &Ident) -> T
     gen
 }
 
-fn get_tag(ctx: &Context) -> String {
+fn get_tag(ctx: &Context) -> syn::Result<String> {
     ctx.attrs
         .iter()
         .find_map(|attr| match attr {
             RustlerAttr::Tag(ref tag) => Some(tag.clone()),
             _ => None,
         })
-        .expect("NifStruct requires a 'tag' attribute")
+        .ok_or_else(|| {
+            syn::Error::new(
+                ctx.ident.span(),
+                "NifRecord requires a `#[tag = \"...\"]` attribute",
+            )
+        })
+}
+
+/// Checks the struct against the record definition in the header given with
+/// `#[rustler(hrl = "...")]`. The returned tokens include the header, so that the crate is
+/// rebuilt when it changes.
+fn check_hrl(ctx: &Context, fields: &[&Field], tag: &str) -> syn::Result<TokenStream> {
+    let hrl = match ctx.attrs.iter().find_map(|attr| match attr {
+        RustlerAttr::Hrl(ref hrl) => Some(hrl),
+        _ => None,
+    }) {
+        Some(hrl) => hrl,
+        None => return Ok(quote! {}),
+    };
+
+    let path = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join(hrl.value());
+    let source = fs::read_to_string(&path).map_err(|err| {
+        syn::Error::new_spanned(hrl, format!("Could not read {}: {}", path.display(), err))
+    })?;
+
+    let record_fields = hrl::record_fields(&source, tag).ok_or_else(|| {
+        syn::Error::new_spanned(
+            hrl,
+            format!("Record {} is not defined in {}", tag, hrl.value()),
+        )
+    })?;
+
+    if ctx.is_tuple_struct {
+        if record_fields.len() != fields.len() {
+            return Err(syn::Error::new_spanned(
+                hrl,
+                format!(
+                    "Record {} has {} fields, but {} has {}",
+                    tag,
+                    record_fields.len(),
+                    ctx.ident,
+                    fields.len()
+                ),
+            ));
+        }
+    } else {
+        for (index, field) in fields.iter().enumerate() {
+            let name = field.ident.as_ref().unwrap().to_string();
+            let name = Context::remove_raw(&name);
+
+            match record_fields.get(index) {
+                Some(expected) if expected == name => (),
+                Some(expected) => {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        format!(
+                            "Expected field {} of record {} at this position, found {}",
+                            expected, tag, name
+                        ),
+                    ))
+                }
+                None => {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        format!("Record {} has no field {}", tag, name),
+                    ))
+                }
+            }
+        }
+
+        if record_fields.len() > fields.len() {
+            return Err(syn::Error::new(
+                ctx.ident.span(),
+                format!(
+                    "Missing fields of record {}: {}",
+                    tag,
+                    record_fields[fields.len()..].join(", ")
+                ),
+            ));
+        }
+    }
+
+    let path = path.to_string_lossy();
+    Ok(quote! {
+        const _: &[u8] = include_bytes!(#path);
+    })
 }
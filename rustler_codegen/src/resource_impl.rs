@@ -0,0 +1,367 @@
+use heck::SnakeCase;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse_quote;
+use syn::visit_mut::{self, VisitMut};
+
+#[derive(Default)]
+struct MethodOptions {
+    skip: bool,
+    name: Option<String>,
+    schedule: Option<syn::LitStr>,
+}
+
+pub fn resource_impl(
+    args: syn::AttributeArgs,
+    mut item: syn::ItemImpl,
+) -> syn::Result<TokenStream> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "`resource_impl` can only be used on inherent impl blocks",
+        ));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "`resource_impl` does not support generic types",
+        ));
+    }
+
+    let self_ty = (*item.self_ty).clone();
+    let type_name = match &self_ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            path.segments.last().unwrap().ident.to_string()
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &self_ty,
+                "`resource_impl` can only be used on impl blocks of named types",
+            ))
+        }
+    };
+    let prefix = extract_prefix(args)?.unwrap_or_else(|| type_name.to_snake_case());
+
+    let mut nifs = TokenStream::new();
+
+    for impl_item in item.items.iter_mut() {
+        if let syn::ImplItem::Method(method) = impl_item {
+            let options = take_options(&mut method.attrs)?;
+
+            // Only `pub` methods are exported, so that helpers stay private to the impl block.
+            if !matches!(method.vis, syn::Visibility::Public(_)) {
+                if options.name.is_some() || options.schedule.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        &method.sig.ident,
+                        "Only `pub` methods are exported as NIFs, make this method `pub`",
+                    ));
+                }
+                continue;
+            }
+
+            if !options.skip {
+                nifs.extend(method_nif(&self_ty, &type_name, &prefix, method, options)?);
+            }
+        }
+    }
+
+    let resource = resource_type(&self_ty, &type_name);
+
+    Ok(quote! {
+        #item
+        #resource
+        #nifs
+    })
+}
+
+/// Implements `ResourceImpl` for the type and registers the resource type so that it is opened
+/// when the library is loaded.
+fn resource_type(self_ty: &syn::Type, type_name: &str) -> TokenStream {
+    quote! {
+        const _: () = {
+            static RESOURCE_TYPE: std::sync::OnceLock<
+                rustler::resource::ResourceType<rustler::ResourceLock<#self_ty>>
+            > = std::sync::OnceLock::new();
+
+            impl rustler::resource::ResourceImpl for #self_ty {
+                fn get_type() -> &'static rustler::resource::ResourceType<rustler::ResourceLock<Self>> {
+                    RESOURCE_TYPE
+                        .get()
+                        .expect("The resource type hasn't been initialized. Was the library loaded through `rustler::init!`?")
+                }
+            }
+
+            #[cfg(not(test))]
+            fn open(env: rustler::Env) -> bool {
+                match rustler::resource::open_struct_resource_type::<rustler::ResourceLock<#self_ty>>(
+                    env,
+                    concat!(#type_name, "\x00"),
+                    rustler::resource::NIF_RESOURCE_FLAGS::ERL_NIF_RT_CREATE,
+                ) {
+                    Some(resource_type) => {
+                        let _ = RESOURCE_TYPE.set(resource_type);
+                        true
+                    }
+                    None => false,
+                }
+            }
+
+            #[cfg(not(test))]
+            rustler::codegen_runtime::inventory::submit!(
+                rustler::codegen_runtime::ResourceRegistration { open }
+            );
+        };
+    }
+}
+
+/// Generates a NIF calling `method`. Methods taking `&self` or `&mut self` receive the resource as
+/// their first argument after `Env`, and associated functions returning `Self` return a new
+/// resource.
+fn method_nif(
+    self_ty: &syn::Type,
+    type_name: &str,
+    prefix: &str,
+    method: &syn::ImplItemMethod,
+    options: MethodOptions,
+) -> syn::Result<TokenStream> {
+    let sig = &method.sig;
+    let method_name = &sig.ident;
+    let name = options.name.unwrap_or_else(|| {
+        let method_name = method_name.to_string();
+        format!("{}_{}", prefix, method_name.trim_start_matches("r#"))
+    });
+    let nif_name = syn::parse_str::<syn::Ident>(&name).map_err(|_| {
+        syn::Error::new_spanned(method_name, format!("`{}` is not a valid NIF name", name))
+    })?;
+
+    let mut replace_self = ReplaceSelf(self_ty);
+    let resource_name = syn::Ident::new(&type_name.to_snake_case(), Span::call_site());
+    let resource_ty: syn::Type =
+        parse_quote!(rustler::ResourceArc<rustler::ResourceLock<#self_ty>>);
+
+    let mut env_param = None;
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    let mut receiver = None;
+
+    for (idx, input) in sig.inputs.iter().enumerate() {
+        match input {
+            syn::FnArg::Receiver(syn::Receiver {
+                reference: Some(_),
+                mutability,
+                ..
+            }) => receiver = Some(mutability.is_some()),
+            syn::FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "Methods taking `self` by value can't be exported, use `&self` or `&mut self`",
+                ))
+            }
+            syn::FnArg::Typed(typed) => {
+                let mut ty = (*typed.ty).clone();
+                replace_self.visit_type_mut(&mut ty);
+
+                if is_env(&ty) {
+                    env_param = Some(quote!(env: #ty));
+                    args.push(quote!(env));
+                    continue;
+                }
+
+                let arg = match &*typed.pat {
+                    syn::Pat::Ident(pat) => pat.ident.clone(),
+                    _ => syn::Ident::new(&format!("arg{}", idx), Span::call_site()),
+                };
+                params.push(quote!(#arg: #ty));
+                args.push(quote!(#arg));
+            }
+        }
+    }
+
+    let mut output = sig.output.clone();
+    if let syn::ReturnType::Type(_, ty) = &mut output {
+        replace_self.visit_type_mut(ty);
+    }
+
+    // `Env` has to be the first parameter of a NIF, followed by the resource.
+    let mut all_params: Vec<TokenStream> = env_param.into_iter().collect();
+
+    let (output, body) = match receiver {
+        Some(mutable) => {
+            let lock = if mutable { quote!(write) } else { quote!(read) };
+            all_params.push(quote!(#resource_name: #resource_ty));
+
+            (
+                output,
+                quote!(#resource_name.#lock().#method_name(#(#args),*)),
+            )
+        }
+        None => {
+            let call = quote!(<#self_ty>::#method_name(#(#args),*));
+
+            match constructor_output(&output, self_ty, &resource_ty) {
+                Some((output, true)) => (
+                    output,
+                    quote!(#call.map(|value| rustler::ResourceArc::new(rustler::ResourceLock::new(value)))),
+                ),
+                Some((output, false)) => (
+                    output,
+                    quote!(rustler::ResourceArc::new(rustler::ResourceLock::new(#call))),
+                ),
+                None => (output, call),
+            }
+        }
+    };
+    all_params.extend(params);
+
+    let generics = &sig.generics;
+    let where_clause = &sig.generics.where_clause;
+
+    let fun: syn::ItemFn = parse_quote! {
+        fn #nif_name #generics (#(#all_params),*) #output #where_clause {
+            #body
+        }
+    };
+
+    let mut nif_args: syn::AttributeArgs = vec![parse_quote!(name = #name)];
+    if let Some(schedule) = options.schedule {
+        nif_args.push(parse_quote!(schedule = #schedule));
+    }
+
+    crate::nif::transcoder_decorator(nif_args, fun)
+}
+
+/// If `output` is `Self` or a `Result` of `Self`, returns the output with `Self` replaced by the
+/// resource type, and whether it is a `Result`.
+fn constructor_output(
+    output: &syn::ReturnType,
+    self_ty: &syn::Type,
+    resource_ty: &syn::Type,
+) -> Option<(syn::ReturnType, bool)> {
+    let ty = match output {
+        syn::ReturnType::Type(_, ty) => ty,
+        syn::ReturnType::Default => return None,
+    };
+
+    if same_type(ty, self_ty) {
+        return Some((parse_quote!(-> #resource_ty), false));
+    }
+
+    let mut result = (**ty).clone();
+    if let syn::Type::Path(syn::TypePath { path, .. }) = &mut result {
+        let segment = path.segments.last_mut().unwrap();
+        let is_result = segment.ident == "Result" || segment.ident == "NifResult";
+
+        if let syn::PathArguments::AngleBracketed(generics) = &mut segment.arguments {
+            if let Some(syn::GenericArgument::Type(ok)) = generics.args.first_mut() {
+                if is_result && same_type(ok, self_ty) {
+                    *ok = resource_ty.clone();
+                    return Some((parse_quote!(-> #result), true));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn same_type(ty: &syn::Type, self_ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) if path.is_ident("Self") => true,
+        _ => quote!(#ty).to_string() == quote!(#self_ty).to_string(),
+    }
+}
+
+fn is_env(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            matches!(path.segments.last(), Some(segment) if segment.ident == "Env")
+        }
+        _ => false,
+    }
+}
+
+fn extract_prefix(args: syn::AttributeArgs) -> syn::Result<Option<String>> {
+    use syn::{Lit, Meta, MetaNameValue, NestedMeta};
+
+    let mut prefix = None;
+
+    for arg in args.iter() {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(lit),
+                ..
+            })) if path.is_ident("prefix") => prefix = Some(lit.value()),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "Unknown attribute. Allowed attributes: [\"prefix\"]",
+                ))
+            }
+        }
+    }
+
+    Ok(prefix)
+}
+
+/// Removes the `#[rustler(...)]` attributes of a method and returns the options they set.
+fn take_options(attrs: &mut Vec<syn::Attribute>) -> syn::Result<MethodOptions> {
+    use syn::{Lit, Meta, MetaNameValue, NestedMeta};
+
+    let mut options = MethodOptions::default();
+    let mut rest = Vec::with_capacity(attrs.len());
+
+    for attr in attrs.drain(..) {
+        if !attr.path.is_ident("rustler") {
+            rest.push(attr);
+            continue;
+        }
+
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            meta => return Err(syn::Error::new_spanned(meta, "Expected `#[rustler(...)]`")),
+        };
+
+        for meta in nested.iter() {
+            match meta {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => options.skip = true,
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("name") => options.name = Some(lit.value()),
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("schedule") => options.schedule = Some(lit.clone()),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        meta,
+                        "Unknown attribute. Allowed attributes: [\"skip\", \"name\", \"schedule\"]",
+                    ))
+                }
+            }
+        }
+    }
+
+    *attrs = rest;
+    Ok(options)
+}
+
+/// Replaces `Self` in types with the type of the impl block, since the generated NIFs are free
+/// functions.
+struct ReplaceSelf<'a>(&'a syn::Type);
+
+impl VisitMut for ReplaceSelf<'_> {
+    fn visit_type_mut(&mut self, ty: &mut syn::Type) {
+        if let syn::Type::Path(syn::TypePath { qself: None, path }) = ty {
+            if path.is_ident("Self") {
+                *ty = self.0.clone();
+                return;
+            }
+        }
+
+        visit_mut::visit_type_mut(self, ty);
+    }
+}
@@ -0,0 +1,318 @@
+use proc_macro2::{Delimiter, Ident, Literal, Spacing, Span, TokenStream, TokenTree};
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, Lit, LitInt, Token};
+
+/// The input of `rustler::term!`: an `Env` expression and a term written in Elixir syntax.
+pub struct TermMacroInput {
+    env: Expr,
+    term: TokenStream,
+}
+
+impl Parse for TermMacroInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let env = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let term = input.parse()?;
+
+        Ok(TermMacroInput { env, term })
+    }
+}
+
+/// A term in the input.
+enum Node {
+    Atom(String),
+    Tuple(Vec<Node>),
+    List(Vec<Node>),
+    Map(Vec<(Node, Node)>),
+    /// A Rust expression, encoded with `Encoder`.
+    Expr(TokenStream),
+}
+
+/// An element of a tuple, list or map.
+enum Element {
+    Value(Vec<TokenTree>),
+    /// A `key: value` entry of a keyword list or map with atom keys.
+    Keyword(String, Vec<TokenTree>),
+}
+
+pub fn expand(input: TermMacroInput) -> syn::Result<TokenStream> {
+    let mut elements = split_elements(input.term.clone())?;
+    let node = match (elements.pop(), elements.is_empty()) {
+        (Some(Element::Value(tokens)), true) => parse_node(tokens)?,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input.term,
+                "Expected a single term after the environment",
+            ))
+        }
+    };
+
+    let mut atoms = Vec::new();
+    let env_ident = Ident::new("__rustler_term_env", Span::call_site());
+    let term = gen_node(&node, &env_ident, &mut atoms);
+    let atom_defs = atom_defs(&atoms);
+
+    let env = &input.env;
+    Ok(quote! {
+        {
+            #atom_defs
+
+            let #env_ident: ::rustler::Env = #env;
+            #term
+        }
+    })
+}
+
+/// Declares the atoms used in a macro invocation with `rustler::atoms!`, so that they are only
+/// created once.
+pub(crate) fn atom_defs(atoms: &[String]) -> TokenStream {
+    if atoms.is_empty() {
+        return quote! {};
+    }
+
+    let atom_defs = atoms.iter().enumerate().map(|(index, atom)| {
+        let atom_fun = atom_fun(index);
+        quote! { #atom_fun = #atom, }
+    });
+    quote! {
+        ::rustler::atoms! {
+            #(#atom_defs)*
+        }
+    }
+}
+
+/// The function returning the atom `name` declared by `atom_defs`.
+pub(crate) fn atom(atoms: &mut Vec<String>, name: &str) -> Ident {
+    let index = match atoms.iter().position(|atom| atom == name) {
+        Some(index) => index,
+        None => {
+            atoms.push(name.to_string());
+            atoms.len() - 1
+        }
+    };
+    atom_fun(index)
+}
+
+fn atom_fun(index: usize) -> Ident {
+    Ident::new(&format!("__rustler_term_atom_{}", index), Span::call_site())
+}
+
+fn gen_node(node: &Node, env: &Ident, atoms: &mut Vec<String>) -> TokenStream {
+    match node {
+        Node::Atom(name) => {
+            let atom_fun = atom(atoms, name);
+            quote! { #atom_fun().to_term(#env) }
+        }
+        Node::Tuple(items) => {
+            let items = items.iter().map(|item| gen_node(item, env, atoms));
+            quote! { ::rustler::types::tuple::make_tuple(#env, &[#(#items),*]) }
+        }
+        Node::List(items) => {
+            let items = items.iter().map(|item| gen_node(item, env, atoms));
+            quote! { ::rustler::codegen_runtime::make_list(#env, &[#(#items),*]) }
+        }
+        Node::Map(entries) => {
+            let (keys, values): (Vec<_>, Vec<_>) = entries
+                .iter()
+                .map(|(key, value)| (gen_node(key, env, atoms), gen_node(value, env, atoms)))
+                .unzip();
+            quote! {
+                ::rustler::codegen_runtime::make_map(#env, &[#(#keys),*], &[#(#values),*])
+            }
+        }
+        Node::Expr(expr) => quote! { ::rustler::Encoder::encode(&(#expr), #env) },
+    }
+}
+
+fn parse_node(tokens: Vec<TokenTree>) -> syn::Result<Node> {
+    match tokens.as_slice() {
+        [] => unreachable!(),
+        // `:atom` or `:"quoted atom"`
+        [colon, name] if is_colon(colon) => match atom_name(name) {
+            Some(name) => Ok(Node::Atom(name)),
+            None => Err(syn::Error::new_spanned(name, "Expected an atom name")),
+        },
+        [TokenTree::Ident(ident)] if ident == "nil" => Ok(Node::Atom("nil".to_string())),
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Brace => {
+            let items = split_elements(group.stream())?
+                .into_iter()
+                .map(|element| match element {
+                    Element::Value(tokens) => parse_node(tokens),
+                    Element::Keyword(..) => Err(syn::Error::new_spanned(
+                        group,
+                        "Keyword entries are not supported in tuples",
+                    )),
+                })
+                .collect::<syn::Result<_>>()?;
+            Ok(Node::Tuple(items))
+        }
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Bracket => {
+            let items = split_elements(group.stream())?
+                .into_iter()
+                .map(|element| match element {
+                    Element::Value(tokens) => parse_node(tokens),
+                    Element::Keyword(key, value) => {
+                        Ok(Node::Tuple(vec![Node::Atom(key), parse_node(value)?]))
+                    }
+                })
+                .collect::<syn::Result<_>>()?;
+            Ok(Node::List(items))
+        }
+        [TokenTree::Punct(percent), rest @ ..] if percent.as_char() == '%' => parse_map(rest),
+        [TokenTree::Literal(literal)] => Ok(Node::Expr(literal_expr(literal, false))),
+        [TokenTree::Punct(minus), TokenTree::Literal(literal)] if minus.as_char() == '-' => {
+            Ok(Node::Expr(literal_expr(literal, true)))
+        }
+        _ => Ok(Node::Expr(tokens.into_iter().collect())),
+    }
+}
+
+/// Parses `%{...}` or `%Module.Name{...}`, without the leading `%`.
+fn parse_map(tokens: &[TokenTree]) -> syn::Result<Node> {
+    let (group, module) = match tokens.split_last() {
+        Some((TokenTree::Group(group), module)) if group.delimiter() == Delimiter::Brace => {
+            (group, module)
+        }
+        _ => {
+            let tokens: TokenStream = tokens.iter().cloned().collect();
+            return Err(syn::Error::new_spanned(tokens, "Expected `%{...}`"));
+        }
+    };
+
+    let mut entries = Vec::new();
+
+    if !module.is_empty() {
+        let name: String = module.iter().map(ToString::to_string).collect();
+        entries.push((
+            Node::Atom("__struct__".to_string()),
+            Node::Atom(format!("Elixir.{}", name)),
+        ));
+    }
+
+    for element in split_elements(group.stream())? {
+        match element {
+            Element::Keyword(key, value) => entries.push((Node::Atom(key), parse_node(value)?)),
+            Element::Value(tokens) => {
+                let arrow = tokens.windows(2).position(|pair| match pair {
+                    [TokenTree::Punct(eq), TokenTree::Punct(gt)] => {
+                        eq.as_char() == '=' && eq.spacing() == Spacing::Joint && gt.as_char() == '>'
+                    }
+                    _ => false,
+                });
+
+                match arrow {
+                    Some(index) if index > 0 && index + 2 < tokens.len() => {
+                        let value = tokens[index + 2..].to_vec();
+                        let key = tokens[..index].to_vec();
+                        entries.push((parse_node(key)?, parse_node(value)?));
+                    }
+                    _ => {
+                        let tokens: TokenStream = tokens.into_iter().collect();
+                        return Err(syn::Error::new_spanned(
+                            tokens,
+                            "Expected `key => value` or `key: value` in map",
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(Node::Map(entries))
+}
+
+/// Splits the contents of a tuple, list or map at commas, except those between the angle
+/// brackets of generic arguments.
+fn split_elements(tokens: TokenStream) -> syn::Result<Vec<Element>> {
+    let mut chunks = vec![Vec::new()];
+    let mut depth = 0;
+    let mut previous: Option<char> = None;
+
+    for token in tokens {
+        if let TokenTree::Punct(ref punct) = token {
+            match punct.as_char() {
+                ',' if depth == 0 => {
+                    if chunks.last().unwrap().is_empty() {
+                        return Err(syn::Error::new_spanned(punct, "Expected a term before `,`"));
+                    }
+                    chunks.push(Vec::new());
+                    previous = None;
+                    continue;
+                }
+                '<' => depth += 1,
+                // Not the end of `=>` or `->`.
+                '>' if previous != Some('=') && previous != Some('-') => depth -= 1,
+                _ => (),
+            }
+            previous = Some(punct.as_char());
+        } else {
+            previous = None;
+        }
+
+        chunks.last_mut().unwrap().push(token);
+    }
+
+    // A trailing comma is allowed.
+    if chunks.last().unwrap().is_empty() {
+        chunks.pop();
+    }
+
+    Ok(chunks.into_iter().map(element).collect())
+}
+
+fn element(mut tokens: Vec<TokenTree>) -> Element {
+    let keyword = match tokens.as_slice() {
+        [key, TokenTree::Punct(colon), next, ..] if colon.as_char() == ':' => {
+            // Not the start of a path such as `a::b`.
+            let path = colon.spacing() == Spacing::Joint
+                && matches!(next, TokenTree::Punct(p) if p.as_char() == ':');
+            if path {
+                None
+            } else {
+                atom_name(key)
+            }
+        }
+        _ => None,
+    };
+
+    match keyword {
+        Some(key) => Element::Keyword(key, tokens.split_off(2)),
+        None => Element::Value(tokens),
+    }
+}
+
+pub(crate) fn is_colon(token: &TokenTree) -> bool {
+    matches!(token, TokenTree::Punct(punct) if punct.as_char() == ':')
+}
+
+/// The name of an atom written as an identifier or a string literal.
+pub(crate) fn atom_name(token: &TokenTree) -> Option<String> {
+    match token {
+        TokenTree::Ident(ident) => {
+            let name = ident.to_string();
+            Some(name.trim_start_matches("r#").to_string())
+        }
+        TokenTree::Literal(literal) => match Lit::new(literal.clone()) {
+            Lit::Str(string) => Some(string.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A literal, where unsuffixed integers are typed as `i64` rather than `i32`.
+pub(crate) fn literal_expr(literal: &Literal, negative: bool) -> TokenStream {
+    let minus = if negative {
+        quote! { - }
+    } else {
+        quote! {}
+    };
+
+    match Lit::new(literal.clone()) {
+        Lit::Int(int) if int.suffix().is_empty() => {
+            let int = LitInt::new(&format!("{}i64", int.base10_digits()), int.span());
+            quote! { #minus #int }
+        }
+        _ => quote! { #minus #literal },
+    }
+}
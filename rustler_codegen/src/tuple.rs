@@ -4,22 +4,19 @@ use syn::{self, spanned::Spanned, Field, Index};
 
 use super::context::Context;
 
-pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
-    let ctx = Context::from_ast(ast);
+pub fn transcoder_decorator(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let ctx = Context::from_ast(ast)?;
 
-    let struct_fields = ctx
-        .struct_fields
-        .as_ref()
-        .expect("NifTuple can only be used with structs");
+    let struct_fields = ctx.struct_fields("NifTuple")?;
 
     let decoder = if ctx.decode() {
-        gen_decoder(&ctx, &struct_fields)
+        gen_decoder(&ctx, struct_fields)
     } else {
         quote! {}
     };
 
     let encoder = if ctx.encode() {
-        gen_encoder(&ctx, &struct_fields)
+        gen_encoder(&ctx, struct_fields)
     } else {
         quote! {}
     };
@@ -29,12 +26,13 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
         #encoder
     };
 
-    gen
+    Ok(gen)
 }
 
 fn gen_decoder(ctx: &Context, fields: &[&Field]) -> TokenStream {
-    let struct_type = &ctx.ident_with_lifetime;
     let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.decoder_generics();
+    let lifetime = ctx.decode_lifetime();
     let struct_name_str = struct_name.to_string();
 
     // Make a decoder for each of the fields in the struct.
@@ -81,8 +79,8 @@ fn gen_decoder(ctx: &Context, fields: &[&Field]) -> TokenStream {
         }
     };
     let gen = quote! {
-        impl<'a> ::rustler::Decoder<'a> for #struct_type {
-            fn decode(term: ::rustler::Term<'a>) -> Result<Self, ::rustler::Error> {
+        impl #impl_generics ::rustler::Decoder<#lifetime> for #struct_name #ty_generics #where_clause {
+            fn decode(term: ::rustler::Term<#lifetime>) -> Result<Self, ::rustler::Error> {
                 let terms = ::rustler::types::tuple::get_tuple(term)?;
                 if terms.len() != #field_num {
                     return Err(::rustler::Error::BadArg);
@@ -107,7 +105,8 @@ fn gen_decoder(ctx: &Context, fields: &[&Field]) -> TokenStream {
 }
 
 fn gen_encoder(ctx: &Context, fields: &[&Field]) -> TokenStream {
-    let struct_type = &ctx.ident_with_lifetime;
+    let struct_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.encoder_generics();
 
     // Make a field encoder expression for each of the items in the struct.
     let field_encoders: Vec<TokenStream> = fields
@@ -131,8 +130,8 @@ fn gen_encoder(ctx: &Context, fields: &[&Field]) -> TokenStream {
 
     // The implementation itself
     let gen = quote! {
-        impl<'b> ::rustler::Encoder for #struct_type {
-            fn encode<'a>(&self, env: ::rustler::Env<'a>) -> ::rustler::Term<'a> {
+        impl #impl_generics ::rustler::Encoder for #struct_name #ty_generics #where_clause {
+            fn encode<'__rustler_encode>(&self, env: ::rustler::Env<'__rustler_encode>) -> ::rustler::Term<'__rustler_encode> {
                 use ::rustler::Encoder;
                 let arr = #field_list_ast;
                 ::rustler::types::tuple::make_tuple(env, &arr)
@@ -5,33 +5,78 @@ use syn::{self, spanned::Spanned, Fields, Ident, Variant};
 
 use super::context::Context;
 
-pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
-    let ctx = Context::from_ast(ast);
+pub fn transcoder_decorator(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let ctx = Context::from_ast(ast)?;
 
-    let variants = ctx
-        .variants
-        .as_ref()
-        .expect("NifUnitEnum can only be used with enums");
+    let variants = ctx.variants("NifUnitEnum")?;
+
+    let mut atoms = Vec::new();
+    let mut other = None;
+    let mut atom_names: Vec<String> = Vec::new();
+    let mut values: Vec<i64> = Vec::new();
+    let mut next_value = Some(0);
 
     for variant in variants {
         if let Fields::Unit = variant.fields {
         } else {
-            return quote_spanned! { variant.span() =>
-                compile_error!("NifUnitEnum can only be used with enums containing unit variants.");
-            };
+            return Err(syn::Error::new(
+                variant.span(),
+                "NifUnitEnum can only be used with enums containing unit variants.",
+            ));
         }
-    }
 
-    let atoms: Vec<TokenStream> = variants
-        .iter()
-        .map(|variant| {
-            let atom_str = variant.ident.to_string().to_snake_case();
-            let atom_fn = Ident::new(&format!("atom_{}", atom_str), Span::call_site());
-            quote! {
-                #atom_fn = #atom_str,
+        let variant_attrs = Context::variant_attrs(variant)?;
+
+        if variant_attrs.other {
+            if other.is_some() {
+                return Err(syn::Error::new(
+                    variant.span(),
+                    "Only one variant can be marked with `#[rustler(other)]`",
+                ));
             }
-        })
-        .collect();
+            other = Some(&variant.ident);
+        }
+
+        if ctx.integer_repr() {
+            if variant_attrs.rename.is_some() {
+                return Err(syn::Error::new(
+                    variant.span(),
+                    "`rename` cannot be used with `#[rustler(repr = \"integer\")]`",
+                ));
+            }
+
+            // Only literal discriminants are known here. A value that does not fit in an `i64`
+            // is truncated when encoded, and may collide with a smaller one.
+            let value = match &variant.discriminant {
+                Some((_, expr)) => discriminant_value(expr),
+                None => next_value,
+            };
+            if let Some(value) = value {
+                if values.contains(&(value as i64)) {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        format!("Duplicate value `{}`", value as i64),
+                    ));
+                }
+                values.push(value as i64);
+            }
+            next_value = value.and_then(|value| value.checked_add(1));
+        } else {
+            let atom_str = ctx.variant_atom(variant, &variant_attrs);
+            if atom_names.contains(&atom_str) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    format!("Duplicate atom `{}`", atom_str),
+                ));
+            }
+            atom_names.push(atom_str.clone());
+
+            let atom_fn = atom_fn(variant);
+            atoms.push(quote! {
+                #atom_fn = #atom_str,
+            });
+        }
+    }
 
     let atom_defs = quote! {
         rustler::atoms! {
@@ -41,16 +86,20 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
 
     let atoms_module_name = ctx.atoms_module_name(Span::call_site());
 
-    let decoder = if ctx.decode() {
-        gen_decoder(&ctx, &variants, &atoms_module_name)
-    } else {
+    let decoder = if !ctx.decode() {
         quote! {}
+    } else if ctx.integer_repr() {
+        gen_integer_decoder(&ctx, variants, other)
+    } else {
+        gen_decoder(&ctx, variants, other, &atoms_module_name)
     };
 
-    let encoder = if ctx.encode() {
-        gen_encoder(&ctx, &variants, &atoms_module_name)
-    } else {
+    let encoder = if !ctx.encode() {
         quote! {}
+    } else if ctx.integer_repr() {
+        gen_integer_encoder(&ctx, variants)
+    } else {
+        gen_encoder(&ctx, variants, &atoms_module_name)
     };
 
     let gen = quote! {
@@ -62,19 +111,54 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
         #encoder
     };
 
-    gen
+    Ok(gen)
+}
+
+fn atom_fn(variant: &Variant) -> Ident {
+    let atom_str = variant.ident.to_string().to_snake_case();
+    Ident::new(&format!("atom_{}", atom_str), Span::call_site())
+}
+
+/// The value of a discriminant written as an integer literal, possibly negated.
+fn discriminant_value(expr: &syn::Expr) -> Option<i128> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(int),
+            ..
+        }) => int.base10_parse().ok(),
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => discriminant_value(expr).map(|value| -value),
+        syn::Expr::Paren(syn::ExprParen { expr, .. }) => discriminant_value(expr),
+        _ => None,
+    }
 }
 
-fn gen_decoder(ctx: &Context, variants: &[&Variant], atoms_module_name: &Ident) -> TokenStream {
-    let enum_type = &ctx.ident_with_lifetime;
+/// The result of decoding an unknown value: the `#[rustler(other)]` variant if there is one.
+fn fallback(other: Option<&Ident>) -> TokenStream {
+    match other {
+        Some(other) => quote! { Ok(Self::#other) },
+        None => quote! { Err(::rustler::Error::Atom("invalid_variant")) },
+    }
+}
+
+fn gen_decoder(
+    ctx: &Context,
+    variants: &[&Variant],
+    other: Option<&Ident>,
+    atoms_module_name: &Ident,
+) -> TokenStream {
     let enum_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.decoder_generics();
+    let lifetime = ctx.decode_lifetime();
 
     let variant_defs: Vec<TokenStream> = variants
         .iter()
         .map(|variant| {
             let variant_ident = &variant.ident;
-            let atom_str = variant_ident.to_string().to_snake_case();
-            let atom_fn = Ident::new(&format!("atom_{}", atom_str), Span::call_site());
+            let atom_fn = atom_fn(variant);
 
             quote! {
                 if value == #atom_fn() {
@@ -84,16 +168,18 @@ fn gen_decoder(ctx: &Context, variants: &[&Variant], atoms_module_name: &Ident)
         })
         .collect();
 
+    let fallback = fallback(other);
+
     let gen = quote! {
-        impl<'a> ::rustler::Decoder<'a> for #enum_type {
-            fn decode(term: ::rustler::Term<'a>) -> Result<Self, ::rustler::Error> {
+        impl #impl_generics ::rustler::Decoder<#lifetime> for #enum_name #ty_generics #where_clause {
+            fn decode(term: ::rustler::Term<#lifetime>) -> Result<Self, ::rustler::Error> {
                 use #atoms_module_name::*;
 
                 let value = ::rustler::types::atom::Atom::from_term(term)?;
 
                 #(#variant_defs)*
 
-                Err(::rustler::Error::Atom("invalid_variant"))
+                #fallback
             }
         }
     };
@@ -102,15 +188,14 @@ fn gen_decoder(ctx: &Context, variants: &[&Variant], atoms_module_name: &Ident)
 }
 
 fn gen_encoder(ctx: &Context, variants: &[&Variant], atoms_module_name: &Ident) -> TokenStream {
-    let enum_type = &ctx.ident_with_lifetime;
     let enum_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.encoder_generics();
 
     let variant_defs: Vec<TokenStream> = variants
         .iter()
         .map(|variant| {
             let variant_ident = &variant.ident;
-            let atom_str = variant_ident.to_string().to_snake_case();
-            let atom_fn = Ident::new(&format!("atom_{}", atom_str), Span::call_site());
+            let atom_fn = atom_fn(variant);
 
             quote! {
                 #enum_name :: #variant_ident => #atom_fn().encode(env),
@@ -119,8 +204,8 @@ fn gen_encoder(ctx: &Context, variants: &[&Variant], atoms_module_name: &Ident)
         .collect();
 
     let gen = quote! {
-        impl<'b> ::rustler::Encoder for #enum_type {
-            fn encode<'a>(&self, env: ::rustler::Env<'a>) -> ::rustler::Term<'a> {
+        impl #impl_generics ::rustler::Encoder for #enum_name #ty_generics #where_clause {
+            fn encode<'__rustler_encode>(&self, env: ::rustler::Env<'__rustler_encode>) -> ::rustler::Term<'__rustler_encode> {
                 use #atoms_module_name::*;
 
                 match *self {
@@ -132,3 +217,66 @@ fn gen_encoder(ctx: &Context, variants: &[&Variant], atoms_module_name: &Ident)
 
     gen
 }
+
+fn gen_integer_decoder(ctx: &Context, variants: &[&Variant], other: Option<&Ident>) -> TokenStream {
+    let enum_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.decoder_generics();
+    let lifetime = ctx.decode_lifetime();
+
+    let variant_defs: Vec<TokenStream> = variants
+        .iter()
+        .map(|variant| {
+            let variant_ident = &variant.ident;
+
+            quote! {
+                if value == Self::#variant_ident as i64 {
+                    return Ok ( #enum_name :: #variant_ident );
+                }
+            }
+        })
+        .collect();
+
+    let fallback = fallback(other);
+
+    let gen = quote! {
+        impl #impl_generics ::rustler::Decoder<#lifetime> for #enum_name #ty_generics #where_clause {
+            fn decode(term: ::rustler::Term<#lifetime>) -> Result<Self, ::rustler::Error> {
+                let value: i64 = term.decode()?;
+
+                #(#variant_defs)*
+
+                #fallback
+            }
+        }
+    };
+
+    gen
+}
+
+fn gen_integer_encoder(ctx: &Context, variants: &[&Variant]) -> TokenStream {
+    let enum_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.encoder_generics();
+
+    let variant_defs: Vec<TokenStream> = variants
+        .iter()
+        .map(|variant| {
+            let variant_ident = &variant.ident;
+
+            quote! {
+                #enum_name :: #variant_ident => (Self::#variant_ident as i64).encode(env),
+            }
+        })
+        .collect();
+
+    let gen = quote! {
+        impl #impl_generics ::rustler::Encoder for #enum_name #ty_generics #where_clause {
+            fn encode<'__rustler_encode>(&self, env: ::rustler::Env<'__rustler_encode>) -> ::rustler::Term<'__rustler_encode> {
+                match *self {
+                    #(#variant_defs)*
+                }
+            }
+        }
+    };
+
+    gen
+}
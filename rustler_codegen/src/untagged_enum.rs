@@ -4,36 +4,33 @@ use syn::{self, spanned::Spanned, Fields, Variant};
 
 use super::context::Context;
 
-pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
-    let ctx = Context::from_ast(ast);
+pub fn transcoder_decorator(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let ctx = Context::from_ast(ast)?;
 
-    let variants = ctx
-        .variants
-        .as_ref()
-        .expect("NifUntaggedEnum can only be used with enums");
+    let variants = ctx.variants("NifUntaggedEnum")?;
 
     for variant in variants {
-        if let Fields::Unnamed(_) = variant.fields {
-            if variant.fields.iter().count() != 1 {
-                return quote_spanned! { variant.span() =>
-                    compile_error!("NifUntaggedEnum can only be used with enums that contain all NewType variants.");
-                };
-            }
-        } else {
-            return quote_spanned! { variant.span() =>
-                compile_error!("NifUntaggedEnum can only be used with enums that contain all NewType variants.");
-            };
+        let newtype = match variant.fields {
+            Fields::Unnamed(ref fields) => fields.unnamed.len() == 1,
+            _ => false,
+        };
+
+        if !newtype {
+            return Err(syn::Error::new(
+                variant.span(),
+                "NifUntaggedEnum can only be used with enums that contain all NewType variants.",
+            ));
         }
     }
 
     let decoder = if ctx.decode() {
-        gen_decoder(&ctx, &variants)
+        gen_decoder(&ctx, variants)
     } else {
         quote! {}
     };
 
     let encoder = if ctx.encode() {
-        gen_encoder(&ctx, &variants)
+        gen_encoder(&ctx, variants)
     } else {
         quote! {}
     };
@@ -43,12 +40,13 @@ pub fn transcoder_decorator(ast: &syn::DeriveInput) -> TokenStream {
         #encoder
     };
 
-    gen
+    Ok(gen)
 }
 
 fn gen_decoder(ctx: &Context, variants: &[&Variant]) -> TokenStream {
-    let enum_type = &ctx.ident_with_lifetime;
     let enum_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.decoder_generics();
+    let lifetime = ctx.decode_lifetime();
 
     let variant_defs: Vec<_> = variants
         .iter()
@@ -57,7 +55,7 @@ fn gen_decoder(ctx: &Context, variants: &[&Variant]) -> TokenStream {
             let field_type = &variant.fields.iter().next().unwrap().ty;
 
             quote! {
-                if let Ok(inner) = #field_type::decode(term) {
+                if let Ok(inner) = <#field_type as ::rustler::Decoder>::decode(term) {
                     return Ok( #enum_name :: #variant_name ( inner ) )
                 }
             }
@@ -65,8 +63,8 @@ fn gen_decoder(ctx: &Context, variants: &[&Variant]) -> TokenStream {
         .collect();
 
     let gen = quote! {
-        impl<'a> ::rustler::Decoder<'a> for #enum_type {
-            fn decode(term: ::rustler::Term<'a>) -> Result<Self, ::rustler::Error> {
+        impl #impl_generics ::rustler::Decoder<#lifetime> for #enum_name #ty_generics #where_clause {
+            fn decode(term: ::rustler::Term<#lifetime>) -> Result<Self, ::rustler::Error> {
                 #(#variant_defs)*
 
                 Err(::rustler::Error::Atom("invalid_variant"))
@@ -78,8 +76,8 @@ fn gen_decoder(ctx: &Context, variants: &[&Variant]) -> TokenStream {
 }
 
 fn gen_encoder(ctx: &Context, variants: &[&Variant]) -> TokenStream {
-    let enum_type = &ctx.ident_with_lifetime;
     let enum_name = ctx.ident;
+    let (impl_generics, ty_generics, where_clause) = ctx.encoder_generics();
 
     let variant_defs: Vec<_> = variants
         .iter()
@@ -93,8 +91,8 @@ fn gen_encoder(ctx: &Context, variants: &[&Variant]) -> TokenStream {
         .collect();
 
     let gen = quote! {
-        impl<'b> ::rustler::Encoder for #enum_type {
-            fn encode<'a>(&self, env: ::rustler::Env<'a>) -> ::rustler::Term<'a> {
+        impl #impl_generics ::rustler::Encoder for #enum_name #ty_generics #where_clause {
+            fn encode<'__rustler_encode>(&self, env: ::rustler::Env<'__rustler_encode>) -> ::rustler::Term<'__rustler_encode> {
                 match *self {
                     #(#variant_defs)*
                 }
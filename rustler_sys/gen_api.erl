@@ -98,9 +98,10 @@ api_list(Opts) -> [
     {"ERL_NIF_TERM", "enif_make_string", "env: *mut ErlNifEnv, string: *const c_uchar, arg1: ErlNifCharEncoding"},
     {"ERL_NIF_TERM", "enif_make_ref", "env: *mut ErlNifEnv"},
 
-    %% Skip threading API for now (perhaps forever)
+    %% Skip most of the threading API for now (perhaps forever)
     %% If anybody has a situation where they want to use this API instead of the very fine
     %% Rust API, please tell me.
+    %% Only thread creation is bound, for the worker threads of rustler::thread::PoolSpawner.
     %%      {"*mut ErlNifMutex", "enif_mutex_create", "name: *mut c_uchar"},
     %%      {"", "enif_mutex_destroy", "mtx: *mut ErlNifMutex"},
     %%      {"c_int", "enif_mutex_trylock", "mtx: *mut ErlNifMutex"},
@@ -123,13 +124,9 @@ api_list(Opts) -> [
     %%      {"", "enif_tsd_key_destroy", "key: ErlNifTSDKey"},
     %%      {"", "enif_tsd_set", "key: ErlNifTSDKey, data: *mut c_void"},
     %%      {"*mut c_void", "enif_tsd_get", "key: ErlNifTSDKey"},
-    %%      {"*mut ErlNifThreadOpts", "enif_thread_opts_create", "name: *mut c_uchar"},
-    %%      {"", "enif_thread_opts_destroy", "opts: *mut ErlNifThreadOpts"},
-    %%      {"c_int", "enif_thread_create", "name: *mut c_uchar, tid: *mut ErlNifTid, func: Option<unsafe extern \"C\" fn (arg1: *mut c_void) -> *mut c_void>, args: *mut c_void, opts: *mut ErlNifThreadOpts"},
     %%      {"ErlNifTid", "enif_thread_self", ""},
     %%      {"c_int", "enif_equal_tids", "tid1: ErlNifTid, tid2: ErlNifTid"},
     %%      {"", "enif_thread_exit", "resp: *mut c_void"},
-    %%      {"c_int", "enif_thread_join", "arg1: ErlNifTid, respp: *mut *mut c_void"},
     {"", "dummy_enif_mutex_create", ""},
     {"", "dummy_enif_mutex_destroy", ""},
     {"", "dummy_enif_mutex_trylock", ""},
@@ -152,13 +149,13 @@ api_list(Opts) -> [
     {"", "dummy_enif_tsd_key_destroy", ""},
     {"", "dummy_enif_tsd_set", ""},
     {"", "dummy_enif_tsd_get", ""},
-    {"", "dummy_enif_thread_opts_create", ""},
-    {"", "dummy_enif_thread_opts_destroy", ""},
-    {"", "dummy_enif_thread_create", ""},
+    {"*mut ErlNifThreadOpts", "enif_thread_opts_create", "name: *mut c_uchar"},
+    {"", "enif_thread_opts_destroy", "opts: *mut ErlNifThreadOpts"},
+    {"c_int", "enif_thread_create", "name: *mut c_uchar, tid: *mut ErlNifTid, func: Option<unsafe extern \"C\" fn (arg1: *mut c_void) -> *mut c_void>, args: *mut c_void, opts: *mut ErlNifThreadOpts"},
     {"", "dummy_enif_thread_self", ""},
     {"", "dummy_enif_equal_tids", ""},
     {"", "dummy_enif_thread_exit", ""},
-    {"", "dummy_enif_thread_join", ""},
+    {"c_int", "enif_thread_join", "arg1: ErlNifTid, respp: *mut *mut c_void"},
 
     {"*mut c_void", "enif_realloc", "ptr: *mut c_void, size: size_t"},
     {"", "enif_system_info", "sip: *mut ErlNifSysInfo, si_size: size_t"},
@@ -296,6 +296,16 @@ pub const ERL_NIF_THR_NORMAL_SCHEDULER: c_int = 1;
 pub const ERL_NIF_THR_DIRTY_CPU_SCHEDULER: c_int = 2;
 pub const ERL_NIF_THR_DIRTY_IO_SCHEDULER: c_int = 3;
 
+/// See [ErlNifTid](http://www.erlang.org/doc/man/erl_nif.html#ErlNifTid) in the Erlang docs.
+pub type ErlNifTid = *mut c_void;
+
+/// See [ErlNifThreadOpts](http://www.erlang.org/doc/man/erl_nif.html#ErlNifThreadOpts) in the Erlang docs.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct ErlNifThreadOpts {
+    pub suggested_stack_size: c_int,
+}
+
 /// See [ErlNifHash](http://www.erlang.org/doc/man/erl_nif.html#ErlNifHash) in the Erlang docs.
 #[derive(Copy, Clone)]
 #[repr(C)]
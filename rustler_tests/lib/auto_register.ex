@@ -0,0 +1,10 @@
+defmodule AutoRegister do
+  use Rustler,
+    otp_app: :rustler_test,
+    crate: :auto_register
+
+  def hello(), do: :erlang.nif_error(:auto_register_not_loaded)
+  def registered_names(), do: :erlang.nif_error(:auto_register_not_loaded)
+  def add(_, _), do: :erlang.nif_error(:auto_register_not_loaded)
+  def dirty_add(_, _), do: :erlang.nif_error(:auto_register_not_loaded)
+end
@@ -23,6 +23,14 @@ defmodule RustlerTest do
   def term_debug(_), do: err()
   def term_eq(_, _), do: err()
   def term_cmp(_, _), do: err()
+  def value_echo(_), do: err()
+  def value_cmp(_, _), do: err()
+  def value_sort(_), do: err()
+  def etf_encode(_, _), do: err()
+  def etf_decode(_), do: err()
+  def term_macro(_, _), do: err()
+  def term_macro_struct(_), do: err()
+  def match_term_example(_), do: err()
 
   def sum_map_values(_), do: err()
   def map_entries_sorted(_), do: err()
@@ -35,6 +43,13 @@ defmodule RustlerTest do
   def resource_make_immutable(_), do: err()
   def resource_immutable_count(), do: err()
 
+  def counter_new(_), do: err()
+  def counter_try_new(_), do: err()
+  def counter_get(_), do: err()
+  def counter_add(_, _), do: err()
+  def counter_reset(_), do: err()
+  def counter_double(_), do: err()
+
   def make_shorter_subbinary(_), do: err()
   def parse_integer(_), do: err()
   def binary_new(), do: err()
@@ -52,25 +67,57 @@ defmodule RustlerTest do
 
   def threaded_fac(_), do: err()
   def threaded_sleep(_), do: err()
+  def pooled_sleep(_), do: err()
+  def threaded_sum(_, _), do: err()
+  def threaded_cancel(_), do: err()
 
   def send_all(_, _), do: err()
+  def send_to(_, _), do: err()
+  def send_from_owned_env(_, _), do: err()
+  def current_process_alive(), do: err()
+  def process_alive(_), do: err()
+  def whereis_pid(_), do: err()
+  def send_to_name(_, _), do: err()
   def sublists(_), do: err()
+  def channel_stream(_), do: err()
+  def channel_bounded(_, _), do: err()
+  def channel_ack(_), do: err()
 
   def tuple_echo(_), do: err()
   def record_echo(_), do: err()
   def map_echo(_), do: err()
+  def string_key_map_echo(_), do: err()
+  def renamed_key_map_echo(_), do: err()
+  def extra_map_echo(_), do: err()
+  def extra_map_count(_), do: err()
+  def extra_struct_incr(_), do: err()
   def struct_echo(_), do: err()
   def unit_enum_echo(_), do: err()
+  def renamed_unit_enum_echo(_), do: err()
+  def integer_unit_enum_echo(_), do: err()
   def untagged_enum_echo(_), do: err()
   def untagged_enum_with_truthy(_), do: err()
   def newtype_echo(_), do: err()
   def tuplestruct_echo(_), do: err()
+  def hrl_record_echo(_), do: err()
   def newtype_record_echo(_), do: err()
   def tuplestruct_record_echo(_), do: err()
+  def keyword_list_echo(_), do: err()
+  def strict_keyword_list_echo(_), do: err()
+  def keyword_get(_, _), do: err()
   def reserved_keywords_type_echo(_), do: err()
+  def generic_map_echo(_), do: err()
+  def generic_borrowed_echo(_), do: err()
+  def generic_struct_echo(_), do: err()
+  def generic_tuple_echo(_), do: err()
+  def generic_record_echo(_), do: err()
+  def generic_untagged_enum_echo(_), do: err()
+  def const_generic_echo(_), do: err()
+  def generic_bounded_echo(_), do: err()
 
   def dirty_io(), do: err()
   def dirty_cpu(), do: err()
+  def dirty_wait_for_exit(_), do: err()
 
   def sum_range(_), do: err()
 
@@ -82,4 +129,18 @@ defmodule RustlerTest do
   def term_with_tuple_error(), do: err()
 
   def nif_attrs_can_rename(), do: err()
+  def nif_attrs_defaults(_), do: err()
+  def nif_attrs_defaults(_, _), do: err()
+  def nif_attrs_defaults(_, _, _), do: err()
+  def nif_attrs_marked_default(_), do: err()
+  def nif_attrs_marked_default(_, _), do: err()
+
+  def nif_args_tuple(_), do: err()
+  def nif_args_pattern(_, _), do: err()
+  def nif_args_array(_), do: err()
+  def nif_args_slice(_), do: err()
+  def nif_args_mut(_, _), do: err()
+
+  def stubs_elixir(), do: err()
+  def stubs_erlang(), do: err()
 end
@@ -0,0 +1,23 @@
+use rustler::{Env, Term};
+
+mod math;
+
+#[rustler::nif]
+fn hello() -> &'static str {
+    "world"
+}
+
+#[rustler::nif(name = "registered_names")]
+fn nif_names() -> Vec<String> {
+    RUSTLER_NIF_MODULE
+        .nifs()
+        .iter()
+        .map(|nif| format!("{}/{}", nif.name, nif.arity))
+        .collect()
+}
+
+fn load(_env: Env, _: Term) -> bool {
+    true
+}
+
+rustler::init!("Elixir.AutoRegister", load = load);
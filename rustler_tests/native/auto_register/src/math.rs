@@ -0,0 +1,9 @@
+#[rustler::nif]
+fn add(a: i64, b: i64) -> i64 {
+    a + b
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn dirty_add(a: i64, b: i64) -> i64 {
+    a + b
+}
@@ -6,10 +6,12 @@ mod test_env;
 mod test_error;
 mod test_list;
 mod test_map;
+mod test_nif_args;
 mod test_nif_attrs;
 mod test_primitives;
 mod test_range;
 mod test_resource;
+mod test_stubs;
 mod test_term;
 mod test_thread;
 
@@ -26,6 +28,14 @@ rustler::init!(
         test_term::term_debug,
         test_term::term_eq,
         test_term::term_cmp,
+        test_term::value_echo,
+        test_term::value_cmp,
+        test_term::value_sort,
+        test_term::etf_encode,
+        test_term::etf_decode,
+        test_term::term_macro,
+        test_term::term_macro_struct,
+        test_term::match_term_example,
         test_map::sum_map_values,
         test_map::map_entries_sorted,
         test_map::map_from_arrays,
@@ -35,6 +45,12 @@ rustler::init!(
         test_resource::resource_get_integer_field,
         test_resource::resource_make_immutable,
         test_resource::resource_immutable_count,
+        test_resource::counter_new,
+        test_resource::counter_try_new,
+        test_resource::counter_get,
+        test_resource::counter_add,
+        test_resource::counter_reset,
+        test_resource::counter_double,
         test_atom::atom_to_string,
         test_atom::atom_equals_ok,
         test_atom::binary_to_atom,
@@ -50,21 +66,45 @@ rustler::init!(
         test_binary::decode_iolist,
         test_thread::threaded_fac,
         test_thread::threaded_sleep,
+        test_thread::pooled_sleep,
+        test_thread::threaded_sum,
+        test_thread::threaded_cancel,
         test_env::send_all,
+        test_env::send_to,
+        test_env::send_from_owned_env,
+        test_env::current_process_alive,
+        test_env::process_alive,
+        test_env::whereis_pid,
+        test_env::send_to_name,
         test_env::sublists,
+        test_env::channel_stream,
+        test_env::channel_bounded,
+        test_env::channel_ack,
         test_codegen::tuple_echo,
         test_codegen::record_echo,
         test_codegen::map_echo,
+        test_codegen::string_key_map_echo,
+        test_codegen::renamed_key_map_echo,
+        test_codegen::extra_map_echo,
+        test_codegen::extra_map_count,
+        test_codegen::extra_struct_incr,
         test_codegen::struct_echo,
         test_codegen::unit_enum_echo,
+        test_codegen::renamed_unit_enum_echo,
+        test_codegen::integer_unit_enum_echo,
         test_codegen::untagged_enum_echo,
         test_codegen::untagged_enum_with_truthy,
         test_codegen::newtype_echo,
         test_codegen::tuplestruct_echo,
+        test_codegen::hrl_record_echo,
         test_codegen::newtype_record_echo,
         test_codegen::tuplestruct_record_echo,
+        test_codegen::keyword_list_echo,
+        test_codegen::strict_keyword_list_echo,
+        test_codegen::keyword_get,
         test_dirty::dirty_cpu,
         test_dirty::dirty_io,
+        test_dirty::dirty_wait_for_exit,
         test_range::sum_range,
         test_error::bad_arg_error,
         test_error::atom_str_error,
@@ -73,12 +113,29 @@ rustler::init!(
         test_error::raise_term_with_atom_error,
         test_error::term_with_tuple_error,
         test_nif_attrs::can_rename,
-        test_codegen::reserved_keywords::reserved_keywords_type_echo
+        test_nif_attrs::nif_attrs_defaults,
+        test_nif_attrs::nif_attrs_marked_default,
+        test_nif_args::nif_args_tuple,
+        test_nif_args::nif_args_pattern,
+        test_nif_args::nif_args_array,
+        test_nif_args::nif_args_slice,
+        test_nif_args::nif_args_mut,
+        test_codegen::reserved_keywords::reserved_keywords_type_echo,
+        test_codegen::generics::generic_map_echo,
+        test_codegen::generics::generic_borrowed_echo,
+        test_codegen::generics::generic_struct_echo,
+        test_codegen::generics::generic_tuple_echo,
+        test_codegen::generics::generic_record_echo,
+        test_codegen::generics::generic_untagged_enum_echo,
+        test_codegen::generics::const_generic_echo,
+        test_codegen::generics::generic_bounded_echo,
+        test_stubs::stubs_elixir,
+        test_stubs::stubs_erlang
     ],
     load = load
 );
 
 fn load(env: rustler::Env, _: rustler::Term) -> bool {
     test_resource::on_load(env);
-    true
+    test_thread::on_load()
 }
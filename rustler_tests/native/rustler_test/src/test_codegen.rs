@@ -1,5 +1,11 @@
+use std::collections::HashMap;
+
+use rustler::types::keyword::Keyword;
 use rustler::types::truthy::Truthy;
-use rustler::{NifMap, NifRecord, NifStruct, NifTuple, NifUnitEnum, NifUntaggedEnum};
+use rustler::{
+    Atom, Binary, NifKeywordList, NifMap, NifRecord, NifStruct, NifTuple, NifUnitEnum,
+    NifUntaggedEnum, Term,
+};
 
 #[derive(NifTuple)]
 pub struct AddTuple {
@@ -37,6 +43,61 @@ pub fn map_echo(map: AddMap) -> AddMap {
     map
 }
 
+#[derive(NifMap)]
+#[rustler(keys = "string")]
+pub struct StringKeyMap {
+    id: i64,
+    #[rustler(key = "displayName")]
+    display_name: String,
+}
+
+#[rustler::nif]
+pub fn string_key_map_echo(map: StringKeyMap) -> StringKeyMap {
+    map
+}
+
+#[derive(NifMap)]
+pub struct RenamedKeyMap {
+    #[rustler(key = "type")]
+    kind: String,
+}
+
+#[rustler::nif]
+pub fn renamed_key_map_echo(map: RenamedKeyMap) -> RenamedKeyMap {
+    map
+}
+
+#[derive(NifMap)]
+pub struct ExtraMap<'a> {
+    id: i64,
+    #[rustler(extra)]
+    extra: Vec<(Term<'a>, Term<'a>)>,
+}
+
+#[rustler::nif]
+pub fn extra_map_echo(map: ExtraMap) -> ExtraMap {
+    map
+}
+
+#[rustler::nif]
+pub fn extra_map_count(map: ExtraMap) -> usize {
+    map.extra.len()
+}
+
+#[derive(NifStruct)]
+#[module = "ExtraStruct"]
+pub struct ExtraStruct<'a> {
+    count: i64,
+    #[rustler(extra)]
+    extra: HashMap<Atom, Term<'a>>,
+}
+
+#[rustler::nif]
+pub fn extra_struct_incr(mut value: ExtraStruct) -> ExtraStruct {
+    value.count += 1;
+    value
+}
+
 #[derive(Debug, NifStruct)]
 #[must_use] // Added to test Issue #152
 #[module = "AddStruct"]
@@ -61,6 +122,34 @@ pub fn unit_enum_echo(unit_enum: UnitEnum) -> UnitEnum {
     unit_enum
 }
 
+#[derive(NifUnitEnum)]
+#[rustler(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RenamedUnitEnum {
+    FooBar,
+    #[rustler(rename = "Elixir.Baz")]
+    Baz,
+    #[rustler(other)]
+    Unknown,
+}
+
+#[rustler::nif]
+pub fn renamed_unit_enum_echo(unit_enum: RenamedUnitEnum) -> RenamedUnitEnum {
+    unit_enum
+}
+
+#[derive(NifUnitEnum)]
+#[rustler(repr = "integer")]
+pub enum IntegerUnitEnum {
+    Ok = 200,
+    NotFound = 404,
+    Teapot = 418,
+}
+
+#[rustler::nif]
+pub fn integer_unit_enum_echo(unit_enum: IntegerUnitEnum) -> IntegerUnitEnum {
+    unit_enum
+}
+
 #[derive(NifUntaggedEnum)]
 pub enum UntaggedEnum {
     Foo(u32),
@@ -101,6 +190,22 @@ pub fn tuplestruct_echo(tuplestruct: TupleStruct) -> TupleStruct {
     tuplestruct
 }
 
+#[derive(NifRecord)]
+#[tag = "hrl_record"]
+#[rustler(hrl = "include/records.hrl")]
+pub struct HrlRecord<'a> {
+    // A charlist, like the default in the header.
+    name: Vec<u8>,
+    tags: Vec<Atom>,
+    r#type: (Atom, u32),
+    data: Binary<'a>,
+}
+
+#[rustler::nif]
+pub fn hrl_record_echo(record: HrlRecord) -> HrlRecord {
+    record
+}
+
 #[derive(NifRecord)]
 #[tag = "newtype"]
 pub struct NewtypeRecord(i64);
@@ -119,6 +224,41 @@ pub fn tuplestruct_record_echo(tuplestruct: TupleStructRecord) -> TupleStructRec
     tuplestruct
 }
 
+fn default_retries() -> u32 {
+    3
+}
+
+#[derive(NifKeywordList)]
+pub struct KeywordOptions {
+    timeout: u64,
+    #[rustler(default = "default_retries")]
+    retries: u32,
+    #[rustler(default)]
+    verbose: bool,
+    name: Option<String>,
+}
+
+#[rustler::nif]
+pub fn keyword_list_echo(options: KeywordOptions) -> KeywordOptions {
+    options
+}
+
+#[derive(NifKeywordList)]
+#[rustler(deny_unknown_keys)]
+pub struct StrictKeywordOptions {
+    timeout: u64,
+}
+
+#[rustler::nif]
+pub fn strict_keyword_list_echo(options: StrictKeywordOptions) -> StrictKeywordOptions {
+    options
+}
+
+#[rustler::nif]
+pub fn keyword_get<'a>(keyword: Keyword<'a>, key: Atom) -> Option<Term<'a>> {
+    keyword.get(key)
+}
+
 pub mod reserved_keywords {
     use rustler::{NifMap, NifRecord, NifStruct, NifTuple, NifUntaggedEnum};
 
@@ -157,3 +297,93 @@ pub mod reserved_keywords {
         reserved
     }
 }
+
+pub mod generics {
+    use rustler::{Decoder, Encoder, NifMap, NifRecord, NifStruct, NifTuple, NifUntaggedEnum};
+
+    #[derive(NifMap)]
+    pub struct Page<T> {
+        items: Vec<T>,
+        total: u64,
+    }
+
+    #[rustler::nif]
+    pub fn generic_map_echo(page: Page<i64>) -> Page<i64> {
+        page
+    }
+
+    #[derive(NifMap)]
+    pub struct Borrowed<'a, T> {
+        name: &'a str,
+        value: T,
+    }
+
+    #[rustler::nif]
+    pub fn generic_borrowed_echo<'a>(borrowed: Borrowed<'a, u32>) -> Borrowed<'a, u32> {
+        borrowed
+    }
+
+    #[derive(NifStruct)]
+    #[module = "GenericStruct"]
+    pub struct GenericStruct<T> {
+        value: T,
+    }
+
+    #[rustler::nif]
+    pub fn generic_struct_echo(value: GenericStruct<String>) -> GenericStruct<String> {
+        value
+    }
+
+    #[derive(NifTuple)]
+    pub struct Pair<A, B>(A, B);
+
+    #[rustler::nif]
+    pub fn generic_tuple_echo(pair: Pair<i64, String>) -> Pair<i64, String> {
+        pair
+    }
+
+    #[derive(NifRecord)]
+    #[tag = "wrapper"]
+    pub struct Wrapper<T> {
+        inner: T,
+    }
+
+    #[rustler::nif]
+    pub fn generic_record_echo(wrapper: Wrapper<Pair<i64, i64>>) -> Wrapper<Pair<i64, i64>> {
+        wrapper
+    }
+
+    #[derive(NifUntaggedEnum)]
+    pub enum Either<L, R> {
+        Left(L),
+        Right(R),
+    }
+
+    #[rustler::nif]
+    pub fn generic_untagged_enum_echo(either: Either<i64, String>) -> Either<i64, String> {
+        either
+    }
+
+    #[derive(NifTuple)]
+    pub struct Fixed<const N: usize> {
+        bytes: [u8; N],
+    }
+
+    #[rustler::nif]
+    pub fn const_generic_echo(fixed: Fixed<4>) -> Fixed<4> {
+        fixed
+    }
+
+    /// `Vec<T>` only needs `T: Encoder` and `T: Decoder<'a>`, which is what the derived bounds
+    /// would give as well. The explicit bounds check that the attribute is accepted.
+    #[derive(NifMap)]
+    #[rustler(bound(encode = "T: Encoder", decode = "T: Decoder<'a>"))]
+    pub struct Bounded<T> {
+        values: Vec<T>,
+    }
+
+    #[rustler::nif]
+    pub fn generic_bounded_echo(bounded: Bounded<bool>) -> Bounded<bool> {
+        bounded
+    }
+}
@@ -1,8 +1,9 @@
-use rustler::Atom;
+use rustler::thread;
+use rustler::{Atom, Encoder, Env, LocalPid};
 use std::time::Duration;
 
 mod atoms {
-    rustler::atoms! { ok }
+    rustler::atoms! { ok, caller_exited }
 }
 
 // TODO: Make these realistic
@@ -22,3 +23,14 @@ pub fn dirty_io() -> Atom {
 
     atoms::ok()
 }
+
+// Wait until the caller exits, then tell `pid`.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn dirty_wait_for_exit(env: Env, token: thread::CancellationToken, pid: LocalPid) -> Atom {
+    while !token.is_cancelled() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    env.send(&pid, atoms::caller_exited().encode(env));
+
+    atoms::ok()
+}
@@ -1,8 +1,9 @@
+use rustler::channel::{self, Acknowledger, Sender};
 use rustler::env::{OwnedEnv, SavedTerm};
 use rustler::types::atom;
 use rustler::types::list::ListIterator;
 use rustler::types::LocalPid;
-use rustler::{Atom, Encoder, Env, NifResult, Term};
+use rustler::{Atom, Encoder, Env, NifResult, Reference, Term};
 use std::thread;
 
 // Send a message to several PIDs.
@@ -15,6 +16,52 @@ pub fn send_all<'a>(env: Env<'a>, pids: Vec<LocalPid>, msg: Term<'a>) -> Term<'a
     msg
 }
 
+// Send a message to a PID, returning whether it was delivered.
+#[rustler::nif]
+pub fn send_to<'a>(env: Env<'a>, pid: LocalPid, msg: Term<'a>) -> bool {
+    env.send(&pid, msg)
+}
+
+#[rustler::nif]
+pub fn current_process_alive(env: Env) -> bool {
+    env.is_current_process_alive()
+}
+
+#[rustler::nif]
+pub fn process_alive(env: Env, pid: LocalPid) -> bool {
+    env.is_process_alive(&pid)
+}
+
+#[rustler::nif]
+pub fn whereis_pid(env: Env, name: Atom) -> Option<LocalPid> {
+    env.whereis(name)
+}
+
+// Send a message to the process registered as `name`, from the NIF and from a new thread.
+#[rustler::nif]
+pub fn send_to_name<'a>(env: Env<'a>, name: Atom, msg: Term<'a>) -> bool {
+    let owned_env = OwnedEnv::new();
+    let saved = owned_env.save(msg);
+
+    thread::spawn(move || {
+        let mut owned_env = owned_env;
+        owned_env.send_to_name_and_clear(name, |env| saved.load(env));
+    });
+
+    env.send_to_name(name, msg)
+}
+
+// Send a message prebuilt in an `OwnedEnv` to several PIDs, returning how many were alive.
+#[rustler::nif]
+pub fn send_from_owned_env<'a>(env: Env<'a>, pids: Vec<LocalPid>, msg: Term<'a>) -> usize {
+    let owned_env = OwnedEnv::new();
+    let saved_msg = owned_env.save(msg);
+
+    pids.iter()
+        .filter(|pid| owned_env.send_from(env, pid, |env| saved_msg.load(env)))
+        .count()
+}
+
 #[rustler::nif]
 pub fn sublists<'a>(env: Env<'a>, list: Term<'a>) -> NifResult<Atom> {
     // This is a "threaded NIF": it spawns a thread that sends a message back
@@ -66,3 +113,40 @@ pub fn sublists<'a>(env: Env<'a>, list: Term<'a>) -> NifResult<Atom> {
 
     Ok(atom::ok())
 }
+
+// Stream `0..count` to the calling process, tagged with a new reference, then the whole range again
+// as a single batch.
+#[rustler::nif]
+pub fn channel_stream(env: Env, count: i64) -> Reference {
+    let reference = Reference::new(env);
+    let mut sender = Sender::new(env.pid()).tagged(reference);
+
+    thread::spawn(move || {
+        for i in 0..count {
+            sender.send(i).unwrap();
+        }
+        sender.send_batch((0..count).collect()).unwrap();
+    });
+
+    reference
+}
+
+// Stream `0..count` to the calling process with at most `capacity` unacknowledged messages.
+#[rustler::nif]
+pub fn channel_bounded(env: Env, count: i64, capacity: usize) -> Acknowledger {
+    let (mut sender, acknowledger) = channel::bounded(env.pid(), capacity);
+
+    thread::spawn(move || {
+        for i in 0..count {
+            sender.send(i).unwrap();
+        }
+    });
+
+    acknowledger
+}
+
+#[rustler::nif]
+pub fn channel_ack(acknowledger: Acknowledger) -> Atom {
+    acknowledger.ack();
+    atom::ok()
+}
@@ -0,0 +1,27 @@
+use rustler::Env;
+
+#[rustler::nif]
+pub fn nif_args_tuple(pair: (i64, String)) -> String {
+    format!("{}:{}", pair.0, pair.1)
+}
+
+#[rustler::nif]
+pub fn nif_args_pattern((a, b): (i64, i64), [c, d]: [i64; 2]) -> i64 {
+    a * b + c * d
+}
+
+#[rustler::nif]
+pub fn nif_args_array(values: [u8; 4]) -> u32 {
+    values.iter().map(|&value| u32::from(value)).sum()
+}
+
+#[rustler::nif]
+pub fn nif_args_slice(bytes: &[u8]) -> usize {
+    bytes.len()
+}
+
+#[rustler::nif]
+pub fn nif_args_mut(_env: Env, mut n: i64, _: i64) -> i64 {
+    n += 1;
+    n
+}
@@ -2,3 +2,13 @@
 pub fn can_rename() -> bool {
     true
 }
+
+#[rustler::nif(defaults)]
+pub fn nif_attrs_defaults(a: i64, b: Option<i64>, c: Option<i64>) -> i64 {
+    a + b.unwrap_or(10) + c.unwrap_or(100)
+}
+
+#[rustler::nif]
+pub fn nif_attrs_marked_default(a: Option<i64>, #[default] b: Option<i64>) -> (Option<i64>, i64) {
+    (a, b.unwrap_or(-1))
+}
@@ -1,4 +1,4 @@
-use rustler::{Env, ResourceArc};
+use rustler::{Env, Error, NifResult, ResourceArc, ResourceLock};
 use std::sync::RwLock;
 
 pub struct TestResource {
@@ -69,3 +69,47 @@ pub fn resource_make_immutable(u: u32) -> ResourceArc<ImmutableResource> {
 pub fn resource_immutable_count() -> u32 {
     COUNT.load(Ordering::SeqCst) as u32
 }
+
+pub struct Counter {
+    value: i64,
+}
+
+#[rustler::resource_impl]
+impl Counter {
+    pub fn new(value: i64) -> Self {
+        Counter { value }
+    }
+
+    pub fn try_new(value: i64) -> NifResult<Self> {
+        if value < 0 {
+            return Err(Error::BadArg);
+        }
+        Ok(Counter { value })
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value
+    }
+
+    pub fn add(&mut self, by: i64) -> i64 {
+        self.value += by;
+        self.value
+    }
+
+    #[rustler(name = "counter_reset")]
+    pub fn clear(&mut self) {
+        self.reset();
+    }
+
+    #[rustler(skip)]
+    pub fn reset(&mut self) {
+        self.value = 0;
+    }
+}
+
+#[rustler::nif]
+pub fn counter_double(counter: ResourceArc<ResourceLock<Counter>>) -> i64 {
+    let mut counter = counter.write();
+    counter.value *= 2;
+    counter.value
+}
@@ -0,0 +1,13 @@
+use rustler::stubs::{Elixir, Erlang};
+
+#[rustler::nif]
+pub fn stubs_elixir() -> String {
+    Elixir::new()
+        .preamble("use Rustler, otp_app: :rustler_test, crate: :rustler_test")
+        .generate(&crate::RUSTLER_NIF_MODULE)
+}
+
+#[rustler::nif]
+pub fn stubs_erlang() -> String {
+    Erlang::new().generate(&crate::RUSTLER_NIF_MODULE)
+}
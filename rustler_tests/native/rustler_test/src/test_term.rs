@@ -1,5 +1,7 @@
-use rustler::{Atom, Term};
+use rustler::types::binary::{Binary, OwnedBinary};
+use rustler::{etf, Atom, Env, Term, Value};
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::io::Write;
 
 mod atoms {
@@ -24,7 +26,87 @@ pub fn term_eq<'a>(a: Term<'a>, b: Term<'a>) -> bool {
 
 #[rustler::nif]
 pub fn term_cmp<'a>(a: Term<'a>, b: Term<'a>) -> Atom {
-    match Ord::cmp(&a, &b) {
+    ordering_atom(Ord::cmp(&a, &b))
+}
+
+#[rustler::nif]
+pub fn value_echo(value: Value) -> Value {
+    value
+}
+
+#[rustler::nif]
+pub fn value_cmp(a: Value, b: Value) -> Atom {
+    ordering_atom(a.cmp(&b))
+}
+
+#[rustler::nif]
+pub fn value_sort(mut values: Vec<Value>) -> Vec<Value> {
+    values.sort();
+    values
+}
+
+#[rustler::nif]
+pub fn etf_encode(value: Value, compressed: bool) -> OwnedBinary {
+    let bytes = if compressed {
+        etf::encode_compressed(&value, 6)
+    } else {
+        etf::encode(&value)
+    };
+    let mut binary = OwnedBinary::new(bytes.len()).unwrap();
+    binary.as_mut_slice().copy_from_slice(&bytes);
+    binary
+}
+
+#[rustler::nif]
+pub fn etf_decode(bytes: Binary) -> Result<Value, String> {
+    etf::decode(bytes.as_slice()).map_err(|err| err.to_string())
+}
+
+#[rustler::nif]
+pub fn term_macro<'a>(env: Env<'a>, name: String, count: i64) -> Term<'a> {
+    let numbers = [3, 4];
+    rustler::term!(env, {
+        :ok,
+        %{
+            name: name,
+            tags: [:a, :"hello world", key: -1, r#type: true],
+            "count" => count + 1,
+            1.5 => [],
+            :empty => {},
+            :list => (numbers.iter().map(|x| x * 2).collect::<Vec<i64>>()),
+            :generic => HashMap::<String, i64>::new(),
+            :missing => nil,
+        }
+    })
+}
+
+#[rustler::nif]
+pub fn term_macro_struct(env: Env, first: i64) -> Term {
+    rustler::term!(env, %Range{first: first, last: 10, step: 1})
+}
+
+#[rustler::nif]
+pub fn match_term_example(term: Term) -> String {
+    rustler::match_term!(term, {
+        {:ok, count: i64} if count > 0 => format!("positive {}", count),
+        {:ok, _} => "ok".to_string(),
+        {:error, reason: String} => format!("error {}", reason),
+        {name: String, :ok} => format!("ok {}", name),
+        [first: i64, second: i64 | rest] => {
+            format!("list {} {} {}", first, second, rest.list_length().unwrap_or(0))
+        }
+        [] => "empty".to_string(),
+        %{"name" => name: String, age: age: u32} => format!("{} is {}", name, age),
+        %{:type => :point, x: x: f64} => format!("point {}", x),
+        -1 => "minus one".to_string(),
+        "hello" => "greeting".to_string(),
+        true => "true".to_string(),
+        other => format!("other {:?}", other.get_type()),
+    })
+}
+
+fn ordering_atom(ordering: Ordering) -> Atom {
+    match ordering {
         Ordering::Equal => atoms::equal(),
         Ordering::Less => atoms::less(),
         Ordering::Greater => atoms::greater(),
@@ -1,9 +1,20 @@
-use rustler::thread;
+use rustler::thread::{self, JobHandle, PoolConfig};
 use rustler::types::atom;
-use rustler::{Atom, Encoder, Env};
+use rustler::{Atom, Encoder, Env, Reference, Term};
+use std::time::Duration;
+
+pub fn on_load() -> bool {
+    // Two threads and a single queued job, so that tests can overload the pool.
+    let config = PoolConfig::new()
+        .threads(2)
+        .queue_len(1)
+        .name("rustler_test")
+        .vm_threads(true);
+    thread::configure_pool(config)
+}
 
 #[rustler::nif]
-pub fn threaded_fac(env: Env, n: u64) -> Atom {
+pub fn threaded_fac(env: Env, n: u64) -> Reference {
     // Multiply two numbers; panic on overflow. In Rust, the `*` operator wraps (rather than
     // panicking) in release builds. A test depends on this panicking, so we make sure it panics in
     // all builds. The test also checks the panic message.
@@ -14,19 +25,49 @@ pub fn threaded_fac(env: Env, n: u64) -> Atom {
     thread::spawn::<thread::ThreadSpawner, _>(env, move |thread_env| {
         let result = (1..=n).fold(1, mul);
         result.encode(thread_env)
-    });
-
-    atom::ok()
+    })
 }
 
 #[rustler::nif]
-pub fn threaded_sleep(env: Env, msec: u64) -> Atom {
+pub fn threaded_sleep(env: Env, msec: u64) -> Reference {
     let q = msec / 1000;
     let r = (msec % 1000) as u32;
     thread::spawn::<thread::ThreadSpawner, _>(env, move |thread_env| {
         std::thread::sleep(std::time::Duration::new(q as u64, r * 1_000_000));
         msec.encode(thread_env)
-    });
+    })
+}
 
+#[rustler::nif]
+pub fn pooled_sleep(env: Env, msec: u64) -> Reference {
+    thread::spawn::<thread::PoolSpawner, _>(env, move |thread_env| {
+        std::thread::sleep(std::time::Duration::from_millis(msec));
+        msec.encode(thread_env)
+    })
+}
+
+// Sum a list, sleeping between elements until the job is cancelled.
+#[rustler::nif]
+pub fn threaded_sum<'a>(env: Env<'a>, numbers: Term<'a>, msec: u64) -> (Reference<'a>, JobHandle) {
+    thread::spawn_with::<thread::ThreadSpawner, _>(
+        env,
+        numbers,
+        move |thread_env, numbers, token| {
+            let mut sum = 0;
+            for number in numbers.decode::<Vec<i64>>().unwrap() {
+                if token.is_cancelled() {
+                    return atom::error().encode(thread_env);
+                }
+                std::thread::sleep(Duration::from_millis(msec));
+                sum += number;
+            }
+            sum.encode(thread_env)
+        },
+    )
+}
+
+#[rustler::nif]
+pub fn threaded_cancel(handle: JobHandle) -> Atom {
+    handle.cancel();
     atom::ok()
 }
@@ -0,0 +1,14 @@
+defmodule AutoRegisterTest do
+  use ExUnit.Case
+
+  test "nifs are exported without an explicit list" do
+    assert "world" == AutoRegister.hello()
+    assert 3 == AutoRegister.add(1, 2)
+    assert 3 == AutoRegister.dirty_add(1, 2)
+  end
+
+  test "registered nifs are listed in the module metadata" do
+    assert ["add/2", "dirty_add/2", "hello/0", "registered_names/0"] ==
+             AutoRegister.registered_names()
+  end
+end
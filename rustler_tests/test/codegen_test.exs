@@ -2,11 +2,26 @@ defmodule AddStruct do
   defstruct lhs: 0, rhs: 0
 end
 
+defmodule ExtraStruct do
+  defstruct count: 0, name: nil, tags: []
+end
+
+defmodule GenericStruct do
+  defstruct value: nil
+end
+
 defmodule AddRecord do
   import Record
   defrecord :record, lhs: 1, rhs: 2
 end
 
+defmodule HrlRecord do
+  import Record
+
+  defrecord :hrl_record,
+            Record.extract(:hrl_record, from: "native/rustler_test/include/records.hrl")
+end
+
 defmodule NewtypeRecord do
   import Record
   defrecord :newtype, a: 1
@@ -48,6 +63,33 @@ defmodule RustlerTest.CodegenTest do
         assert value == RustlerTest.map_echo(value)
       end
     end
+
+    test "with string keys" do
+      value = %{"id" => 1, "displayName" => "Jane"}
+      assert value == RustlerTest.string_key_map_echo(value)
+    end
+
+    test "with invalid string keyed map" do
+      assert_raise ErlangError, "Erlang error: \"Could not decode field \\\"id\\\" on %{}\"", fn ->
+        RustlerTest.string_key_map_echo(%{"id" => "invalid", "displayName" => "Jane"})
+      end
+
+      assert_raise ArgumentError, fn ->
+        RustlerTest.string_key_map_echo(%{id: 1, displayName: "Jane"})
+      end
+    end
+
+    test "with renamed key" do
+      value = %{type: "circle"}
+      assert value == RustlerTest.renamed_key_map_echo(value)
+    end
+
+    test "with extra keys" do
+      value = %{:id => 1, :name => "a", "other" => [1, 2]}
+      assert value == RustlerTest.extra_map_echo(value)
+      assert 2 == RustlerTest.extra_map_count(value)
+      assert 0 == RustlerTest.extra_map_count(%{id: 1})
+    end
   end
 
   describe "struct" do
@@ -57,6 +99,11 @@ defmodule RustlerTest.CodegenTest do
       assert :invalid_struct == RustlerTest.struct_echo(DateTime.utc_now())
     end
 
+    test "with extra keys" do
+      value = %ExtraStruct{count: 1, name: "counter", tags: [:a]}
+      assert %ExtraStruct{value | count: 2} == RustlerTest.extra_struct_incr(value)
+    end
+
     test "with invalid struct" do
       value = %AddStruct{lhs: "lhs", rhs: 123}
 
@@ -90,6 +137,13 @@ defmodule RustlerTest.CodegenTest do
 
       assert_raise ErlangError, message, fn -> RustlerTest.record_echo(value) end
     end
+
+    test "defined in a header" do
+      require HrlRecord
+      value = HrlRecord.hrl_record()
+      assert {:hrl_record, 'a, b % c', [:one, :two], {:default, ?%}, <<1, 2, 3>>} == value
+      assert value == RustlerTest.hrl_record_echo(value)
+    end
   end
 
   test "unit enum transcoder" do
@@ -98,6 +152,21 @@ defmodule RustlerTest.CodegenTest do
     assert :invalid_variant == RustlerTest.unit_enum_echo(:somethingelse)
   end
 
+  test "unit enum with renamed variants" do
+    assert :FOO_BAR == RustlerTest.renamed_unit_enum_echo(:FOO_BAR)
+    assert Baz == RustlerTest.renamed_unit_enum_echo(Baz)
+    assert :UNKNOWN == RustlerTest.renamed_unit_enum_echo(:foo_bar)
+    assert :UNKNOWN == RustlerTest.renamed_unit_enum_echo(:somethingelse)
+    assert_raise ArgumentError, fn -> RustlerTest.renamed_unit_enum_echo("FOO_BAR") end
+  end
+
+  test "unit enum with integer representation" do
+    assert 200 == RustlerTest.integer_unit_enum_echo(200)
+    assert 418 == RustlerTest.integer_unit_enum_echo(418)
+    assert :invalid_variant == RustlerTest.integer_unit_enum_echo(201)
+    assert_raise ArgumentError, fn -> RustlerTest.integer_unit_enum_echo(:ok) end
+  end
+
   test "untagged enum transcoder" do
     assert 123 == RustlerTest.untagged_enum_echo(123)
     assert "Hello" == RustlerTest.untagged_enum_echo("Hello")
@@ -179,6 +248,59 @@ defmodule RustlerTest.CodegenTest do
                  end
   end
 
+  describe "keyword list" do
+    test "transcoder" do
+      value = [timeout: 5000, retries: 1, verbose: true, name: "worker"]
+      assert value == RustlerTest.keyword_list_echo(value)
+    end
+
+    test "with missing optional keys" do
+      assert [timeout: 5000, retries: 3, verbose: false, name: nil] ==
+               RustlerTest.keyword_list_echo(timeout: 5000)
+    end
+
+    test "with unknown and repeated keys" do
+      assert [timeout: 1, retries: 3, verbose: false, name: nil] ==
+               RustlerTest.keyword_list_echo(timeout: 1, other: :ignored, timeout: 2)
+
+      # The first value wins, and the later ones are not decoded.
+      assert [timeout: 1, retries: 3, verbose: false, name: nil] ==
+               RustlerTest.keyword_list_echo(timeout: 1, timeout: "invalid")
+    end
+
+    test "from a proplist" do
+      assert [timeout: 1, retries: 3, verbose: true, name: nil] ==
+               RustlerTest.keyword_list_echo([:verbose, {:timeout, 1}])
+    end
+
+    test "with missing key" do
+      assert_raise ErlangError, "Erlang error: \"Missing key :timeout in keyword list\"", fn ->
+        RustlerTest.keyword_list_echo(retries: 1)
+      end
+    end
+
+    test "with invalid value" do
+      assert_raise ErlangError,
+                   "Erlang error: \"Could not decode key :timeout in keyword list\"",
+                   fn -> RustlerTest.keyword_list_echo(timeout: "invalid") end
+    end
+
+    test "with unknown key denied" do
+      assert [timeout: 1] == RustlerTest.strict_keyword_list_echo(timeout: 1)
+
+      assert_raise ErlangError, "Erlang error: \"Unknown key :other in keyword list\"", fn ->
+        RustlerTest.strict_keyword_list_echo(timeout: 1, other: 2)
+      end
+    end
+
+    test "keyword decoder" do
+      assert 1 == RustlerTest.keyword_get([a: 1, b: 2, a: 3], :a)
+      assert true == RustlerTest.keyword_get([:a], :a)
+      assert nil == RustlerTest.keyword_get([a: 1], :b)
+      assert_raise ArgumentError, fn -> RustlerTest.keyword_get([{"a", 1}], :a) end
+    end
+  end
+
   test "reserved keywords" do
     assert %{override: 1} == RustlerTest.reserved_keywords_type_echo(%{override: 1})
 
@@ -188,4 +310,49 @@ defmodule RustlerTest.CodegenTest do
     assert {1} == RustlerTest.reserved_keywords_type_echo({1})
     assert {:record, 1} == RustlerTest.reserved_keywords_type_echo({:record, 1})
   end
+
+  describe "generics" do
+    test "map with type parameter" do
+      value = %{items: [1, 2, 3], total: 3}
+      assert value == RustlerTest.generic_map_echo(value)
+
+      assert_raise ErlangError, "Erlang error: \"Could not decode field :items on %{}\"", fn ->
+        RustlerTest.generic_map_echo(%{items: ["a"], total: 1})
+      end
+    end
+
+    test "map with lifetime and type parameter" do
+      value = %{name: "answer", value: 42}
+      assert value == RustlerTest.generic_borrowed_echo(value)
+    end
+
+    test "struct with type parameter" do
+      value = %GenericStruct{value: "hello"}
+      assert value == RustlerTest.generic_struct_echo(value)
+    end
+
+    test "tuple with multiple type parameters" do
+      assert {1, "two"} == RustlerTest.generic_tuple_echo({1, "two"})
+    end
+
+    test "record with nested generic type" do
+      assert {:wrapper, {1, 2}} == RustlerTest.generic_record_echo({:wrapper, {1, 2}})
+    end
+
+    test "untagged enum with type parameters" do
+      assert 1 == RustlerTest.generic_untagged_enum_echo(1)
+      assert "right" == RustlerTest.generic_untagged_enum_echo("right")
+      assert :invalid_variant == RustlerTest.generic_untagged_enum_echo(:neither)
+    end
+
+    test "tuple with const parameter" do
+      assert {[1, 2, 3, 4]} == RustlerTest.const_generic_echo({[1, 2, 3, 4]})
+      assert_raise ErlangError, fn -> RustlerTest.const_generic_echo({[1, 2, 3]}) end
+    end
+
+    test "explicit bounds" do
+      value = %{values: [true, false]}
+      assert value == RustlerTest.generic_bounded_echo(value)
+    end
+  end
 end
@@ -8,4 +8,13 @@ defmodule RustlerTest.DirtyTest do
   test "dirty cpu" do
     RustlerTest.dirty_cpu()
   end
+
+  test "dirty nifs can tell when their caller exits" do
+    test = self()
+    pid = spawn(fn -> RustlerTest.dirty_wait_for_exit(test) end)
+    refute_receive :caller_exited, 100
+
+    Process.exit(pid, :kill)
+    assert_receive :caller_exited, 1000
+  end
 end
@@ -57,4 +57,74 @@ defmodule RustlerTest.EnvTest do
                ]
     end
   end
+
+  test "send reports whether the process is alive" do
+    pid = spawn(fn -> :ok end)
+    ref = Process.monitor(pid)
+    assert_receive {:DOWN, ^ref, :process, ^pid, :normal}
+
+    assert RustlerTest.send_to(self(), :hello)
+    assert_receive :hello
+    refute RustlerTest.send_to(pid, :hello)
+  end
+
+  test "process liveness" do
+    assert RustlerTest.current_process_alive()
+    assert RustlerTest.process_alive(self())
+
+    pid = spawn(fn -> :ok end)
+    ref = Process.monitor(pid)
+    assert_receive {:DOWN, ^ref, :process, ^pid, :normal}
+    refute RustlerTest.process_alive(pid)
+  end
+
+  test "send messages to registered names" do
+    Process.register(self(), :rustler_test_registered)
+    assert self() == RustlerTest.whereis_pid(:rustler_test_registered)
+    assert nil == RustlerTest.whereis_pid(:rustler_test_unregistered)
+
+    assert RustlerTest.send_to_name(:rustler_test_registered, :hello)
+    assert_receive :hello
+    assert_receive :hello
+
+    refute RustlerTest.send_to_name(:rustler_test_unregistered, :hello)
+    refute_receive :hello
+  end
+
+  test "send messages from an OwnedEnv within a NIF call" do
+    pid = spawn(fn -> :ok end)
+    ref = Process.monitor(pid)
+    assert_receive {:DOWN, ^ref, :process, ^pid, :normal}
+
+    msg = {:payload, [1, 2, 3], "binary"}
+    assert 2 == RustlerTest.send_from_owned_env([self(), pid, self()], msg)
+    assert_receive ^msg
+    assert_receive ^msg
+  end
+
+  test "channels" do
+    ref = RustlerTest.channel_stream(3)
+    assert is_reference(ref)
+
+    for i <- 0..2 do
+      assert_receive {^ref, ^i}
+    end
+
+    assert_receive {^ref, [0, 1, 2]}
+  end
+
+  test "bounded channels" do
+    acknowledger = RustlerTest.channel_bounded(4, 2)
+
+    assert_receive 0
+    assert_receive 1
+    refute_receive 2, 100
+
+    :ok = RustlerTest.channel_ack(acknowledger)
+    assert_receive 2
+    refute_receive 3, 100
+
+    :ok = RustlerTest.channel_ack(acknowledger)
+    assert_receive 3
+  end
 end
@@ -0,0 +1,26 @@
+defmodule NifArgsTest do
+  use ExUnit.Case
+
+  test "tuple arguments" do
+    assert RustlerTest.nif_args_tuple({1, "one"}) == "1:one"
+    assert_raise ArgumentError, fn -> RustlerTest.nif_args_tuple({1, 2}) end
+  end
+
+  test "pattern arguments" do
+    assert RustlerTest.nif_args_pattern({2, 3}, [4, 5]) == 26
+  end
+
+  test "array arguments" do
+    assert RustlerTest.nif_args_array([1, 2, 3, 4]) == 10
+    assert_raise ArgumentError, fn -> RustlerTest.nif_args_array([1, 2, 3]) end
+  end
+
+  test "slice arguments" do
+    assert RustlerTest.nif_args_slice(<<1, 2, 3>>) == 3
+    assert_raise ArgumentError, fn -> RustlerTest.nif_args_slice([1, 2, 3]) end
+  end
+
+  test "mutable and ignored arguments" do
+    assert RustlerTest.nif_args_mut(1, 2) == 2
+  end
+end
@@ -4,4 +4,16 @@ defmodule NifAttrsTest do
   test "can rename a NIF with an attribute" do
     assert RustlerTest.nif_attrs_can_rename()
   end
+
+  test "trailing options are exported as additional arities" do
+    assert RustlerTest.nif_attrs_defaults(1) == 111
+    assert RustlerTest.nif_attrs_defaults(1, 2) == 103
+    assert RustlerTest.nif_attrs_defaults(1, 2, 3) == 6
+    assert RustlerTest.nif_attrs_defaults(1, nil, 3) == 14
+  end
+
+  test "only arguments marked as default can be left out" do
+    assert RustlerTest.nif_attrs_marked_default(nil) == {nil, -1}
+    assert RustlerTest.nif_attrs_marked_default(1, 2) == {1, 2}
+  end
 end
@@ -35,4 +35,19 @@ defmodule RustlerTest.ResourceTest do
     # Erlang's exact GC should have cleaned all that up.
     assert RustlerTest.resource_immutable_count() == 0
   end
+
+  test "resource_impl methods" do
+    counter = RustlerTest.counter_new(1)
+    assert is_reference(counter)
+    assert RustlerTest.counter_get(counter) == 1
+    assert RustlerTest.counter_add(counter, 2) == 3
+    assert RustlerTest.counter_double(counter) == 6
+    assert RustlerTest.counter_reset(counter) == {}
+    assert RustlerTest.counter_get(counter) == 0
+  end
+
+  test "resource_impl constructor returning a result" do
+    assert RustlerTest.counter_get(RustlerTest.counter_try_new(5)) == 5
+    assert_raise ArgumentError, fn -> RustlerTest.counter_try_new(-1) end
+  end
 end
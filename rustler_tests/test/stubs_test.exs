@@ -0,0 +1,45 @@
+defmodule RustlerTest.StubsTest do
+  use ExUnit.Case, async: true
+
+  test "elixir stubs match the loaded NIFs" do
+    source = RustlerTest.stubs_elixir()
+    {:ok, {:defmodule, _, [_name, [do: {:__block__, _, body}]]}} = Code.string_to_quoted(source)
+
+    stubs =
+      for {:def, _, [{name, _, args}, _]} <- body do
+        {name, length(args || [])}
+      end
+
+    for {name, arity} <- stubs do
+      assert function_exported?(RustlerTest, name, arity), "#{name}/#{arity} is not a NIF"
+    end
+
+    assert {:add_u32, 2} in stubs
+    assert {:nif_attrs_can_rename, 0} in stubs
+  end
+
+  test "elixir specs are derived from the rust types" do
+    source = RustlerTest.stubs_elixir()
+
+    assert source =~
+             "@spec add_u32(a :: non_neg_integer(), b :: non_neg_integer()) :: non_neg_integer()"
+
+    assert source =~ "@spec option_inc(opt :: float() | nil) :: float() | nil"
+
+    assert source =~
+             "@spec result_to_int(res :: {:ok, boolean()} | {:error, String.t()}) :: {:ok, non_neg_integer()} | {:error, String.t()}"
+
+    assert source =~ "def add_u32(_a, _b), do: :erlang.nif_error(:nif_not_loaded)"
+    assert source =~ "@spec nif_args_mut(n :: integer(), arg1 :: integer()) :: integer()"
+  end
+
+  test "erlang stubs" do
+    source = RustlerTest.stubs_erlang()
+
+    assert source =~ "-module('Elixir.RustlerTest')."
+    assert source =~ "add_u32/2"
+    assert source =~
+             "-spec add_u32(A :: non_neg_integer(), B :: non_neg_integer()) -> non_neg_integer()."
+    assert source =~ "add_u32(_A, _B) ->\n    erlang:nif_error(nif_not_loaded)."
+  end
+end
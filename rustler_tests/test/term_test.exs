@@ -40,4 +40,135 @@ defmodule RustlerTest.TermTest do
     # Other term types
     assert RustlerTest.term_cmp(5, :test) == :less
   end
+
+  test "value roundtrip" do
+    ref = make_ref()
+    fun = fn x -> x end
+
+    values = [
+      :atom,
+      42,
+      -(2 ** 100),
+      2 ** 64,
+      1.5,
+      "binary",
+      [],
+      [1, [2, 3]],
+      [1 | 2],
+      {:ok, self(), ref},
+      %{"key" => [a: 1], 1 => 1.0},
+      fun
+    ]
+
+    for value <- values do
+      assert value === RustlerTest.value_echo(value)
+    end
+  end
+
+  test "value ordering" do
+    values = [
+      "b",
+      "a",
+      [1 | 2],
+      [1],
+      [],
+      %{a: 1},
+      {1, 2},
+      {3},
+      self(),
+      make_ref(),
+      :b,
+      :a,
+      2 ** 100,
+      -(2 ** 100),
+      1.5,
+      -3
+    ]
+
+    assert Enum.sort(values) == RustlerTest.value_sort(Enum.shuffle(values))
+
+    assert RustlerTest.value_cmp(1, 2) == :less
+    assert RustlerTest.value_cmp(1.0, 1) == :greater
+    assert RustlerTest.value_cmp(2 ** 64, 2.0 ** 64) == :less
+    assert RustlerTest.value_cmp(2 ** 64 + 1, 2.0 ** 64) == :greater
+    assert RustlerTest.value_cmp({:a, [1]}, {:a, [1]}) == :equal
+  end
+
+  test "external term format" do
+    ref = make_ref()
+    fun = fn x -> x end
+
+    values = [
+      :atom,
+      :"ünicode",
+      42,
+      -1,
+      2 ** 40,
+      -(2 ** 100),
+      1.5,
+      "binary",
+      [],
+      'charlist',
+      [1, [2, 3]],
+      [1 | 2],
+      {:ok, self(), ref, Port.list() |> hd()},
+      Tuple.duplicate(0, 300),
+      %{"key" => [a: 1], 1 => 1.0},
+      fun,
+      &Enum.map/2,
+      List.duplicate("compressible", 100)
+    ]
+
+    for value <- values do
+      assert :erlang.binary_to_term(RustlerTest.etf_encode(value, false)) === value
+      assert :erlang.binary_to_term(RustlerTest.etf_encode(value, true)) === value
+      assert RustlerTest.etf_decode(:erlang.term_to_binary(value)) === {:ok, value}
+      assert RustlerTest.etf_decode(:erlang.term_to_binary(value, [:compressed])) === {:ok, value}
+      assert RustlerTest.etf_decode(:erlang.term_to_binary(value, minor_version: 0)) === {:ok, value}
+    end
+
+    assert RustlerTest.etf_encode(2 ** 64, false) == :erlang.term_to_binary(2 ** 64)
+
+    assert {:error, _} = RustlerTest.etf_decode(<<131, 97>>)
+    assert {:error, _} = RustlerTest.etf_decode(<<131, 97, 1, 2>>)
+    assert {:error, _} = RustlerTest.etf_decode(:erlang.term_to_binary(<<1::3>>))
+  end
+
+  test "term macro" do
+    assert {:ok, map} = RustlerTest.term_macro("Joe", 41)
+
+    assert map == %{
+             :name => "Joe",
+             :tags => [:a, :"hello world", key: -1, type: true],
+             "count" => 42,
+             1.5 => [],
+             :empty => {},
+             :list => [6, 8],
+             :generic => %{},
+             :missing => nil
+           }
+
+    assert 1..10 == RustlerTest.term_macro_struct(1)
+  end
+
+  test "match_term macro" do
+    assert RustlerTest.match_term_example({:ok, 5}) == "positive 5"
+    assert RustlerTest.match_term_example({:ok, -5}) == "ok"
+    assert RustlerTest.match_term_example({:ok, :value}) == "ok"
+    assert RustlerTest.match_term_example({:error, "failed"}) == "error failed"
+    assert RustlerTest.match_term_example({:error, :failed}) == "other Tuple"
+    assert RustlerTest.match_term_example({"joe", :ok}) == "ok joe"
+    assert RustlerTest.match_term_example({"joe", :error}) == "other Tuple"
+    assert RustlerTest.match_term_example([1, 2, 3, 4]) == "list 1 2 2"
+    assert RustlerTest.match_term_example([1, 2]) == "list 1 2 0"
+    assert RustlerTest.match_term_example([1]) == "other List"
+    assert RustlerTest.match_term_example([]) == "empty"
+    assert RustlerTest.match_term_example(%{"name" => "Joe", :age => 42, :x => 1}) == "Joe is 42"
+    assert RustlerTest.match_term_example(%{type: :point, x: 1.5}) == "point 1.5"
+    assert RustlerTest.match_term_example(%{type: :point}) == "other Map"
+    assert RustlerTest.match_term_example(-1) == "minus one"
+    assert RustlerTest.match_term_example("hello") == "greeting"
+    assert RustlerTest.match_term_example(true) == "true"
+    assert RustlerTest.match_term_example(1.0) == "other Number"
+  end
 end
@@ -2,15 +2,15 @@ defmodule RustlerTest.ThreadTest do
   use ExUnit.Case, async: true
 
   test "simple threaded nif" do
-    RustlerTest.threaded_fac(19)
+    ref = RustlerTest.threaded_fac(19)
 
     receive do
-      x -> assert x == 121_645_100_408_832_000
+      x -> assert x == {ref, 121_645_100_408_832_000}
     end
   end
 
   test "sleeping nif" do
-    RustlerTest.threaded_sleep(200)
+    ref = RustlerTest.threaded_sleep(200)
 
     receive do
       _ -> raise "timeout_expected"
@@ -20,7 +20,7 @@ defmodule RustlerTest.ThreadTest do
     end
 
     receive do
-      x -> assert x == 200
+      x -> assert x == {ref, 200}
     after
       1000 ->
         raise "message_expected"
@@ -30,13 +30,13 @@ defmodule RustlerTest.ThreadTest do
   test "many threads" do
     # Spawn 50 threads.
     times = Enum.map(1..50, fn x -> x * 10 end)
-    Enum.map(times, &RustlerTest.threaded_sleep/1)
+    refs = Enum.map(times, &RustlerTest.threaded_sleep/1)
 
     # Wait for them all to respond.
     results =
-      Enum.map(times, fn _ ->
+      Enum.map(refs, fn ref ->
         receive do
-          y -> y
+          {^ref, y} -> y
         after
           1000 ->
             :timeout
@@ -44,16 +44,41 @@ defmodule RustlerTest.ThreadTest do
       end)
 
     # The OS scheduler guarantees virtually nothing about sleep().
-    # Answers may arrive out of order.
-    assert Enum.sort(results) == times
+    # Answers may arrive out of order, but each matches its reference.
+    assert results == times
   end
 
   test "thread panic" do
     # overflows u64 and panics
-    RustlerTest.threaded_fac(100)
+    ref = RustlerTest.threaded_fac(100)
 
     receive do
-      msg -> assert msg == {:error, "threaded_fac: integer overflow"}
+      msg -> assert msg == {ref, {:error, "threaded_fac: integer overflow"}}
     end
   end
+
+  test "pool rejects jobs when overloaded" do
+    # The pool has two threads and a queue of one job.
+    refs = for _ <- 1..4, do: RustlerTest.pooled_sleep(200)
+
+    assert_receive {_, {:error, :overloaded}}
+    assert_receive {_, 200}, 1000
+    assert_receive {_, 200}, 1000
+    assert_receive {_, 200}, 1000
+    refute_receive _, 100
+    assert length(Enum.uniq(refs)) == 4
+  end
+
+  test "threaded nif with arguments" do
+    {ref, _handle} = RustlerTest.threaded_sum([1, 2, 3], 0)
+    assert_receive {^ref, 6}
+  end
+
+  test "cancelling a threaded nif" do
+    {ref, handle} = RustlerTest.threaded_sum(Enum.to_list(1..100), 10)
+    :ok = RustlerTest.threaded_cancel(handle)
+
+    assert_receive {^ref, {:error, :cancelled}}, 1000
+    refute_receive _, 100
+  end
 end